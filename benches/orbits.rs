@@ -0,0 +1,54 @@
+//! Compares how many `Orbit`s per second the scalar `Orbit` iterator and the batched
+//! `escape_batch()` kernel get through, when testing uniformly random samples for escaping, which is
+//! what `MHOrbits` spends most of its time on.
+//!
+//! Run with `cargo bench --bench orbits`.
+
+extern crate rand;
+
+use butterbrot_rs::math::*;
+use rand::Rng;
+use std::time::Instant;
+
+/// runs `f` on the `samples` and returns the number of `Orbit`s per second, and whatever `f` made
+/// of them (so the compiler can't just optimize the work away)
+fn measure<F: Fn(&[Complex]) -> usize>(samples:&[Complex], f:F) -> (f64, usize) {
+
+    let timestamp = Instant::now();
+    let escaped = f(samples);
+    let seconds = timestamp.elapsed().as_secs_f64();
+
+    (samples.len() as f64 / seconds, escaped)
+
+}
+
+fn main() {
+
+    let mut rng = rand::thread_rng();
+    let fractal = Fractal::mandelbrot();
+
+    let count = 200_000;
+    let samples: Vec<Complex> = (0..count)
+        .map(|_| Complex::new(rng.gen_range(-2f64, 2f64), rng.gen_range(-2f64, 2f64)))
+        .collect();
+
+    println!("{} uniformly random samples on [-2, 2] x [-2, 2], {} lanes\n", count, LANES);
+
+    for &iterations in [100, 400, 2000].iter() {
+
+        let (scalar, a) = measure(&samples, |samples| {
+            samples.iter().filter(|&&c| fractal.orbit(c, iterations).position(|z| z.abs() >= 2f64).is_some()).count()
+        });
+
+        let (batched, b) = measure(&samples, |samples| {
+            escape_batch(&fractal, samples, iterations).iter().filter(|e| e.is_some()).count()
+        });
+
+        assert_eq!(a, b, "the scalar and the batched kernel disagree on which samples escape");
+
+        println!("iterations: {:>5}   scalar: {:>12.0} orbits/s   batched: {:>12.0} orbits/s   speedup: {:.2}x",
+                 iterations, scalar, batched, batched / scalar);
+
+    }
+
+}
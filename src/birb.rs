@@ -0,0 +1,55 @@
+use butterbrot_rs::*;
+use butterbrot_rs::log::fail;
+
+fn main() {
+
+    let args: Vec<String> = std::env::args().collect();
+
+    // Every command gets its command line like a program of its own, with its name first
+    let command = args[1..].to_vec();
+
+    match args.get(1).map(|a| a.as_ref()) {
+
+        Some("render")  => butterbrot::run(&butterbrot::command(), command),
+        Some("serve")   => butterbrot::run(&butterbrot::serve_command(), command),
+        Some("worker")  => butterbrot::run(&butterbrot::worker_command(), command),
+        Some("convert") => birb2bmp::run(command),
+        Some("combine") => birb_combinator::run(command),
+        Some("info")    => info::run(command),
+        Some("queue")   => queue::run(command),
+        Some("view")    => birb2term::run(command),
+
+        Some("completions") => {
+            let m = cli::parse(&cli::completions_command(), &args[2..]);
+            match cli::completions(&m.positionals[0], &cli::commands()) {
+                Some(script) => print!("{}", script),
+                None         => fail(format!("There are no completions for '{}', only for {}.", m.positionals[0], cli::SHELLS.join(", "))),
+            }
+        },
+
+        Some("help") => {
+            let m = cli::parse(&cli::help_command(), &args[2..]);
+            let commands = cli::commands();
+            match m.positionals.first() {
+                Some(name) => match commands.iter().find(|c| c.name == name) {
+                    Some(command) if m.flag("man")      => print!("{}", cli::man(command)),
+                    Some(command) if m.flag("markdown") => print!("{}", cli::markdown(command)),
+                    Some(command)                       => print!("{}", cli::help(command)),
+                    None                                => unknown(name),
+                },
+                None if m.flag("man") => print!("{}", cli::man_overview(&commands)),
+                None                  => print!("{}", cli::overview(&commands)),
+            }
+        },
+
+        None | Some("-h") | Some("--help") => print!("{}", cli::overview(&cli::commands())),
+
+        Some(name) => unknown(name),
+
+    }
+
+}
+
+fn unknown(name:&str) -> ! {
+    fail(format!("There's no command '{}', see 'birb --help' for the ones there are.", name))
+}
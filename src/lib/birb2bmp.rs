@@ -0,0 +1,248 @@
+//! `birb convert`, and the old `birb2bmp` binary, which is the same thing: turns a birb into a
+//! bitmap, grey, or coloured by a statistics channel.
+
+use rayon::prelude::*;
+use std::io::{Seek, SeekFrom, Write};
+
+use super::cli::{self, Command, Opt, Positional};
+use super::io::*;
+use super::ARG_SCALE;
+
+/// `birb convert`
+pub fn command() -> Command {
+
+    let mut options = vec![
+        Opt::value("filename", &["<filename>"], "The bitmap file to write. It may be a template, like the --filename of 'birb render', which can also use {name}, the name of the birb without its extension, like '{name}_{iterations}.bmp'.\n\nWithout it, the bitmap is called birb_ and eight random letters and digits, like birb_Q3ZK81DX.bmp.")
+            .short('o'),
+        Opt::value("colour", &["<channel>"], "Colour the image by a statistics channel of a birb computed with 'birb render --stats'. The brightness still is the count of hits, the hue is one of:")
+            .choices(&[
+                ("iteration", "when in their Orbits the numbers landed in a pixel: early ones are red, late ones blue"),
+                ("direction", "the mean argument of the numbers, faded out where the arguments disagree a lot"),
+            ]),
+        Opt::flag("invert", "Make the grey image dark on white, instead of bright on black."),
+    ];
+    options.extend(clobber_options());
+
+    Command {
+        name: "convert",
+        about: "Turn a birb into a bitmap",
+        help: "Converts a birb to a bitmap image. The brightness of a pixel is its count of hits, the largest count of the birb being white. The birb is read, and the bitmap written, a band of rows at a time, so even birbs larger than the memory convert fine.\n\nThe old 'birb2bmp <birb> <bitmap> [iteration|direction|invert]' still works the same.",
+        options,
+        positionals: vec![
+            Positional { name: "<birb>", required: false, many: false, help: "The birb to convert. Without it, it's data.birb." },
+        ],
+    }
+
+}
+
+/// translates the command line of the old `birb2bmp` binary into the one of `birb convert`
+///
+/// That's `birb2bmp [<birb> [<bitmap> [<colour>]]]`, where any `<colour>`, that isn't a channel,
+/// inverts the image.
+pub fn old_style(args:Vec<String>) -> Vec<String> {
+
+    let mut args = args.into_iter();
+    let mut new: Vec<String> = args.by_ref().take(1).collect();
+
+    let (flags, positionals): (Vec<String>, Vec<String>) = args.partition(|a| a == "--overwrite" || a == "--no-clobber");
+    new.extend(flags);
+
+    if let Some(bitmap) = positionals.get(1) { new.extend(vec!["-o".to_string(), bitmap.clone()]) }
+
+    match positionals.get(2).map(|c| c.as_ref()) {
+        Some("iteration") | Some("direction") => new.extend(vec!["--colour".to_string(), positionals[2].clone()]),
+        Some(_)                               => new.push("--invert".to_string()),
+        None                                  => (),
+    }
+
+    new.push("--".to_string());
+    new.extend(positionals.into_iter().take(1));
+
+    new
+
+}
+
+/// turns a hue, saturation and value, all from 0 to 1, into red, green and blue from 0 to 255
+fn hsv(h:f64, s:f64, v:f64) -> (u8, u8, u8) {
+
+    let h = (h.fract() + 1f64).fract() * 6f64;
+    let f = h - h.floor();
+
+    let p = v * (1f64 - s);
+    let q = v * (1f64 - s * f);
+    let t = v * (1f64 - s * (1f64 - f));
+
+    let (r, g, b) = match h.floor() as u8 {
+        0 => (v, t, p),
+        1 => (q, v, p),
+        2 => (p, v, t),
+        3 => (p, q, v),
+        4 => (t, p, v),
+        _ => (v, p, q),
+    };
+
+    ((r * 255f64) as u8, (g * 255f64) as u8, (b * 255f64) as u8)
+
+}
+
+/// the 54 bytes in front of the pixels of a 24 bit bitmap of `width` by `height` pixels, with
+/// rows of `row_bytes` bytes, as the `bmp` crate writes them
+///
+/// The sizes of the file and the pixels don't fit into the header beyond 4 GB, they're as large as
+/// they go then. Image viewers go by the width and the height anyway.
+fn bmp_header(width:u64, height:u64, row_bytes:u64) -> Vec<u8> {
+
+    let data_size = (row_bytes * height).min(u32::MAX as u64 - 54) as u32;
+
+    let mut header = Vec::with_capacity(54);
+
+    header.extend_from_slice(b"BM");
+    header.extend_from_slice(&(54 + data_size).to_le_bytes());     // the file size
+    header.extend_from_slice(&0u32.to_le_bytes());                  // unused
+    header.extend_from_slice(&54u32.to_le_bytes());                 // where the pixels start
+
+    header.extend_from_slice(&40u32.to_le_bytes());                 // the size of this second part
+    header.extend_from_slice(&(width as i32).to_le_bytes());
+    header.extend_from_slice(&(height as i32).to_le_bytes());      // positive, so the rows are bottom-up
+    header.extend_from_slice(&1u16.to_le_bytes());                  // planes
+    header.extend_from_slice(&24u16.to_le_bytes());                 // bits per pixel
+    header.extend_from_slice(&0u32.to_le_bytes());                  // not compressed
+    header.extend_from_slice(&data_size.to_le_bytes());
+    header.extend_from_slice(&1000i32.to_le_bytes());               // pixels per metre, both ways
+    header.extend_from_slice(&1000i32.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());                  // no palette
+    header.extend_from_slice(&0u32.to_le_bytes());
+
+    header
+
+}
+
+/// converts the birb, that the command line `args` name, the name of the program first
+pub fn run(args:Vec<String>) {
+
+    /* Figure out, what to read and write */
+
+    let (clobber, args) = clobber(args);
+    let m = cli::parse(&command(), &args[1..]);
+
+    let src_name = m.positionals.first().cloned().unwrap_or_else(|| "data.birb".to_string());
+    let filename = m.value("filename").map(|f| f.to_string()).unwrap_or_else(|| gen_filename("bmp"));
+    let invert   = m.flag("invert");
+    let colour   = m.value("colour");   // which statistics channel to colour by, if any
+
+
+
+    /* Get the data, a band of rows at a time, so huge birbs don't have to fit into memory */
+
+    let mut reader = BirbReader::open(&src_name);
+
+    let header = reader.header().clone();
+    let (width, height) = (reader.width(), reader.height());
+
+    // The filename may be a template, which can also use the {name} of the birb. All of that is
+    // checked before reading through all of the birb.
+    let mut values = template_values(&header, width, height);
+    values.push(("name".to_string(), std::path::Path::new(&src_name).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()));
+
+    let filename = fill_template(&filename, &values, clobber);
+    check_output(&filename, clobber);
+
+    // Only the count channel decides the brightness, find its largest value
+    let max = reader.bands()
+        .map(|(_, rows)| rows[2..(rows[0] * rows[1]) as usize + 2].par_iter().cloned().max().unwrap_or(0))
+        .max()
+        .unwrap_or(0);
+
+
+    // What to divide by to map to 256, images with fewer hits (like escape-time ones) stay as they are
+    let mapper = (max / 255).max(1);
+
+    // The statistics channels are sums, dividing them by the count gives the mean
+    let stat_channel = |name:&str| header.channel(name).unwrap_or_else(|| {
+        error!(Err(()), "The birb has no statistics channels, compute it with 'birb render --stats' to colour it.")
+    });
+
+    let stats = colour.map(|_| (stat_channel("iteration"), stat_channel("length"), stat_channel("arg_r"), stat_channel("arg_i")));
+
+
+
+    /* Make an image out of it, and write it to file a band of rows at a time */
+
+    // It only takes its place once it's complete
+    let mut f = error!(AtomicFile::create(&filename), "Couldn't open the bitmap file to write.", full);
+
+    // Every row of a bitmap is padded to 4 bytes, and they're stored bottom-up, so every band goes
+    // in front of the ones above it
+    let row_bytes = (3 * width).div_ceil(4) * 4;
+
+    error!(f.write_all(&bmp_header(width, height, row_bytes)), "There was an error while writing the bitmap file.", full);
+
+    for (first, rows) in reader.bands() {
+
+        let n = (rows[0] * rows[1]) as usize;
+        let band_height = rows[1];
+
+        let stat = |c:usize, i:usize| rows[2 + c * n + i];
+
+        // Map to 256 range, or colour
+        let pixel = |i:usize| {
+
+            let hits = rows[2 + i];
+
+            match (colour, stats) {
+
+                (Some(_), _) if hits == 0 => (0, 0, 0),
+
+                (Some(channel), Some((iteration, length, arg_r, arg_i))) => {
+
+                    let v = hits as f64 / max as f64;
+
+                    if channel == "iteration" {
+                        // Early numbers of an Orbit are red, late ones blue
+                        let h = stat(iteration, i) as f64 / stat(length, i) as f64;
+                        hsv(h * 0.7, 1f64, v)
+                    } else {
+                        // The hue is the mean argument, faded out if the arguments disagree a lot
+                        let ar = stat(arg_r, i) as i64 as f64 / (ARG_SCALE * hits as f64);
+                        let ai = stat(arg_i, i) as i64 as f64 / (ARG_SCALE * hits as f64);
+                        hsv(ai.atan2(ar) / (2f64 * std::f64::consts::PI), (ar * ar + ai * ai).sqrt().min(1f64), v)
+                    }
+
+                },
+
+                _ => {
+                    let n = (hits / mapper) as u8;
+                    let n = if invert { 255 - n } else { n };
+                    (n, n, n)
+                },
+
+            }
+
+        };
+
+        // The rows of the band, bottom-up, as blue, green and red
+        let mut bytes = vec![0u8; (row_bytes * band_height) as usize];
+
+        bytes
+            .par_chunks_mut(row_bytes as usize)
+            .enumerate()
+            .for_each(|(k, row)| {
+
+                let y = band_height as usize - 1 - k;
+
+                for x in 0..width as usize {
+                    let (r, g, b) = pixel(y * width as usize + x);
+                    row[3 * x..3 * x + 3].copy_from_slice(&[b, g, r]);
+                }
+
+            });
+
+        let offset = 54 + (height - first - band_height) * row_bytes;
+
+        error!(f.seek(SeekFrom::Start(offset)).and_then(|_| f.write_all(&bytes)), "There was an error while writing the bitmap file.", full);
+
+    }
+
+    error!(f.commit(), "There was an error while writing the bitmap file.", full);
+
+}
@@ -0,0 +1,194 @@
+//! `birb view`, and the old `birb2term` binary, which is the same thing: a very limited renderer,
+//! that prints a birb to the terminal with block characters. It's what verified, that
+//! `butterbrot` actually worked, back in the day.
+
+use super::cli::{self, Command, Positional};
+use super::io::BirbReader;
+
+/// `birb view`
+pub fn command() -> Command {
+    Command {
+        name: "view",
+        about: "Print a birb to the terminal, with block characters",
+        help: "Prints the birb to the terminal, two characters a pixel, the more hits, the denser the character. The thresholds are fixed, so it works best for small birbs with a few thousand hits a pixel.",
+        options: Vec::new(),
+        positionals: vec![
+            Positional { name: "<birb>", required: false, many: false, help: "The birb to print. Without it, it's data.birb." },
+        ],
+    }
+}
+
+/// prints the birb, that the command line `args` name, the name of the program first
+pub fn run(args:Vec<String>) {
+
+    let m = cli::parse(&command(), &args[1..]);
+
+    // The birb is read a band of rows at a time, so huge ones print just as well. Every band is a
+    // birb of its own, with the number of its rows as the height.
+    let mut reader = BirbReader::open(m.positionals.first().map(|b| b.as_ref()).unwrap_or("data.birb"));
+
+    for (_, birb) in reader.bands() {
+
+        let w = birb[0];
+        let h = birb[1];
+
+
+
+        /* The good parts.
+         *
+         * Render 'Honk4.birb' using the first section's for loops.
+         * This one's the first time, I made the Buddhabrot apparent.
+         * This one's gotten a weird resolution, so does Honk7.
+         *
+         * Render 'Honk7.birb' using the second section's for loops.
+         * This one's gotten about 20 times the resolution of honk4.
+         *
+         * Render 'Honk8.birb' using the third section's for loops.
+         * This one's the first with a round resolution: 1000x1000
+         *
+         */
+
+
+
+
+        // good render settings:
+        // --samples 10_000_000 --iterations 200 and whatever warmup it was, that I set as default right now (I think 400, or maybe 10)
+
+
+        /*****************************************************/
+        /*
+
+        // works well with data set 'honk4' -- the first truly successfull run!
+        for i in 0..h {
+
+            for j in 2..w {
+
+            let c = birb[(i*w+j-2) as usize];
+            if c > 50 && c < 150 {
+                print!("   ∙    ");
+            } else if c > 150 && c < 1000 {
+                print!("   ○    ");
+            } else if c > 500 && c < 1000 {
+                print!("   ◆    ");
+            } else if c > 1000 && c < 5000 {
+                print!("░░░░░░░░");
+            } else if c > 5000 && c < 10000 {
+                print!("▒▒▒▒▒▒▒▒");
+                // print!("{:^10}", c);
+            } else if c > 10000 && c < 20000{
+                print!("▓▓▓▓▓▓▓▓");
+            } else if c > 20000 {
+                print!("████████");
+            }
+                // print!("{:^10}", (birb[(i*w+j) as usize] as f64).sqrt() as u64);
+                // print!("{:^10}", (birb[(i*w+j) as usize]) as u64);
+
+            }
+
+            print!("\n");
+
+        }
+
+        */
+        /*****************************************************/
+        /*
+
+        for i in 0..h {
+
+            for j in 2..w {
+
+            let c = birb[(i*w+j-2) as usize];
+            if c > 50 && c < 150 {
+                print!(" ∙ ");
+            } else if c > 150 && c < 1000 {
+                print!(" ○ ");
+            } else if c > 500 && c < 1000 {
+                print!(" ◆ ");
+            } else if c > 1000 && c < 5000 {
+                print!("░░░");
+            } else if c > 5000 && c < 10000 {
+                print!("▒▒▒");
+            } else if c > 10000 && c < 15000{
+                print!("▓▓▓");
+            } else if c > 15000 {
+                print!("███");
+            } else {
+                print!("   ");
+            }
+
+            }
+
+            print!("\n");
+
+        }
+
+        */
+        /*****************************************************/
+
+        for i in 0..h {
+
+            for j in 0..w {
+
+                // The counters come after the width and the height
+                let c = birb[(2+i*w+j) as usize];
+                if c > 50 && c < 150 {
+                    print!("∙ ");
+                } else if c > 150 && c < 1000 {
+                    print!("○ ");
+                } else if c > 500 && c < 1000 {
+                    print!("■ ");
+                } else if c > 1000 && c < 5000 {
+                    print!("░░");
+                } else if c > 5000 && c < 10000 {
+                    print!("▒▒");
+                } else if c > 10000 && c < 15000{
+                    print!("▓▓");
+                } else if c > 15000 {
+                    print!("██");
+                } else {
+                    print!("  ");
+                }
+
+            }
+
+            println!();
+
+        }
+
+        /*****************************************************/
+        /*
+
+        for i in 0..h {
+
+            for j in 2..w {
+
+            let c = birb[(i*w+j-2) as usize];
+            if c > 50 && c < 150 {
+                print!("∙ ");
+            } else if c > 150 && c < 1000 {
+                print!("○ ");
+            } else if c > 1000 && c < 20000 {
+                print!("■ ");
+            } else if c > 20000 && c < 80000 {
+                print!("░░");
+            } else if c > 80000 && c < 150000 {
+                print!("▒▒");
+            } else if c > 150000 && c < 200000{
+                print!("▓▓");
+            } else if c > 200000 {
+                print!("██");
+            } else {
+                print!("  ");
+            }
+
+            }
+
+            print!("\n");
+
+        }
+
+        */
+
+    }
+
+}
@@ -0,0 +1,192 @@
+//! `birb combine`, and the old `birb_combinator` binary, which is the same thing: sums up the
+//! counters of several birbs of the same render into one.
+
+use super::cli::{self, Command, Opt, Positional};
+use super::io::*;
+use super::SIGNED_CHANNELS;
+use super::counters::CounterWidth;
+
+/// `birb combine`
+pub fn command() -> Command {
+
+    let mut options = vec![
+        Opt::value("filename", &["<filename>"], "The birb to write the sum to. It may be a template, like the --filename of 'birb render', which can also use {name}, the name of the first birb without its extension.\n\nWithout it, the birb is called birb_ and eight random letters and digits, and .combined.birb, like birb_Q3ZK81DX.combined.birb.")
+            .short('o'),
+    ];
+    options.extend(clobber_options());
+
+    Command {
+        name: "combine",
+        about: "Sum up several birbs of the same render into one",
+        help: "Sums up the counters of the birbs, so several renders with the exact same settings make one with all of their samples. The birbs have to be of the same width, height and channels, and either all or none of them low-precision previews. Birbs, that aren't, are left out with an error. The birbs are read a band of rows at a time, and only once, so combining huge ones doesn't need much memory. The sum gets counters wide enough to hold the largest counters of all the birbs added up.\n\nThe old 'birb_combinator [<output>] <birb> <birb>...' still works the same: with three or more arguments, the first one is the birb to write.",
+        options,
+        positionals: vec![
+            Positional { name: "<birb>", required: true, many: false, help: "The first birb, whose header the sum keeps." },
+            Positional { name: "<birb>", required: true, many: true, help: "The birbs to add to it." },
+        ],
+    }
+
+}
+
+/// translates the command line of the old `birb_combinator` binary into the one of `birb combine`
+///
+/// That's `birb_combinator <birb> <birb>` or `birb_combinator <output> <birb> <birb>...`.
+pub fn old_style(args:Vec<String>) -> Vec<String> {
+
+    let mut args = args.into_iter();
+    let mut new: Vec<String> = args.by_ref().take(1).collect();
+
+    let (flags, mut positionals): (Vec<String>, Vec<String>) = args.partition(|a| a == "--overwrite" || a == "--no-clobber");
+    new.extend(flags);
+
+    if positionals.len() > 2 { new.extend(vec!["-o".to_string(), positionals.remove(0)]) }
+
+    new.push("--".to_string());
+    new.extend(positionals);
+
+    new
+
+}
+
+/// combines the birbs, that the command line `args` name, the name of the program first
+pub fn run(args:Vec<String>) {
+
+    /* Parse Input */
+
+    let (clobber, args) = clobber(args);
+    let m = cli::parse(&command(), &args[1..]);
+
+    let filename = m.value("filename").map(|f| f.to_string()).unwrap_or_else(|| gen_filename("combined.birb"));
+    let first    = &m.positionals[0];               // filename of the birb to sum other birbs into
+    let mut reader = BirbReader::open(first);       // birb to sum other birbs into
+    let rest     = &m.positionals[1..];             // the filenames of the birbs to add... the 'rest' of the arguments
+
+
+    /* Find all the files, that are of same width, height and channels, and either all or none of them are previews */
+
+    let mut header = reader.header().clone();
+    let width  = reader.width();
+    let height = reader.height();
+
+    let mut readers = Vec::new();
+
+    for b in rest.iter() {
+
+        let reader2 = BirbReader::open(b);
+        let header2 = reader2.header();
+
+        // Are the buffers at least somewhat compatible?
+        if reader2.width() != width || reader2.height() != height {
+            errln!("\x1B[31;1mError:\x1B[0m The birb file \"{}\" does not have width {} and height {}!\n       \"{0}\" has width {} and height {}.", b, width, height, reader2.width(), reader2.height());
+            continue;
+        }
+
+        if header2.channels != header.channels {
+            errln!("\x1B[31;1mError:\x1B[0m The birb file \"{}\" does not have the channels {}!\n       \"{0}\" has the channels {}.", b, header.channels.join(", "), header2.channels.join(", "));
+            continue;
+        }
+
+        // Previews are computed with too little precision to be added to anything, but other previews
+        let preview  = header.get("preview")  == Some("true");
+        let preview2 = header2.get("preview") == Some("true");
+
+        if preview != preview2 {
+            if preview2 {
+                errln!("\x1B[31;1mError:\x1B[0m The birb file \"{}\" is a low-precision preview!\n       Previews can only be combined with other previews.", b);
+            } else {
+                errln!("\x1B[31;1mError:\x1B[0m The birb file \"{}\" is not a preview!\n       The first birb is a low-precision preview, those can only be combined with other previews.", b);
+            }
+            continue;
+        }
+
+        readers.push(reader2);
+
+    }
+
+
+    // The combined birb holds the samples of all of them, if they all say how many
+    let samples = std::iter::once(&reader).chain(readers.iter())
+        .map(|r| r.header().get("samples").and_then(|s| s.parse::<u64>().ok()))
+        .try_fold(0u64, |total, s| s.map(|s| total.saturating_add(s)));
+
+    match samples {
+        Some(samples) => header.set("samples", samples.to_string()),
+        None          => header.remove("samples"),
+    }
+
+    // They're drawn with more than one seed, too
+    header.remove("seed");
+
+    // The filename may be a template, which can also use the {name} of the first birb. All of that
+    // is checked before reading through all of the birbs.
+    let mut values = template_values(&header, width, height);
+    values.push(("name".to_string(), std::path::Path::new(first).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()));
+
+    let filename = fill_template(&filename, &values, clobber);
+    check_output(&filename, clobber);
+
+
+    /* Combine them band by band, so only a few bands are in memory at once */
+
+    // Which of the numbers are two's complement and simply wrap around
+    let signed: Vec<bool> = header.channels.iter().map(|c| SIGNED_CHANNELS.contains(&c.as_ref())).collect();
+
+    // Sums `band2` into `band`, warning about overflows
+    let add = |band:&mut [u64], band2:&[u64]| {
+
+        let n = (band[0] * band[1]).max(1) as usize;
+
+        band.iter_mut()
+            .zip(band2.iter())
+            .enumerate()
+            .skip(2)        // Don't overwrite width and height
+            .for_each(|(i,(n1,&n2))| {
+
+            if signed[(i - 2) / n] {
+
+                *n1 = (*n1).wrapping_add(n2);
+
+            } else {
+
+                // Don't overflow while adding
+                let ov = (*n1).overflowing_add(n2);
+                match ov {
+                    (m, false) => *n1 = m,
+                    (_,  true) => {
+                        warning!("Overflow has been detected. The value will be capped to u64 max.");
+                        *n1 = u64::MAX;
+                    },
+                }
+
+            }
+
+        })
+
+    };
+
+    // The sums fit into counters, that hold the largest counters of all the birbs added up. That's
+    // known before reading any of them, so every birb is read just once.
+    let largest = std::iter::once(&reader).chain(readers.iter())
+        .fold(0u64, |total, r| total.saturating_add(r.header().counter_width().max()));
+
+    let counter_width = header.counter_width().wider(CounterWidth::for_max(largest));
+
+
+    /* Write output */
+
+    let rows = reader.band_rows();
+
+    let mut writer = BirbWriter::create(&filename, &header, width, height, counter_width);
+
+    for first in (0..height).step_by(rows as usize) {
+
+        let mut band = reader.rows(first..first + rows);
+        readers.iter_mut().for_each(|r| add(&mut band, &r.rows(first..first + rows)));
+
+        writer.write_rows(first, &band);
+
+    }
+
+    writer.finish();
+
+}
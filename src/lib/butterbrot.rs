@@ -1,285 +1,835 @@
-//! This module only contains the `parse_args()` function and the associated `parse!()` macro, which are used by the `butterbrot`
-//! binairy. When I put this function here, I wasn't yet certain, wether I'd need more bin specific
-//! modules or not.
-
-use super::math::Complex;
-use std::io::*;
-use std::fs::File;
+//! `birb render`, and the old `butterbrot` binary, which is the same thing: the options of a
+//! render, `parse_args()`, which turns them into the settings of `butterbrot_run()`, and `run()`,
+//! which computes the birb.
+//!
+//! `serve_command()` and `worker_command()` are the same render, spread over several machines,
+//! see the `distributed` module.
+
+use super::math::{Complex, DoubleDouble, Fractal, Frame, Precision, Projection};
+use super::{Deposit, Mode};
+use super::counters::CounterWidth;
+use super::cli::{self, Command, Matches, Opt, Source};
 use super::io::gen_filename;
+use super::log::fail;
+use super::{io, math, counters, tiles, estimate, distributed, config, queue, log};
+use super::{STATS_CHANNELS, SIGNED_CHANNELS, RENDER_KEYS, RenderSettings, butterbrot_run, escape_time_run};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-/// Macro to help with parsing command line arguments.
-///
-/// ### What does this do?
-///
-/// This Macro has four rules:
-///
-/// 1. Complex Numbers: `parse!("-c1", args, complex)`
-/// 2. Strings: `parse!("--filename", args, string)`
-/// 3. Primitives: `parse!("--width", args, u64)`
-/// 4. Parse Errors: `parse!(unmatched, error)`
-///
-/// The three first variants take a token `&str`, which should be the name of the command line
-/// flag, that is currently being parsed. (For error output)
-///
-/// The second argument is an Iterator, that produces the arguments in order, so the Macro has
-/// access to the Flag's arguments. **This must be an Iterator over Strings!**
-///
-/// The third argument specifies the operating mode.
-///
-/// If it's `complex` the Macro will attempt to get the next two items from the `args` iterator and
-/// parse them as `f64`.
-///
-/// If it's `string` the Macro will attempt to get the next item from iterator and simply yields it
-/// as a `String`.
-///
-/// If it's some primitive type, the Macro will attempt to get the next item from the iterator and
-/// parse it as the specified primitive type using `&str.parse::<TYPE>()`.
-///
-/// If any of these operations fail a custom error message is displayed and the application
-/// **panics**.
-///
-/// The fourth variant is especially for dealing with unmatched command line flags. See the example
-/// below.
-///
-/// ### Usage Example (Why should I care?)
-///
-/// **Note: I didn't actually test these code snippets.**  
-/// **Note:* The `math::Complex` type is part of *my* `butterbrot_rs` crate, for which I made this
-/// Macro.
-///
-/// ```
-/// let args_v: Vec<String> = std::env::args().collect();
-/// let args = args_v.into_iter();
-///
-/// // output is for config:
-/// // (width, height, math::Complex( real, imaginairy ), filename)
-/// let mut output = (0i32, 0i32, math::Complex::new(88.9, 72.4), "file.name".to_string());
+
+/* The Options */
+
+/// the options of a render
 ///
-/// let mut curr:String;
-/// loop {
+/// The defaults here are the ones `parse_args()` uses. Options without one are either optional,
+/// or figured out from the others, as their help says.
+pub fn options() -> Vec<Opt> {
+    vec![
+        Opt::value("filename", &["<filename>"], "The filename to write the computed data to. This will be a birb file. It's written under a temporary name next to it first, and only takes its place once it's complete, so a crash never leaves half a birb.\n\nThe filename may be a template, like '{formula}_{width}x{height}_i{iterations}_s{samples}_{date}.birb', whose placeholders are filled in from the render: {formula}, {width}, {height}, {iterations}, {samples}, {seed}, {precision}, {deposit} and every other key the birb records, as well as {date} (UTC, like 20261018-153012) and {rand} (eight random letters and digits). Should the name be taken, _2, _3 and so on is added, unless --overwrite or --no-clobber say otherwise.\n\nWithout it, the birb is called birb_ and eight random letters and digits, like birb_Q3ZK81DX.birb.")
+            .short('o'),
+        Opt::value("threads", &["<number>"], "How many threads to use for parallel computation. Note, that this is the number of computation threads. The total number of threads is one larger, as this doesn't include the main thread. The threads take the samples in chunks of --phase-len from one shared pool, so fast threads simply do more of them.\n\nWithout it, there's one thread per available core.")
+            .short('t').integer(1, i32::MAX as u64).env("BIRB_THREADS"),
+        Opt::value("timeout", &["<seconds>"], "How many whole seconds to run AT MINIMUM, before the program terminates the computation. Note, that the program will finish some time after the timeout has been reached, as each thread will finish the currently active computation before returning. Without it, the computation runs until all the --samples are done.")
+            .aliases(&["-to"]).integer(0, u64::MAX),
+        Opt::value("interval", &["<seconds>"], "The logging function will attempt to output a log only after <seconds> seconds have elapsed.")
+            .aliases(&["-int"]).integer(0, u64::MAX).default("10").env("BIRB_INTERVAL"),
+        Opt::value("log-format", &["<format>"], "How to log the progress of the render to stdout, every --interval. Everything else, like notes, warnings and errors, goes to stderr, so stdout can be piped. Colours are only used on terminals, and not at all, if NO_COLOR is set. One of:")
+            .default("text").env("BIRB_LOG_FORMAT").choices(&[
+                ("text", "for people, with the progress of every thread"),
+                ("json", "one JSON object a line: a \"start\" event with the frame and the filename, a \"progress\" event every --interval with the samples done and left of every thread and all together, the smoothed samples and deposits per second, the time elapsed and the ETA in seconds, with a --timeout also the time left until it and the samples projected to be done by then, and a \"written\" event with the birb at the end"),
+            ]),
+        Opt::flag("quiet", "Don't log the progress, and leave out the notes. Only warnings and errors are printed.")
+            .short('q'),
+        Opt::value("width", &["<number>"], "How wide to make the birb.")
+            .short('w').integer(1, u32::MAX as u64).default("400"),
+        Opt::value("height", &["<number>"], "How tall to make the birb.")
+            .integer(1, u32::MAX as u64).default("400"),
+        Opt::value("zoom", &["<number>"], "How much to zoom in. This zoom factor is used to map the --width and the --height onto the Complex plane, relative to the complex number specified using --center. The <number> may be a float.\n\nThis flag is overridden by either of --complex1 and --complex2. Using the --zoom and --center flags to control the image is more convenient, than using --complex1 and --complex2 directly, since the zoom method preserves the image ratio.")
+            .short('z').number(f64::MIN_POSITIVE, f64::INFINITY).default("100"),
+        Opt::value("center", &["<real>", "<imaginairy>"], "The complex number, that should be in the center point of the final image.")
+            .short('c').number(f64::NEG_INFINITY, f64::INFINITY).default("0 0"),
+        Opt::value("warmup", &["<number>"], "How many samples should the Metropolis-Hastings Iterators discard as warmup. See documentation for more.")
+            .aliases(&["-wu"]).integer(0, u64::MAX).default("100"),
+        Opt::value("samples", &["<number>"], "How many samples should the program compute in total, across all threads. This does not include the warmup.")
+            .short('s').integer(0, u64::MAX).default("400"),
+        Opt::value("iterations", &["<number>"], "How many iterations long should each Orbit be at max. See documentation for more.")
+            .short('i').integer(1, u64::MAX).default("10"),
+        Opt::value("phase-len", &["<number>"], "How many Metropolis Hastings Orbits each thread computes before calling write_back -- The length of a write_back phase.")
+            .short('p').aliases(&["--phase_len"]).integer(1, u64::MAX).default("10000"),
+        Opt::value("complex1", &["<real>", "<imaginairy>"], "One of the corners of the frame of the Complex Plane that is to be explored. This must be a diagonally opposite corner to --complex2. Without the two corners, the frame is made from --zoom and --center.")
+            .aliases(&["-c1"]).number(f64::NEG_INFINITY, f64::INFINITY),
+        Opt::value("complex2", &["<real>", "<imaginairy>"], "One of the corners of the frame of the Complex Plane that is to be explored. This must be a diagonally opposite corner to --complex1.")
+            .aliases(&["-c2"]).number(f64::NEG_INFINITY, f64::INFINITY),
+        Opt::value("deposit", &["<mode>"], "How the Orbits are deposited into the birb. One of:")
+            .short('d').default("points").choices(&[
+                ("points",          "count every number an Orbit visits (the classic Buddhabrot)"),
+                ("trails",          "draw the line segments between consecutive numbers of an Orbit, for continuous filaments at high zooms"),
+                ("weighted-trails", "like trails, but every segment deposits the same total, so long segments are fainter"),
+            ]),
+        Opt::flag("stats", "Besides counting hits, also sum up per pixel the iteration index at which numbers landed there, their argument as a unit vector and the total length of their Orbits. These go into extra channels of the birb, so renderers can colour by when and in which direction Orbits pass through a pixel."),
+        Opt::value("projection", &["<plane>"], "Which plane of the four dimensional space of Orbits to render. Every number of an Orbit is a point (zr, zi, cr, ci), the Buddhabrot is just its shadow on the zr-zi plane. Name the axes for the real and the imaginairy part like 'zr-cr' or 'zi-ci'. The frame is on this plane.")
+            .aliases(&["-pr"]).default("zr-zi"),
+        Opt::value("matrix", &["<zr>", "<zi>", "<cr>", "<ci>", "<zr>", "<zi>", "<cr>", "<ci>"], "Project using an arbitrary 2x4 matrix instead of a plane. The first four numbers make the real part from (zr, zi, cr, ci), the last four the imaginairy part. Overrides --projection.")
+            .short('m').number(f64::NEG_INFINITY, f64::INFINITY),
+        Opt::value("rotate", &["<plane>", "<angle>"], "Rotate the four dimensional space by <angle> radians in the <plane> (named like for --projection) before projecting it. May be given multiple times, the rotations are applied in the order given.")
+            .short('r').repeats(),
+        Opt::value("julia", &["<real>", "<imaginairy>"], "Render a Julia-style Buddhabrot: fix c of the Mandelbrot equation to this complex number and sample the starting points z0 of the Orbits instead.")
+            .short('j').number(f64::NEG_INFINITY, f64::INFINITY),
+        Opt::value("z0", &["<real>", "<imaginairy>"], "The starting point z0 of every Orbit, when sampling c. Can't be used together with --julia.")
+            .number(f64::NEG_INFINITY, f64::INFINITY).default("0 0"),
+        Opt::value("mode", &["<mode>"], "What to render. One of:")
+            .default("buddhabrot").choices(&[
+                ("buddhabrot",  "the Buddhabrot, as described by all the other flags"),
+                ("escape-time", "a classic escape-time image of the same frame, for a quick preview: every pixel is the iteration at which the Orbit of its c (or z0 with --julia) escaped, or 0. Only --iterations, the frame, --julia and --z0 matter."),
+            ]),
+        Opt::value("precision", &["<precision>"], "Which kind of floating point numbers to compute with. Numbers on the command line are read with all their digits, so deep zoom coordinates may be longer than an f64 can hold. A warning is printed, if the precision is too low for the frame. One of:")
+            .default("auto").env("BIRB_PRECISION").choices(&[
+                ("f32",           "the fastest, but only good up to zooms of about 1e3. For previews only: the birb is marked as a preview, and birb combine won't mix it with other renders"),
+                ("f64",           "fast, good up to zooms of about 1e12"),
+                ("double-double", "about 106 bits, good up to zooms of about 1e27, but roughly ten to twenty times slower"),
+                ("auto",          "the fastest one, that is precise enough for the frame"),
+            ]),
+        Opt::flag("perturbation", "Compute one reference Orbit of the center of the frame in full precision, and the Orbits of all the samples as small differences to it in f64. That's a lot faster than double-double for deep zooms. Where the differences lose their precision, the Orbits are rebased onto the start of the reference. Works best, if the center's Orbit doesn't escape."),
+        Opt::value("counters", &["<type>"], "Which type of unsigned integers to count the hits with. Narrower counters need a lot less memory for large birbs. Should a counter fill up, all of them are widened to the next larger type right away, so nothing is lost. The birb records the type, all the birb tools read all of them. One of:")
+            .default("u64").env("BIRB_COUNTERS").choices(&[
+                ("u16", "2 bytes a counter, up to 65535 hits"),
+                ("u32", "4 bytes a counter, up to about 4.3 billion hits"),
+                ("u64", "8 bytes a counter, as many hits as there'll ever be"),
+            ]),
+        Opt::value("memory", &["<megabytes>"], "Keep no more than about this many megabytes of counters in memory, for birbs too large to fit. The birb is cut into tiles of 256x256 pixels, the ones that don't fit go to a scratch file next to the birb, which is removed again at the end. Hits are collected and added to the tiles in large batches, so this is only a little slower, as long as the scratch file is on a fast disk. The birb is written as a tiled birb, which all the birb tools read band by band, without loading it all at once.\n\nWithout it, all the counters are kept in memory.")
+            .integer(1, u64::MAX).env("BIRB_MEMORY"),
+        Opt::flag("dry-run", "Don't compute anything. Check the flags, print the frame, that they make, how much memory the birb and the Orbits of the threads will take, and how long the computation will run, which is measured by computing for a few seconds. Then exit."),
+        Opt::flag("force", "Start the computation, even if it's estimated to need more memory, than the system has available. Without it, the render refuses to start, as running out of memory hours into a render is no fun."),
+        Opt::value("append", &["<birb>"], "Add to an existing birb, instead of starting from nothing. Its counts are loaded first, the new samples are added on top, and it's written back in place, with the total number of samples it holds. It has to be of the same size and channels, with the same frame, fractal, projection, --iterations and --deposit, which every birb records. It's written to a temporary file first, which then replaces it, so a crash never damages it. Can't be used together with --filename."),
+        Opt::value("seed", &["<number>"], "The seed of the random numbers, that the samples are drawn from. Every thread draws from a stream of its own, renders with the same seed draw from the same streams. Without it, a random seed is picked. Either way it's recorded in the birb, and the workers of a distributed render get it from the coordinator.")
+            .integer(0, u64::MAX),
+        Opt::value("config", &["<file>"], "Take the options from a configuration file, a TOML file, or a JSON one, if it ends in .json. Its keys are the long names of the options, like 'width = 4000' or 'center = [-0.75, 0.1]', flags are true or false. Flags on the command line take the place of the ones in the file. --print-config writes such a file."),
+        Opt::value("preset", &["<name>"], "Take the options from the configuration file <name>.toml in ~/.config/butterbrot/presets (or in $XDG_CONFIG_HOME/butterbrot/presets). A --config takes the place of the options of the preset, and the flags on the command line of both."),
+        Opt::flag("print-config", "Don't compute anything. Print the options of the render as a configuration file, with everything from --preset, --config, the environment and the defaults filled in, and exit. Save it, and give it to --config, to render the same again."),
+    ]
+}
+
+/// `birb render`
+pub fn command() -> Command {
+
+    let mut options = options();
+    options.extend(io::clobber_options());
+
+    Command {
+        name: "render",
+        about: "Compute a Buddhabrot, or an escape-time image of the same frame, into a birb",
+        help: "Computes a Buddhabrot of the frame, that the flags describe, and writes it to a birb file. The frame is either made from --zoom and --center, or given by its corners --complex1 and --complex2.\n\nTo spread a render over several machines, see 'birb serve --help' and 'birb worker --help'. The old 'butterbrot' binary is the same as 'birb render', it only spells --height as -h, and --help as h.",
+        options,
+        positionals: Vec::new(),
+    }
+
+}
+
+/// `birb serve`, the coordinator of a distributed render
+pub fn serve_command() -> Command {
+
+    let mut options = distributed::serve_options();
+    options.extend(command().options);
+
+    Command {
+        name: "serve",
+        about: "Have workers on other machines compute a render, and sum up their birbs",
+        help: "Don't compute, but have workers compute. The --samples are cut into units of --unit samples, which are handed out to the workers, that connect to the --listen address. Every unit is drawn from a random stream of its own. The birbs of the units are summed up and written to --filename. Units of workers, that disappear, are handed out again. Everything is sent unencrypted, so keep it to networks you trust.\n\nAll the other flags are the ones of 'birb render', they describe the render, and are passed on to the workers.",
+        options,
+        positionals: Vec::new(),
+    }
+
+}
+
+/// `birb worker`, which computes units for a coordinator
+pub fn worker_command() -> Command {
+
+    let mut options = distributed::worker_options();
+    options.extend(command().options);
+
+    Command {
+        name: "worker",
+        about: "Compute units of a distributed render for 'birb serve'",
+        help: "Connect to the coordinator at the --connect address, and compute the units it hands out, until there are none left. The render is described by the coordinator's flags, the worker's own ones are applied on top, so e.g. every worker can use a different number of --threads. The --timeout applies to each unit. Workers log every second, unless they are given an --interval.",
+        options,
+        positionals: Vec::new(),
+    }
+
+}
+
+/// translates the command line of the old `butterbrot` binary into the one of `birb render`
 ///
-///     match args.next() {
-///         Some(s) => curr = s,
-///         None    => break,
-///     }
+/// Only two spellings changed: `-h` used to be `--height`, and `h` used to be `--help`. All the
+/// other old spellings, like `-wu`, are aliases of the new ones anyway. The values of the flags are
+/// left as they are, so a file called `h` still works.
+pub fn old_style(args:Vec<String>) -> Vec<String> {
+
+    // The queue is new, it has no old spellings
+    if args.get(1).map(|a| a == "queue").unwrap_or(false) { return args }
+
+    let mut command = serve_command();
+    command.options.extend(distributed::worker_options());
+
+    let mut args = args.into_iter();
+    let mut new: Vec<String> = args.by_ref().take(1).collect();
+
+    while let Some(arg) = args.next() {
+
+        let values = command.option(&arg).map(|o| o.values.len()).unwrap_or(0);
+
+        match arg.as_ref() {
+            "-h" => { new.push("--height".to_string()); new.extend(args.next()) },
+            "h"  => new.push("--help".to_string()),
+            _    => { new.push(arg); new.extend(args.by_ref().take(values)) },
+        }
+
+    }
+
+    new
+
+}
+
+
+/* Parsing */
+
+/// the options of the render `args`, the name of the program first, with the options of the
+/// `--preset` and the `--config` beneath the ones given, see `cli::parse_with()`
+pub fn resolve(args:&[String]) -> Matches {
+
+    let command = command();
+    let given = cli::parse(&command, &args[1..]);
+
+    let mut layers = Vec::new();
+
+    if let Some(name) = given.value("preset") {
+        layers.push(config::options(&config::read(&config::preset(name)), &command, &format!("the preset '{}'", name)));
+    }
+
+    if let Some(file) = given.value("config") {
+        layers.push(config::options(&config::read(file), &command, &format!("'{}'", file)));
+    }
+
+    cli::parse_with(&command, &args[1..], &config::merge(layers))
+
+}
+
+/// the configuration file `--print-config` prints for the options `m` of a render
 ///
-///     match curr.as_ref() {
+/// The `z0` of a Julia-style render is left out, it's never set then, but has a default anyway.
+pub fn printed_config(m:&Matches) -> String {
+    config::to_toml(m, &command(), if m.values("julia").is_some() { &["z0"] } else { &[] })
+}
+
+/// parses command line arguments
 ///
-///         "-w" || "--width"  => { output.0 = parse!("--width",    args, i32) },
-///         "-h" || "--height" => { output.1 = parse!("--height",   args, i32) },
-///         "--filename"       => { output.3 = parse!("--filename", args, string) },
-///         "--complex"        => { output.2 = parse!("--complex",  args, complex) },
+/// `args_v` is a whole command line, the name of the program first, which is checked against the
+/// options of `command()`, see `cli::parse()`. Wrong flags, values out of their range and so on
+/// stop the program with an error, that says what's wrong. Options, that aren't given, are taken
+/// from the `--preset` and the `--config`, see `resolve()`. With `--print-config`, the options are
+/// printed as a configuration file, once they're all checked, and the program exits.
 ///
-///         // print an error with the flag the parsing failed on
-///         unmatched_flag => { parse!(unmatched_flag, error) },
+/// For what the `RenderSettings` mean, see the help of `birb render` and their docs.
 ///
-///     }
+/// All the coordinates are `DoubleDouble`s, so they keep all the digits given on the command line.
+/// They're narrowed to the `precision`, when the computation starts. Unless a precision is asked
+/// for, it's the narrowest one, that resolves the frame, see `Precision`.
 ///
-/// }
-/// ```
-macro_rules! parse {
-
-    // Parse Complex Number
-    ($token:expr, $source:ident, complex) => {
-        {
-
-            // Override panic! message
-            std::panic::set_hook(Box::new(|_| {}));
-
-            // Get real part of the complex number
-            let r = $source.next().unwrap_or_else(|| {
-                eprintln!("\x1B[31;1mError:\x1B[0m The flag '{}' expects two arguments, a real part and an imaginairy part.", $token);
-                panic!("");
-            });
-
-            // Get real part of the complex number
-            let i = $source.next().unwrap_or_else(|| {
-                eprintln!("\x1B[31;1mError:\x1B[0m The flag '{}' expects one more argument, the imaginairy part.", $token);
-                panic!("");
-            });
-
-            // Parse the next token
-            let rp = r.parse::<f64>().unwrap_or_else(|_| {
-                eprintln!("\x1B[31;1mError:\x1B[0m Couldn't parse the real part '{real}' as f64 in '{token} {real} {imag}'.", real = r, imag = i, token = $token);
-                panic!("");
-            });
-
-            // Parse the next token
-            let ip = i.parse::<f64>().unwrap_or_else(|_| {
-                eprintln!("\x1B[31;1mError:\x1B[0m Couldn't parse the imaginairy part '{imag}' as f64 in '{token} {real} {imag}'.", real = r, imag = i, token = $token);
-                panic!("");
-            });
-
-            super::math::Complex::new(rp, ip)
+/// If a flag isn't provided, the default of its `Opt` is used in its place, or whatever its help
+/// says it's figured out from.
+pub fn parse_args(args_v:Vec<String>) -> RenderSettings {
 
-        }
+    let m = resolve(&args_v);
+
+    // How to log is the same for the whole process, see the log module
+    log::set(if m.value("log-format") == Some("json") { log::Format::Json } else { log::Format::Text }, m.flag("quiet"));
+
+    // The options with a default always have a value
+    let number  = |name:&str| m.get::<u64>(name).unwrap();
+    let complex = |name:&str| m.get_all::<DoubleDouble>(name).map(|v| Complex::new(v[0], v[1]));
+
+    let (width, height) = (number("width"), number("height"));
+
+    let deposit = match m.value("deposit") {
+        Some("trails")          => Deposit::Trails { weighted: false },
+        Some("weighted-trails") => Deposit::Trails { weighted: true  },
+        _                       => Deposit::Points,
     };
 
-    // Parse Flag with stringy argument
-    ($token:expr, $source:ident, string) => {
-        {
+    let mode = if m.value("mode") == Some("escape-time") { Mode::EscapeTime } else { Mode::Buddhabrot };
 
-            // Override panic! message
-            std::panic::set_hook(Box::new(|_| {}));
+    // 'auto' isn't the name of any of them
+    let precision = m.value("precision").and_then(Precision::from_name);
 
-            // Get the next token, which is a String
-            $source.next().unwrap_or_else(|| {
-                eprintln!("\x1B[31;1mError:\x1B[0m The flag '{}' expects one argument.", $token);
-                panic!("");
-            })
-        }
+    let counter_width = m.value("counters").and_then(CounterWidth::from_name).unwrap_or(CounterWidth::U64);
 
+    let unknown_plane = |plane:&str| -> ! {
+        fail(format!("Unknown plane '{}'. Name two different axes of 'zr', 'zi', 'cr' and 'ci' like 'zr-cr'.", plane))
     };
 
-    // Parse Flag with one argument to type
-    ($token:expr, $source:ident, $type:ty) => {
-        {
+    // Project onto a plane, or with a matrix of its own
+    let mut projection = match m.get_all::<f64>("matrix") {
+        Some(v) => Projection { m: [[v[0], v[1], v[2], v[3]], [v[4], v[5], v[6], v[7]]] },
+        None    => m.value("projection").and_then(Projection::plane).unwrap_or_else(|| unknown_plane(m.value("projection").unwrap_or(""))),
+    };
 
-            // Override panic! message
-            std::panic::set_hook(Box::new(|_| {}));
+    // Rotate the projection. The first rotation given has to be the rightmost factor, so it's
+    // applied to the points first.
+    for rotation in m.all("rotate").iter().rev() {
 
-            // Get the next token
-            let t = $source.next().unwrap_or_else(|| {
-                eprintln!("\x1B[31;1mError:\x1B[0m The flag '{}' expects one argument.", $token);
-                panic!("");
-            });
+        let (a, b) = Projection::axes(&rotation[0]).unwrap_or_else(|| unknown_plane(&rotation[0]));
 
-            // Parse the next token
-            let i = t.parse::<$type>().unwrap_or_else(|_| {
-                eprintln!("\x1B[31;1mError:\x1B[0m Couldn't parse '{arg}' as {type} in '{token} {arg}'.", arg = t, token = $token, type = stringify!($type));
-                panic!("");
-            });
+        let angle = rotation[1].parse::<f64>().unwrap_or_else(|_| fail(format!("Couldn't parse the angle '{}' as a number in '--rotate {} {}'.", rotation[1], rotation[0], rotation[1])));
 
-            i
-        }
+        projection = projection.rotated(a, b, angle);
+
+    }
+
+    // Figure out, what to sample
+    let z0_set = m.source("z0") == Some(Source::Given) || m.source("z0") == Some(Source::Config);
+
+    let fractal = match (complex("julia"), z0_set) {
+        (Some(c), false) => Fractal::Julia { c },
+        (None, _)        => Fractal::Mandelbrot { z0: complex("z0").unwrap() },
+        (Some(_), true)  => fail("The flags '--julia' and '--z0' can't be used together, a Julia-style Buddhabrot samples z0."),
     };
 
-    // Better error handling, than using error!
-    ($msg:expr, error) => {
-        {
+    // An appended birb is written back to where it came from
+    let append = m.value("append").map(|a| a.to_string());
 
-            // Override panic! message
-            std::panic::set_hook(Box::new(|_| {}));
+    let filename = match (&append, m.value("filename")) {
+        (None, Some(filename))  => filename.to_string(),
+        (None, None)            => gen_filename("birb"),
+        (Some(birb), None)      => birb.clone(),
+        (Some(_), Some(_))      => fail("'--append' writes back to the birb it appends to, it can't be used together with '--filename'."),
+    };
 
-            // Print message and panic
-            eprintln!("\x1B[31;1mError:\x1B[0m Encountered invalid flag: '{}'", $msg);
-            panic!("")
+    // An escape-time image is always of the c (or z0) plane
+    if mode == Mode::EscapeTime && projection != Projection::buddhabrot() {
+        fail("Projections can't be used with '--mode escape-time'.")
+    }
 
-        }
+    // Compute coordinates of the frame of the Complex plane, if none have been provided
+    let unset = Complex::new(DoubleDouble::new(42.0), DoubleDouble::new(42.0));
+
+    let (c1, c2) = match (complex("complex1"), complex("complex2")) {
+
+        (None, None) => {
+
+            let step_size = 1f64 / m.get::<f64>("zoom").unwrap();
+            let frame_width  = width as f64 * step_size;
+            let frame_height = height as f64 * step_size;
+
+            let center = complex("center").unwrap();
+            let delta = Complex::new(DoubleDouble::new(frame_width / 2f64), DoubleDouble::new(frame_height / 2f64));
+
+            (center.sub(&delta), center.add(&delta))
+
+        },
+
+        (c1, c2) => (c1.unwrap_or(unset), c2.unwrap_or(unset)),
+
+    };
+
+    // Figure out the precision: the one asked for, or the narrowest one deep enough for the frame
+    let frame = Frame::new(c1, c2, width, height);
+
+    let precision = match (precision, Precision::for_frame(&frame)) {
+        (Some(p), deep_enough) => {
+            if !p.resolves(&frame) {
+                warning!("The frame is zoomed in too deep for '{}', neighbouring pixels will smear into each other. Try '--precision {}'.", p.name(), deep_enough.unwrap_or(Precision::DoubleDouble).name());
+            }
+            p
+        },
+        (None, Some(p)) => p,
+        (None, None)    => {
+            warning!("The frame is zoomed in too deep even for 'double-double', neighbouring pixels will smear into each other.");
+            Precision::DoubleDouble
+        },
+    };
+
+    if m.flag("print-config") {
+        print!("{}", printed_config(&m));
+        std::process::exit(0);
+    }
+
+    RenderSettings {
+        width,
+        height,
+        complex1: c1,
+        complex2: c2,
+        filename,
+        threads: m.get::<i32>("threads").unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get() as i32).unwrap_or(1)),
+        samples: number("samples"),
+        iterations: number("iterations"),
+        warmup: number("warmup"),
+        phase_len: number("phase-len"),
+        timeout: Duration::from_secs(m.get::<u64>("timeout").unwrap_or(u64::MAX)),
+        interval: Duration::from_secs(number("interval")),
+        deposit,
+        stats: m.flag("stats"),
+        fractal,
+        projection,
+        mode,
+        precision,
+        perturbation: m.flag("perturbation"),
+        counter_width,
+        memory: m.get::<u64>("memory"),
+        dry_run: m.flag("dry-run"),
+        force: m.flag("force"),
+        append,
+        seed: m.get::<u64>("seed"),
     }
+
 }
 
-/// parses command line arguments
-///
-/// This function has a hard-coded set of CLI-args it knows. It will linearly iterate over the list
-/// of user-provided arguments and parse them, as it goes along. It will gracefully `panic!` with a
-/// customised and even somewhat helpful error message, thanks to the `parse!` macro.
-///
-/// There's one curious thing to consider: If a flag takes multiple arguments and the user didn't
-/// provide enough, but instead wrote another flag, this function will treat that flag as the
-/// argument to the previous flag and (*probably*) get a parse error there. That means one has to
-/// be slightly careful with flags and their order, and if parameters aren't provided, this ain't
-/// gonna fly... The `parse!` macro will output, that an argument is missing, if the flag is at the
-/// end of the list.
-///
-/// This function returns a tuple with this structure:
-/// ```
-/// (   (width, height),
-///     (complex1, complex2),
-///     filename,
-///     thread count,
-///     (samples, iterations, warmup, phase_len),
-///     (timeout, loggin_interval)
-/// )
-/// ```
-/// For what these mean, see the helptext and the docs of `butterbrot_run` (which takes most of
-/// these numbers as arguments).
+
+/* Rendering */
+
+/// computes the render, that the command line `args` describe, writes it to a birb, and exits
 ///
-/// If a flag isn't provided, default values are used in its place.
+/// The render is run as the `command`, `command()`, `serve_command()` or `worker_command()`, and
+/// `args` are its flags, its name first, see `distributed::role()`.
+pub fn run(command:&Command, args:Vec<String>) {
+
+    render(command, args);
+
+    // Whatever threads are still around, they're done
+    std::process::exit(0);
+
+}
+
+/// runs the command line `args` of the old `butterbrot` binary, the name of the program first
 ///
+/// That's `butterbrot [FLAGS]`, `butterbrot serve [FLAGS]`, `butterbrot worker [FLAGS]` or
+/// `butterbrot queue [FLAGS]`, see `old_style()` and `queue::run()`.
+pub fn run_old_style(args:Vec<String>) {
+
+    let args = old_style(args);
+
+    match args.get(1).map(|a| a.as_ref()) {
+        Some("queue")  => queue::run(args[1..].to_vec()),
+        Some("serve")  => run(&serve_command(), args[1..].to_vec()),
+        Some("worker") => run(&worker_command(), args[1..].to_vec()),
+        _              => run(&command(), args),
+    }
+
+    std::process::exit(0);
+
+}
+
+/// computes the render, that the command line `args` describe, writes it to a birb, and returns
+/// the name of the birb
 ///
-/// **Note:** This function is written specifically for this project, but it can easily be adapted
-/// for other projects, that only require primitive command-line facilities. Simply overwrite the
-/// type declaration and the defaults for the `output` variable, then use the type declaration of
-/// output as the return value of the function. Lastly change the `match` against the flags to suit
-/// the project's needs and adapt the `parse!` macro to reflect the new parsing needs (that
-/// shouldn't be hard: take out the bit about `math::Complex`, and add whatever rules and variants
-/// of rules you need and you're good to go), and this should be fine. Piece of cake.
-pub fn parse_args(args_v:Vec<String>) -> ( (u64,u64), (Complex,Complex), String, i32, (i32,i32,i32,i32), (u64,u64) ) {
-
-    let mut args = args_v.into_iter();
-    args.next();    // skip the name of the application
-
-    let mut zoom = 100f64;
-    let mut center = Complex::new(0f64, 0f64);
-
-    // TODO make the defaults and the help text align
-    // output has the format:
-    // ( (width, height), (c1, c2), filename, thread_count, (samples, iterations, warmup, phase_len), (timeout, logging_interval) )
-    let mut output: ( (u64,u64), (Complex,Complex), String, i32, (i32,i32,i32,i32), (u64,u64) ) = (
-        (400, 400),
-        (Complex::new(42.0, 42.0), Complex::new(42.0, 42.0)),
-        gen_filename("birb"),
-        7,
-        (400, 10, 100, 10_000),
-        (std::u64::MAX,10),
-        );
-
-    let mut next;
-
-    loop {
-
-        // Get the next flag
-        match args.next() {
-            Some(s) => next = s,
-            None    => break,
+/// This is `run()`, without exiting at the end, so the renders of a queue can be run one after the
+/// other. Dry runs, `--print-config`, workers and `--no-clobber`, that finds the birb there
+/// already, still exit right away.
+pub fn render(command:&Command, args:Vec<String>) -> String {
+
+    /* Parse Arguments and setup */
+
+    // Coordinators and workers have flags of their own
+    if command.name != "render" && args.iter().any(|a| a == "-h" || a == "--help") {
+        print!("{}", cli::help(command));
+        std::process::exit(0);
+    }
+
+    let (role, args)    = distributed::role(command.name, args);
+    let (clobber, args) = io::clobber(args);
+
+    // A worker computes, what the coordinator asks for, with its own arguments on top. It logs
+    // every second, unless told otherwise, as the units tend to be short.
+    let (connection, args) = match &role {
+        distributed::Role::Worker { connect } => {
+            let (connection, config) = distributed::Connection::open(connect);
+            let mut all = vec![args[0].clone()];
+            all.extend(config);
+            all.extend(vec!["--interval".to_string(), "1".to_string()]);
+            all.extend_from_slice(&args[1..]);
+            (Some(connection), all)
+        },
+        _ => (None, args),
+    };
+
+    let mut settings = parse_args(args.clone());
+
+    let RenderSettings { width, height, complex1: c1, complex2: c2, threads: thread_count, samples: sample_count, iterations, warmup, phase_len, timeout, deposit, stats, fractal, projection, mode, precision, perturbation, counter_width, memory, dry_run, force, seed, .. } = settings;
+    let (filename, append) = (settings.filename.clone(), settings.append.clone());
+
+    // Without a seed, any will do, as long as it's recorded
+    let seed = seed.unwrap_or_else(rand::random);
+
+    // Used to compute the 'total' time taken right at the end
+    let outer_timestamp = Instant::now();
+
+
+    /* Describe the birb */
+
+    let mut header = if stats && mode == Mode::Buddhabrot { io::Header::new(&STATS_CHANNELS) } else { io::Header::new(&["count"]) };
+    header.set("precision", precision.name().to_string());
+    if perturbation { header.set("perturbation", "true".to_string()) }
+    if precision.is_preview() {
+        header.set("preview", "true".to_string());
+        note!("Computing a low-precision preview, it won't combine with other renders");
+    }
+    if mode == Mode::EscapeTime { header.set("mode", "escape-time".to_string()) }
+
+    // What was rendered, see RENDER_KEYS. The numbers are written with all their digits.
+    let exact   = |x:math::DoubleDouble| if x.lo == 0f64 { format!("{:?}", x.hi) } else { format!("{:?}{:+?}", x.hi, x.lo) };
+    let complex = |c:math::Complex<math::DoubleDouble>| format!("{} {}", exact(c.r), exact(c.i));
+    header.set("complex1", complex(c1));
+    header.set("complex2", complex(c2));
+    header.set("fractal", match fractal {
+        math::Fractal::Mandelbrot { z0 } => format!("mandelbrot {}", complex(z0)),
+        math::Fractal::Julia { c }       => format!("julia {}", complex(c)),
+    });
+    header.set("projection", projection.m.iter().flatten().map(|x| format!("{:?}", x)).collect::<Vec<_>>().join(" "));
+    header.set("iterations", iterations.to_string());
+    header.set("deposit", deposit.name().to_string());
+
+    // An appended birb holds the samples of more than one seed
+    if append.is_none() { header.set("seed", seed.to_string()) }
+
+    let channels = header.channels.len() as u64;
+
+    // Which of the channels hold two's complements, that wrap around
+    let signed: Vec<bool> = header.channels.iter().map(|c| SIGNED_CHANNELS.contains(&c.as_ref())).collect();
+
+
+    /* Check, where the birb goes */
+
+    // The filename may be a template, that's filled in with what's known about the birb
+    let filename = match (&append, &connection) {
+        (None, None) => {
+            let mut values = io::template_values(&header, width, height);
+            values.push(("samples".to_string(), sample_count.to_string()));
+            io::fill_template(&filename, &values, clobber)
+        },
+        _ => filename,
+    };
+    settings.filename = filename.clone();
+
+    // No other render of the process may write it, until this one is done, like another job of a
+    // queue. A worker sends its birbs to the coordinator, and a dry run doesn't write anything.
+    let _reservation = if connection.is_none() && !dry_run { Some(io::Reservation::new(&filename)) } else { None };
+
+    // Better to find out now, than after hours of computing. An appended birb is replaced anyway.
+    if append.is_none() && connection.is_none() && !dry_run {
+        io::check_output(&filename, clobber);
+    }
+
+
+    /* Check the birb to append to */
+
+    // A worker only computes units, the coordinator does the appending
+    let (previous_samples, counter_width) = match (&append, &connection) {
+
+        (Some(existing), None) => {
+
+            if mode == Mode::EscapeTime {
+                error!(Err(()), "Escape-time images are the same every time, there's nothing to append to them.");
+            }
+
+            let found = io::read_header(existing);
+
+            let samples = appendable(&found, io::read_dimensions(existing), &header, (width, height)).unwrap_or_else(|reason| fail(format!("Can't append to the birb '{}': {}", existing, reason)));
+
+            note!("Appending to \x1B[34m{}\x1B[0m, which holds \x1B[33m{}\x1B[0m samples", existing, samples);
+
+            (samples, counter_width.wider(found.counter_width()))
+
+        },
+
+        _ => (0, counter_width),
+
+    };
+
+    // The coordinates come in as DoubleDoubles, the computation runs with whichever Scalar type the
+    // precision asks for, so the frame and the generator of the Orbits are made for that type.
+    macro_rules! setup {
+        ($scalar:ty) => {{
+
+            let frame = math::Frame::<$scalar>::new(c1.cast(), c2.cast(), width, height);
+
+            let generator = if perturbation {
+                note!("Computing the reference Orbit");
+                math::OrbitGenerator::Perturbation(math::Reference::new(fractal.cast(), frame.center(), iterations))
+            } else {
+                math::OrbitGenerator::Direct(fractal.cast())
+            };
+
+            (frame, generator)
+
+        }};
+    }
+
+
+    /* Check, whether it's going to fit */
+
+    let whole = deposit != Deposit::Points || stats;
+
+    // Escape-time images are computed by rayon's threads, one per core, and don't collect Orbits.
+    // A coordinator doesn't compute at all.
+    let (threads, orbit_phase) = match (mode, &role) {
+        (_, distributed::Role::Serve { .. }) => (0, 0),
+        (Mode::Buddhabrot, _)                => (thread_count as u64, phase_len),
+        (Mode::EscapeTime, _)                => (std::thread::available_parallelism().map(|n| n.get() as u64).unwrap_or(1), 0),
+    };
+
+    let estimate = estimate::Estimate {
+        birb:      estimate::birb(width, height, channels, counter_width, stats && mode == Mode::Buddhabrot, memory.map(|m| m * 1_000_000)),
+        orbits:    estimate::orbits(orbit_phase, iterations, precision),
+        reference: if perturbation { estimate::reference(iterations, precision) } else { 0 },
+        threads,
+    };
+
+    let available = estimate::available_memory();
+    let too_large = available.map(|a| estimate.total() > a).unwrap_or(false);
+
+    if dry_run {
+
+        let (d1, d2): (math::Complex, math::Complex) = (c1.cast(), c2.cast());
+
+        out!("Dry run, nothing is computed\n");
+        out!("birb:         \x1B[33m{}\x1B[0mx\x1B[33m{}\x1B[0m, {} channel(s), precision \x1B[33m{}\x1B[0m", width, height, channels, precision.name());
+        out!("filename:     \x1B[34m{}\x1B[0m", filename);
+        out!("complex1:     {{ r: \x1B[33m{}\x1B[0m, i: \x1B[33m{}\x1B[0m }}", d1.r, d1.i);
+        out!("complex2:     {{ r: \x1B[33m{}\x1B[0m, i: \x1B[33m{}\x1B[0m }}", d2.r, d2.i);
+        out!();
+        out!("memory of the birb:         {}", estimate::human_bytes(estimate.birb));
+        out!("Orbits of a phase:          {} per thread", estimate::human_bytes(estimate.orbits));
+        out!("reference Orbits:           {} per thread", estimate::human_bytes(estimate.reference));
+        out!("all together, at the most:  \x1B[33m{}\x1B[0m with {} thread(s), of {} available", estimate::human_bytes(estimate.total()), threads, available.map(estimate::human_bytes).unwrap_or_else(|| "unknown".to_string()));
+        if too_large { out!("\x1B[33;1mWarning:\x1B[0m That's more memory than there is, the computation won't start without '--force'.") }
+        out!();
+
+        out!("Computing for up to {} seconds, to see how fast it goes", estimate::CALIBRATION.as_secs());
+
+        macro_rules! calibrate {
+            ($scalar:ty) => {{
+                let (frame, generator) = setup!($scalar);
+                match mode {
+                    Mode::Buddhabrot => estimate::calibrate(frame, &generator, iterations, whole, projection),
+                    Mode::EscapeTime => estimate::calibrate_escape_time(&frame, &generator, width, height, iterations),
+                }
+            }};
         }
 
-        // Parse the flag and its arguments
-        match next.as_ref() {
-
-            "--width"      | "-w"   => { (output.0).0 = parse!("--width",      args, u64)     },
-            "--height"     | "-h"   => { (output.0).1 = parse!("--height",     args, u64)     },
-            "--zoom"       | "-z"   => {  zoom        = parse!("--zoom",       args, f64)     },
-            "--warmup"     | "-wu"  => { (output.4).2 = parse!("--warmup",     args, i32)     },
-            "--timeout"    | "-to"  => { (output.5).0 = parse!("--timeout",    args, u64)     },
-            "--interval"   | "-int" => { (output.5).1 = parse!("--interval",   args, u64)     },
-            "--filename"   | "-o"   => {  output.2    = parse!("--filename",   args, string)  },
-            "--threads"    | "-t"   => {  output.3    = parse!("--threads",    args, i32)     },
-            "--samples"    | "-s"   => { (output.4).0 = parse!("--samples",    args, i32)     },
-            "--complex1"   | "-c1"  => { (output.1).0 = parse!("--complex1",   args, complex) },
-            "--complex2"   | "-c2"  => { (output.1).1 = parse!("--complex2",   args, complex) },
-            "--center"     | "-c"   => {  center      = parse!("--center",     args, complex) },
-            "--phase_len"  | "-p"   => { (output.4).3 = parse!("--phase_len",  args, i32)     },
-            "--iterations" | "-i"   => { (output.4).1 = parse!("--iterations", args, i32)     },
-            "--help"       | "h"    => {
-                println!("USAGE:\n\n  butterbrot [ARGUMENTS]\n\n\nPOSSIBLE FLAGS AND WHAT THEY MEAN:\n\n  h, --help\n        Display this help text.\n\n  -o, --filename <filename>\n        The filename to write the computed data to. This will be a birb file.\n\n        Default: birb_{{rand}}.birb, where {{rand}} will be turned into a random\n        string, to insure the file is available.\n\n  -t, --threads <number>\n        How many threads to use for parallel computation. Note, that this is the\n        number of computation threads. The total number of threads is one\n        larger, as this doesn't include the main thread.  This works better, if\n        the total number of threads doesn't exceed the number of available\n        cores.\n\n        Default: 7\n\n  -to, --timeout <seconds>\n        How many whole seconds to run AT MINIMUM, before the program terminates\n        the computation. Note, that the program will finish some time after the\n        timeout has been reached, as each thread will finish the currently\n        active computation before returning.\n        If no timeout is specified this value will be set to the larges possible\n        unsigned 64-Bit integer, a number of seconds, that is unlikely to be\n        reached, while computation is active.\n\n  -int, --interval <seconds>\n        The logging function will attempt to output a log only after <seconds>\n        seconds have elapsed.\n\n        Default: 10\n\n  -w, --width <number>\n        How wide to make the birb.\n\n        Default: 400\n\n  -h, --height <number>\n        How tall to make the birb.\n\n        Default: 400\n\n  -z, --zoom <number>\n        How much to zoom in.\n        This zoom factor is used to map the --width and the --height onto\n        the Complex plane, relative to the complex number specified using\n        --center.\n\n        This flag is overridden by either of --complex1 and --complex2.\n\n        Using the --zoom and --center flags to control the image is more\n        convenient, than using --complex1 and --complex2 directly, since the\n        zoom method preserves the image ratio.\n\n        The <number> may be a float.\n\n        Default: 100\n\n  -c, --center <real> <imaginairy>\n        The complex number, that should be in the center point of the final\n        image.\n\n        Default: 0 0\n\n  -wu, --warmup <number>\n        How many samples should the Metropolis-Hastings Iterators discard as\n        warmup. See documentation for more.\n\n        Default: 1000\n\n  -s, --samples <number>\n        How many samples should the program compute in total, across all\n        threads. This does not include the warmup.\n\n        Default: 10000\n\n  -i, --iterations <number>\n        How many iterations long should each Orbit be at max. See documentation\n        for more.\n\n        Default: 100\n\n  -p, --phase_len <number>\n        How many Metropolis Hastings Orbits each thread computes before calling\n        write_back -- The length of a write_back phase.\n\n        Default: 10000\n\n  -c1, --complex1 <real> <imaginairy>\n        One of the corners of the frame of the Complex Plane that is to be\n        explored. This must be a diagonally opposite corner to --complex1.\n        The real and imaginairy parts must be floats.\n\n        Default: 42.0 42.0\n\n  -c2, --complex2 <real> <imaginairy>\n        One of the corners of the frame of the Complex Plane that is to be\n        explored. This must be a diagonally opposite corner to --complex1.\n        The real and imaginairy parts must be floats.\n\n        Default: 42.0 42.0\n");
-                std::process::exit(0);
-
-            },
-
-            s => { parse!(s, error) },
+        let rate = match precision {
+            math::Precision::F32          => calibrate!(f32),
+            math::Precision::F64          => calibrate!(f64),
+            math::Precision::DoubleDouble => calibrate!(math::DoubleDouble),
+        };
+
+        // Every thread warms up on its own, the samples are shared
+        let seconds = match mode {
+            Mode::Buddhabrot => warmup as f64 / rate + sample_count as f64 / (rate * threads as f64),
+            Mode::EscapeTime => (width * height) as f64 / (rate * threads as f64),
+        };
+
+        out!("speed:              \x1B[33m{:.0}\x1B[0m {} per second and thread", rate, if mode == Mode::Buddhabrot { "samples" } else { "pixels" });
+        out!("estimated runtime:  \x1B[33m{}\x1B[0m{}", estimate::human_duration(seconds), if mode == Mode::Buddhabrot && seconds > timeout.as_secs_f64() { format!(", but the timeout stops it after {}", estimate::human_duration(timeout.as_secs_f64())) } else { String::new() });
+
+        std::process::exit(0);
+
+    }
+
+    if too_large && !force {
+        fail(format!("The computation is estimated to take up to {} of memory, but only {} are available. Try '--memory', fewer '--threads' or a shorter '--phase_len', see '--dry-run' for the details, or start it anyway with '--force'.", estimate::human_bytes(estimate.total()), estimate::human_bytes(available.unwrap_or(0))))
+    }
+
+
+    // A coordinator needs to be reachable, before it starts
+    let listener = match &role {
+        distributed::Role::Serve { listen, .. } => {
+            if mode == Mode::EscapeTime {
+                error!(Err(()), "Escape-time images are computed in no time, they can't be distributed.");
+            }
+            Some(error!(std::net::TcpListener::bind(listen), "Couldn't listen for workers at the given address.", full))
+        },
+        _ => None,
+    };
+
+
+    /* Do the actual thing */
+
+    // The computation deposits into whichever kind of counters there are, so the very same code
+    // gets compiled once per type. `seed` is the random stream to draw from, see math::seed_thread().
+    // It comes to how many samples were computed.
+    macro_rules! compute {
+        ($scalar:ty, $birb:expr, $samples:expr, $seed:expr) => {{
+
+            let birb = $birb;
+
+            let (frame, generator) = setup!($scalar);
+
+            match mode {
+
+                Mode::Buddhabrot => {
+
+                    butterbrot_run::<$scalar, _>(Arc::clone(birb), &settings, generator, $samples, $seed)
+
+                },
+
+                Mode::EscapeTime => {
+
+                    note!("Computing an escape-time image of {y}{}{w}x{y}{}{w} with {y}{}{w} iterations", width, height, iterations, y = "\x1B[33m", w = "\x1B[0m");
+
+                    let mut birb = error!(birb.lock(), "Couldn't acquire Mutex Lock for computing the escape-time image!");
+
+                    escape_time_run(&mut *birb, iterations, &frame, &generator);
+
+                    0
+
+                },
+
+            }
+        }};
+    }
+
+    macro_rules! compute_with {
+        ($birb:expr, $samples:expr, $seed:expr) => {
+            match precision {
+                math::Precision::F32          => compute!(f32, $birb, $samples, $seed),
+                math::Precision::F64          => compute!(f64, $birb, $samples, $seed),
+                math::Precision::DoubleDouble => compute!(math::DoubleDouble, $birb, $samples, $seed),
+            }
+        };
+    }
+
+    // A coordinator has the workers compute, and only sums up their birbs, until all are done
+    macro_rules! compute_or_serve {
+        ($birb:expr) => {
+            match (listener, &role) {
+                (Some(listener), distributed::Role::Serve { unit, .. }) => {
+                    let unit = unit.unwrap_or((sample_count / 100).max(phase_len));
+                    // The workers get the options resolved, they may not have the configuration
+                    let mut config = resolve(&args).arguments(&config::COMMAND_LINE_ONLY);
+                    config.extend(vec!["--seed".to_string(), seed.to_string()]);
+                    distributed::serve(listener, config, Arc::clone($birb), signed.clone(), sample_count, unit, distributed::TIMEOUT);
+                    sample_count
+                },
+                _ => compute_with!($birb, sample_count, Some(seed)),
+            }
+        };
+    }
+
+
+    /* Work for a coordinator */
+
+    if let Some(mut connection) = connection {
+
+        while let Some(unit) = connection.next_unit() {
+
+            note!("Computing unit {y}{}{w} of {y}{}{w} samples", unit.id, unit.samples, y = "\x1B[33m", w = "\x1B[0m");
+
+            let birb = Arc::new(Mutex::new(counters::Counters::new(width, height, channels, counter_width)));
+
+            // Every unit draws from streams of its own
+            let heartbeat = connection.heartbeat(unit);
+            compute_with!(&birb, unit.samples, Some(math::stream(seed, unit.id)));
+            drop(heartbeat);
+
+            connection.send(unit, &error!(birb.lock(), "Couldn't acquire Mutex Lock for sending the birb!").to_birb());
 
         }
 
+        note!("\x1B[32mNothing left to do.\x1B[0m");
+        std::process::exit(0);
+
     }
 
-    // Compute coordinates of the frame of the Complex plane, if none have been provided
-    if (output.1).0.r == 42.0 && (output.1).0.i == 42.0 && (output.1).1.r == 42.0 && (output.1).1.i == 42.0 {
 
-        let step_size = 1f64 / zoom;
-        let frame_width  = (output.0).0 as f64 * step_size;
-        let frame_height = (output.0).1 as f64 * step_size;
+    /* Compute, and write data to file */
+
+    // The samples of this computation are added to the ones of the birb appended to
+    let writing = |samples:u64| {
+        note!("\nNow writing to file \x1B[34m{}\x1B[0m", filename);
+        let mut header = header.clone();
+        if mode == Mode::Buddhabrot { header.set("samples", previous_samples.saturating_add(samples).to_string()) }
+        header
+    };
+
+    match memory {
+
+        // All the counters in memory
+        None => {
+
+            let birb = Arc::new(Mutex::new(counters::Counters::new(width, height, channels, counter_width)));
+
+            if let Some(existing) = &append {
+                io::read_into(existing, &mut *error!(birb.lock(), "Couldn't acquire Mutex Lock for loading the birb to append to!"), &signed);
+            }
 
-        let delta = Complex::new(frame_width / 2f64, frame_height / 2f64);
-        let c1 = center.sub(&delta);
-        let c2 = center.add(&delta);
+            let samples = compute_or_serve!(&birb);
 
-        (output.1).0 = c1;
-        (output.1).1 = c2;
+            let birb = error!(birb.lock(), "Couldn't acquire Mutex Lock for writing the birb to a file!");
 
+            io::write_counters(&filename, &birb, &writing(samples));
+
+        },
+
+        // Only as many tiles in memory as fit, the rest in a scratch file next to the birb
+        Some(megabytes) => {
+
+            let scratch = format!("{}.scratch", filename);
+            note!("Keeping \x1B[33m{}\x1B[0m MB of tiles in memory, the rest goes to \x1B[34m{}\x1B[0m", megabytes, scratch);
+
+            let birb = Arc::new(Mutex::new(tiles::TiledCounters::new(width, height, channels, megabytes * 1_000_000, &scratch)));
+
+            if let Some(existing) = &append {
+                io::read_into(existing, &mut *error!(birb.lock(), "Couldn't acquire Mutex Lock for loading the birb to append to!"), &signed);
+            }
+
+            let samples = compute_or_serve!(&birb);
+
+            let mut birb = error!(birb.lock(), "Couldn't acquire Mutex Lock for writing the birb to a file!");
+
+            io::write_tiled(&filename, &mut birb, &writing(samples));
+
+        },
+
+    }
+
+    note!("{g}Successfully wrote to file.{w}", g = "\x1B[32m", w = "\x1B[0m");
+
+    note!("Total time taken: {g}{}{w}", estimate::human_duration(outer_timestamp.elapsed().as_secs_f64()), g = "\x1B[32m", w = "\x1B[0m");
+
+    if log::format() == log::Format::Json {
+        log::event("written", &[("filename", log::json_string(&filename)), ("seconds", log::json_number(outer_timestamp.elapsed().as_secs_f64()))]);
+    }
+
+
+    filename
+
+}
+
+/// how many samples the birb with the `found` header and `size` holds, if the render with the
+/// `header` and `render_size` can be appended to it, or why not
+///
+/// It has to be the same render: the same size and channels, either both or neither a preview,
+/// and the same `RENDER_KEYS`. It has to say, how many samples it holds, too, or the sum wouldn't.
+pub fn appendable(found:&io::Header, size:(u64,u64), header:&io::Header, render_size:(u64,u64)) -> Result<u64, String> {
+
+    if size != render_size {
+        return Err(format!("it's {}x{}, not {}x{}.", size.0, size.1, render_size.0, render_size.1))
+    }
+
+    if found.channels != header.channels {
+        return Err(format!("it has the channels {}, not {}.", found.channels.join(", "), header.channels.join(", ")))
+    }
+
+    if found.get("preview") != header.get("preview") {
+        return Err("previews can only be combined with other previews.".to_string())
+    }
+
+    for key in RENDER_KEYS.iter() {
+        match (found.get(key), header.get(key)) {
+            (a, b) if a == b => (),
+            (None, _)        => return Err(format!("it doesn't record its {}, so there's no telling, whether it's the same render.", key)),
+            (Some(a), b)     => return Err(format!("it was rendered with {} '{}', not '{}'.", key, a, b.unwrap_or("none"))),
+        }
     }
 
-    output
+    found.get("samples").and_then(|s| s.parse().ok()).ok_or_else(|| "it doesn't record, how many samples it holds.".to_string())
 
 }
@@ -0,0 +1,897 @@
+//! The command line of the birb tools: a declarative parser, and the help texts and shell
+//! completions, that are generated from the very same declarations
+//!
+//!  - `Opt` declares an option: its names, the values it takes, its default, the environment
+//!    variable, that may stand in for it, and what its values have to look like
+//!  - `Command` declares a command of `birb`, with its options and positional arguments
+//!  - `parse()` checks a command line against a `Command`, and returns the `Matches`
+//!  - `extract()` takes only a few options out of a command line, and leaves the rest be
+//!  - `help()`, `overview()` and `completions()` make the help texts and the bash, zsh and fish
+//!    completions
+//!  - `man()`, `man_overview()` and `markdown()` make the man pages in `doc/man`, and the table
+//!    of the flags in the README, see `birb help --man` and `birb help --markdown`
+//!
+//! Options are given as `--name value`, `--name=value` or `-n value`. Options, that take more than
+//! one value, like `--center <real> <imaginary>`, take them as separate arguments. An option, that
+//! isn't given, is taken from a configuration file, if there's one, see `parse_with()`, then from
+//! its environment variable, if that's set, and from its default otherwise. The old spellings of
+//! the old binaries, like `-wu` and `-int`, are aliases, which still work, but aren't shown in the
+//! help.
+
+use std::fmt::Write;
+use super::log::fail;
+
+/// The name of the binary, that has all the commands
+pub const PROGRAM: &str = "birb";
+
+/// How wide the help texts are, at the most
+const WIDTH: usize = 80;
+
+/// What the values of an `Opt` have to look like
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Check {
+    Text,
+    Integer(u64, u64),      // a whole number, from the first to the second one
+    Number(f64, f64),       // a decimal number, from the first to the second one
+}
+
+/// An option of a `Command`
+///
+/// Options are made with `Opt::flag()` or `Opt::value()`, and the rest is added on, like
+/// `Opt::value("width", &["<number>"], "How wide to make the birb.").short('w').default("400")`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Opt {
+    pub name: &'static str,                                 // the long name, without the dashes
+    pub short: Option<char>,
+    pub aliases: &'static [&'static str],                   // other spellings, dashes included
+    pub values: &'static [&'static str],                    // the names of the values, empty for flags
+    pub choices: &'static [(&'static str, &'static str)],   // the only values allowed, and what they mean
+    pub default: Option<&'static str>,
+    pub env: Option<&'static str>,
+    pub check: Check,
+    pub repeats: bool,                                      // whether every occurrence counts
+    pub help: &'static str,
+}
+
+impl Opt {
+
+    /// an option without a value, that's either given or not
+    pub fn flag(name:&'static str, help:&'static str) -> Opt {
+        Opt { name, short: None, aliases: &[], values: &[], choices: &[], default: None, env: None, check: Check::Text, repeats: false, help }
+    }
+
+    /// an option, that takes `values`, named like they are in the help: `&["<real>", "<imaginary>"]`
+    pub fn value(name:&'static str, values:&'static [&'static str], help:&'static str) -> Opt {
+        Opt { values, ..Opt::flag(name, help) }
+    }
+
+    pub fn short(self, short:char) -> Opt {
+        Opt { short: Some(short), ..self }
+    }
+
+    pub fn aliases(self, aliases:&'static [&'static str]) -> Opt {
+        Opt { aliases, ..self }
+    }
+
+    /// allows only the `choices`, each with a description for the help
+    pub fn choices(self, choices:&'static [(&'static str, &'static str)]) -> Opt {
+        Opt { choices, ..self }
+    }
+
+    /// the values, if the option isn't given, separated by spaces, if there's more than one
+    pub fn default(self, default:&'static str) -> Opt {
+        Opt { default: Some(default), ..self }
+    }
+
+    /// the environment variable, that's used instead of the default, if it's set
+    pub fn env(self, env:&'static str) -> Opt {
+        Opt { env: Some(env), ..self }
+    }
+
+    /// allows only whole numbers from `min` to `max`
+    pub fn integer(self, min:u64, max:u64) -> Opt {
+        Opt { check: Check::Integer(min, max), ..self }
+    }
+
+    /// allows only numbers from `min` to `max`
+    pub fn number(self, min:f64, max:f64) -> Opt {
+        Opt { check: Check::Number(min, max), ..self }
+    }
+
+    /// keeps every occurrence, instead of just the last one
+    pub fn repeats(self) -> Opt {
+        Opt { repeats: true, ..self }
+    }
+
+    /// `--name`, the way it's spelled in the help and the errors
+    pub fn long(&self) -> String {
+        format!("--{}", self.name)
+    }
+
+    /// whether `token` is any of the spellings of the option
+    fn is(&self, token:&str) -> bool {
+        token == self.long() || self.short.map(|s| token == format!("-{}", s)).unwrap_or(false) || self.aliases.contains(&token)
+    }
+
+    /// checks the `values` of the option, which came from `source`, like `'--width 0'`
+    fn validate(&self, values:&[String], source:&str) {
+
+        for (value, name) in values.iter().zip(self.values.iter()) {
+
+            if !self.choices.is_empty() && !self.choices.iter().any(|(c, _)| c == value) {
+                let names: Vec<String> = self.choices.iter().map(|(c, _)| format!("'{}'", c)).collect();
+                fail(format!("Unknown {} '{}' in {}. Use {}.", name.trim_matches(|c| c == '<' || c == '>'), value, source, one_of(&names)))
+            }
+
+            let fits = match self.check {
+                Check::Text              => true,
+                Check::Integer(min, max) => value.parse::<u64>().map(|n| n >= min && n <= max).unwrap_or(false),
+                Check::Number(min, max)  => value.parse::<f64>().map(|n| n >= min && n <= max).unwrap_or(false),
+            };
+
+            if !fits { fail(format!("Couldn't use '{}' in {}, {} must be {}.", value, source, name, self.range())) }
+
+        }
+
+    }
+
+    /// what the `Check` asks for, in words
+    fn range(&self) -> String {
+        match self.check {
+            Check::Text                              => "text".to_string(),
+            Check::Integer(0, u64::MAX)              => "a whole number".to_string(),
+            Check::Integer(min, u64::MAX)            => format!("a whole number of at least {}", min),
+            Check::Integer(min, max)                 => format!("a whole number from {} to {}", min, max),
+            Check::Number(min, max) if min.is_infinite() && max.is_infinite() => "a number".to_string(),
+            Check::Number(min, _) if min == f64::MIN_POSITIVE => "a number larger than 0".to_string(),
+            Check::Number(min, max) if max.is_infinite() => format!("a number of at least {}", min),
+            Check::Number(min, max)                  => format!("a number from {} to {}", min, max),
+        }
+    }
+
+    /// the first sentence of the help, for the completions
+    fn summary(&self) -> String {
+        summary(self.help)
+    }
+
+}
+
+/// A positional argument of a `Command`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Positional {
+    pub name: &'static str,
+    pub required: bool,
+    pub many: bool,         // whether it takes all the remaining arguments, only for the last one
+    pub help: &'static str,
+}
+
+/// A command of `birb`, like `birb render`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    pub name: &'static str,
+    pub about: &'static str,        // a line, for the overview and the completions
+    pub help: &'static str,         // what it does, at the top of its help
+    pub options: Vec<Opt>,
+    pub positionals: Vec<Positional>,
+}
+
+impl Command {
+
+    /// the option spelled `token`, if there is one
+    pub fn option(&self, token:&str) -> Option<&Opt> {
+        self.options.iter().find(|o| o.is(token))
+    }
+
+    /// the usage line, like `birb convert [OPTIONS] <birb>`
+    pub fn usage(&self) -> String {
+
+        let mut usage = format!("{} {} [OPTIONS]", PROGRAM, self.name);
+
+        for p in self.positionals.iter() {
+            let name = if p.many { format!("{}...", p.name) } else { p.name.to_string() };
+            if p.required { write!(usage, " {}", name).unwrap() } else { write!(usage, " [{}]", name).unwrap() }
+        }
+
+        usage
+
+    }
+
+}
+
+
+/* Parsing */
+
+/// Where the values of an option came from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    Given,
+    Config,
+    Env,
+    Default,
+}
+
+/// What a command line came to: the values of the options, and the positional arguments
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Matches {
+    options: Vec<(&'static str, Vec<String>, Source)>,      // the long name, the values, and where they came from
+    pub positionals: Vec<String>,
+}
+
+impl Matches {
+
+    /// whether the option `name` was given on the command line
+    pub fn given(&self, name:&str) -> bool {
+        self.options.iter().any(|(n, _, s)| *n == name && *s == Source::Given)
+    }
+
+    /// whether the flag `name` is set, on the command line, in a configuration or by its environment
+    /// variable
+    pub fn flag(&self, name:&str) -> bool {
+        self.options.iter().any(|(n, _, _)| *n == name)
+    }
+
+    /// the values of the option `name`, the last ones given, if it repeats
+    pub fn values(&self, name:&str) -> Option<&[String]> {
+        self.options.iter().rev().find(|(n, _, _)| *n == name).map(|(_, v, _)| v.as_slice())
+    }
+
+    /// the values of every time the option `name` was given, in order
+    pub fn all(&self, name:&str) -> Vec<&[String]> {
+        self.options.iter().filter(|(n, _, _)| *n == name).map(|(_, v, _)| v.as_slice()).collect()
+    }
+
+    /// the first value of the option `name`
+    pub fn value(&self, name:&str) -> Option<&str> {
+        self.values(name).and_then(|v| v.first()).map(|v| v.as_ref())
+    }
+
+    /// the value of the option `name`, as a `T`
+    pub fn get<T: std::str::FromStr>(&self, name:&str) -> Option<T> {
+        self.value(name).map(|v| parse_value(v, name))
+    }
+
+    /// all the values of the option `name`, each as a `T`
+    pub fn get_all<T: std::str::FromStr>(&self, name:&str) -> Option<Vec<T>> {
+        self.values(name).map(|v| v.iter().map(|v| parse_value(v, name)).collect())
+    }
+
+    /// where the values of the option `name` came from, if it has any
+    pub fn source(&self, name:&str) -> Option<Source> {
+        self.options.iter().rev().find(|(n, _, _)| *n == name).map(|(_, _, s)| *s)
+    }
+
+    /// the options given on the command line or in a configuration as command line arguments,
+    /// except for the ones named in `except`
+    pub fn arguments(&self, except:&[&str]) -> Vec<String> {
+        let mut args = Vec::new();
+        for (name, values, source) in self.options.iter() {
+            if *source == Source::Env || *source == Source::Default || except.contains(name) { continue }
+            args.push(format!("--{}", name));
+            args.extend(values.iter().cloned());
+        }
+        args
+    }
+
+    fn add(&mut self, opt:&Opt, values:Vec<String>, source:Source) {
+        if !opt.repeats { self.options.retain(|(n, _, _)| *n != opt.name) }
+        self.options.push((opt.name, values, source));
+    }
+
+    /// fills in the options, that weren't given, from their environment variables and defaults
+    fn fill(&mut self, options:&[Opt]) {
+
+        for opt in options.iter() {
+
+            if self.flag(opt.name) { continue }
+
+            let env = opt.env.and_then(|e| std::env::var(e).ok().filter(|v| !v.is_empty()).map(|v| (e, v)));
+
+            match (env, opt.default) {
+
+                (Some((var, value)), _) => {
+                    // A flag is set by anything, but '0' or 'false'
+                    if opt.values.is_empty() {
+                        if value != "0" && value != "false" { self.add(opt, Vec::new(), Source::Env) }
+                        continue
+                    }
+                    let values = split(&value, opt.values.len());
+                    check_count(opt, &values, &format!("the environment variable '{}'", var));
+                    opt.validate(&values, &format!("the environment variable '{}'", var));
+                    self.add(opt, values, Source::Env);
+                },
+
+                (None, Some(default)) => self.add(opt, split(default, opt.values.len()), Source::Default),
+
+                (None, None) => (),
+
+            }
+
+        }
+
+    }
+
+}
+
+fn parse_value<T: std::str::FromStr>(value:&str, name:&str) -> T {
+    value.parse().unwrap_or_else(|_| fail(format!("Couldn't parse '{}' for '--{}'.", value, name)))
+}
+
+/// `value` as `n` values: the words of it, or all of it, if there's only one
+fn split(value:&str, n:usize) -> Vec<String> {
+    if n == 1 { vec![value.to_string()] } else { value.split_whitespace().map(|s| s.to_string()).collect() }
+}
+
+fn check_count(opt:&Opt, values:&[String], source:&str) {
+    if values.len() != opt.values.len() {
+        fail(format!("{} needs {} value(s): {}.", source, opt.values.len(), opt.values.join(" ")))
+    }
+}
+
+/// `'a', 'b' or 'c'`
+fn one_of(names:&[String]) -> String {
+    match names.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("one of {} or {}", rest.join(", "), last),
+        Some((last, _))                        => last.clone(),
+        None                                   => String::new(),
+    }
+}
+
+/// reads the values of `opt`, which was spelled `token`, maybe with an `inline` value after a `=`
+fn take_values(opt:&Opt, token:&str, inline:Option<String>, args:&mut impl Iterator<Item=String>) -> Vec<String> {
+
+    if opt.values.is_empty() {
+        if inline.is_some() { fail(format!("The flag '{}' doesn't take a value.", token)) }
+        return Vec::new()
+    }
+
+    let mut values: Vec<String> = inline.into_iter().collect();
+
+    while values.len() < opt.values.len() {
+        match args.next() {
+            Some(v) => values.push(v),
+            None    => fail(format!("The flag '{}' expects {} argument(s): {}.", token, opt.values.len(), opt.values.join(" "))),
+        }
+    }
+
+    opt.validate(&values, &format!("'{} {}'", token, values.join(" ")));
+
+    values
+
+}
+
+/// `--name=value` as the name and the value, anything else as it is
+fn split_inline(token:&str) -> (&str, Option<String>) {
+    match token.find('=') {
+        Some(i) if token.starts_with("--") => (&token[..i], Some(token[i + 1..].to_string())),
+        _                                  => (token, None),
+    }
+}
+
+/// parses the command line `args`, without the name of the program, against `command`
+///
+/// `-h` and `--help` print the help of the command, and exit. Everything, that isn't right, stops
+/// with an error: unknown options, missing values, values out of their range, and too many or too
+/// few positional arguments. Options, that aren't given, are filled in from their environment
+/// variables and defaults. An option given more than once counts the last time, unless it
+/// `repeats`. Anything after a `--` is a positional argument.
+pub fn parse(command:&Command, args:&[String]) -> Matches {
+    parse_with(command, args, &[])
+}
+
+/// parses the command line `args` like `parse()`, with the options of a configuration beneath them
+///
+/// `config` are the long names of options and their values, see `config::options()`. They're
+/// checked like the ones on the command line, and fill in the options, that aren't given there,
+/// before the environment variables and the defaults do. An option, that repeats, is taken either
+/// from the command line or from the configuration, not from both.
+pub fn parse_with(command:&Command, args:&[String], config:&[(String, Vec<String>)]) -> Matches {
+
+    let mut matches = Matches::default();
+    let mut args = args.iter().cloned();
+    let mut rest_positional = false;
+
+    while let Some(token) = args.next() {
+
+        // Negative numbers are values, not flags
+        let flag = token.starts_with('-') && token.len() > 1 && token.parse::<f64>().is_err();
+
+        if rest_positional || !flag {
+            matches.positionals.push(token);
+            continue
+        }
+
+        match token.as_ref() {
+            "--"             => { rest_positional = true; continue },
+            "-h" | "--help"  => { print!("{}", help(command)); std::process::exit(0) },
+            _                => (),
+        }
+
+        let (name, inline) = split_inline(&token);
+
+        let opt = command.option(name).unwrap_or_else(|| fail(format!("Encountered invalid flag: '{}', see '{} {} --help' for the ones there are.", name, PROGRAM, command.name)));
+
+        let values = take_values(opt, name, inline, &mut args);
+        matches.add(opt, values, Source::Given);
+
+    }
+
+    // The positional arguments, the last one may take all the rest
+    let required = command.positionals.iter().filter(|p| p.required).count();
+    let many     = command.positionals.last().map(|p| p.many).unwrap_or(false);
+
+    if matches.positionals.len() < required {
+        fail(format!("Missing {}, see '{} {} --help'.", command.positionals[matches.positionals.len()].name, PROGRAM, command.name))
+    }
+
+    if !many && matches.positionals.len() > command.positionals.len() {
+        fail(format!("Unexpected argument '{}', see '{} {} --help'.", matches.positionals[command.positionals.len()], PROGRAM, command.name))
+    }
+
+    for (name, values) in config.iter() {
+
+        let opt = command.options.iter().find(|o| o.name == name).unwrap_or_else(|| fail(format!("There's no option '{}'.", name)));
+        if matches.given(name) { continue }
+
+        let source = format!("'{} = {}' of the configuration", name, values.join(" "));
+        check_count(opt, values, &source);
+        opt.validate(values, &source);
+        matches.add(opt, values.clone(), Source::Config);
+
+    }
+
+    matches.fill(&command.options);
+
+    matches
+
+}
+
+/// takes the `options` out of the command line `args`, wherever they are, and returns them, and
+/// the arguments left
+///
+/// Everything else is left as it is, for another parser. The options are checked, and filled in
+/// from their environment variables and defaults, just like `parse()` does.
+pub fn extract(options:&[Opt], args:Vec<String>) -> (Matches, Vec<String>) {
+
+    let mut matches = Matches::default();
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(token) = args.next() {
+
+        let (name, inline) = split_inline(&token);
+
+        match options.iter().find(|o| o.is(name)) {
+            Some(opt) => { let values = take_values(opt, name, inline, &mut args); matches.add(opt, values, Source::Given) },
+            None      => rest.push(token),
+        }
+
+    }
+
+    matches.fill(options);
+
+    (matches, rest)
+
+}
+
+
+/* Help */
+
+/// the first sentence of `text`
+fn summary(text:&str) -> String {
+    let first = text.split("\n\n").next().unwrap_or("");
+    match first.find(". ") {
+        Some(i) => first[..=i].to_string(),
+        None    => first.to_string(),
+    }
+}
+
+/// the paragraphs of `text`, separated by empty lines, wrapped to `WIDTH` and indented by `indent`
+/// spaces
+fn wrap(text:&str, indent:usize) -> String {
+
+    let mut out = String::new();
+
+    for (k, paragraph) in text.split("\n\n").enumerate() {
+
+        if k > 0 { out.push('\n') }
+
+        let mut line = " ".repeat(indent);
+
+        for word in paragraph.split_whitespace() {
+            if !line.trim().is_empty() && line.len() + 1 + word.len() > WIDTH {
+                out.push_str(line.trim_end());
+                out.push('\n');
+                line = " ".repeat(indent);
+            }
+            if !line.trim().is_empty() { line.push(' ') }
+            line.push_str(word);
+        }
+
+        out.push_str(line.trim_end());
+        out.push('\n');
+
+    }
+
+    out
+
+}
+
+/// the spellings of `opt` and its values, like `-w, --width <number>`
+fn spelling(opt:&Opt) -> String {
+    let short = opt.short.map(|s| format!("-{}, ", s)).unwrap_or_default();
+    let values = opt.values.iter().map(|v| format!(" {}", v)).collect::<String>();
+    format!("{}{}{}", short, opt.long(), values)
+}
+
+/// the help of `opt`, indented like the rest of the help
+pub fn option_help(opt:&Opt) -> String {
+
+    let mut out = format!("  {}\n", spelling(opt));
+    out.push_str(&wrap(opt.help, 8));
+
+    if !opt.choices.is_empty() {
+
+        out.push('\n');
+        let column = opt.choices.iter().map(|(c, _)| c.len()).max().unwrap_or(0) + 2;
+
+        for (choice, meaning) in opt.choices.iter() {
+            let text = wrap(meaning, 10 + column);
+            write!(out, "          {:w$}{}", choice, text.trim_start(), w = column).unwrap();
+        }
+
+    }
+
+    let default = opt.default.map(|d| format!("Default: {}", d));
+    let env     = opt.env.map(|e| format!("Environment: {}", e));
+
+    if default.is_some() || env.is_some() {
+        out.push('\n');
+        default.into_iter().chain(env).for_each(|l| writeln!(out, "        {}", l).unwrap());
+    }
+
+    out
+
+}
+
+/// the whole help of `command`, as `birb <command> --help` prints it
+pub fn help(command:&Command) -> String {
+
+    let mut out = format!("USAGE:\n\n  {}\n\n", command.usage());
+
+    out.push_str(&wrap(command.help, 2));
+
+    if !command.positionals.is_empty() {
+        out.push_str("\n\nARGUMENTS:\n");
+        for p in command.positionals.iter() {
+            write!(out, "\n  {}\n{}", p.name, wrap(p.help, 8)).unwrap();
+        }
+    }
+
+    out.push_str("\n\nPOSSIBLE FLAGS AND WHAT THEY MEAN:\n\n  -h, --help\n        Display this help text.\n");
+
+    for opt in command.options.iter() {
+        out.push('\n');
+        out.push_str(&option_help(opt));
+    }
+
+    out
+
+}
+
+/// the help of `birb` itself, with all the `commands`
+pub fn overview(commands:&[Command]) -> String {
+
+    let mut out = format!("USAGE:\n\n  {} <command> [OPTIONS]\n\n\nCOMMANDS:\n\n", PROGRAM);
+
+    let column = commands.iter().map(|c| c.name.len()).max().unwrap_or(0) + 2;
+
+    for c in commands.iter() {
+        write!(out, "  {:w$}{}", c.name, wrap(c.about, 2 + column).trim_start(), w = column).unwrap();
+    }
+
+    out.push('\n');
+    out.push_str(&wrap(&format!("See '{} <command> --help', or '{} help <command>', for what a command does, and the flags it takes.", PROGRAM, PROGRAM), 0));
+
+    out
+
+}
+
+
+/* Man Pages and the README */
+
+/// `text` with the characters, that mean something to roff, escaped
+fn roff(text:&str) -> String {
+    text.replace('\\', "\\e").replace('-', "\\-")
+}
+
+/// the paragraphs of `text` as roff paragraphs, separated by the request `between`, `.PP` or `.IP`
+/// to stay indented, none of whose lines start with a roff request
+fn roff_paragraphs(text:&str, between:&str) -> String {
+    text.split("\n\n").map(|p| format!("\\&{}\n", roff(&p.split_whitespace().collect::<Vec<_>>().join(" ")))).collect::<Vec<_>>().join(&format!("{}\n", between))
+}
+
+/// the start of a man page, with its title and its NAME
+fn man_head(title:&str, name:&str, about:&str) -> String {
+    format!(".TH {} 1 \"\" \"butterbrot_rs {}\" \"birb manual\"\n.SH NAME\n{} \\- {}\n",
+        title, env!("CARGO_PKG_VERSION"), roff(name), roff(about))
+}
+
+/// the man page of `command`, as it's in `doc/man/birb-<command>.1`
+pub fn man(command:&Command) -> String {
+
+    let mut out = man_head(&format!("{}-{}", PROGRAM, command.name).to_uppercase(), &format!("{}-{}", PROGRAM, command.name), command.about);
+
+    write!(out, ".SH SYNOPSIS\n.B {}\n.SH DESCRIPTION\n{}", roff(&command.usage()), roff_paragraphs(command.help, ".PP")).unwrap();
+
+    if !command.positionals.is_empty() {
+        out.push_str(".SH ARGUMENTS\n");
+        for p in command.positionals.iter() {
+            write!(out, ".TP\n.B {}\n{}", roff(p.name), roff_paragraphs(p.help, ".IP")).unwrap();
+        }
+    }
+
+    out.push_str(".SH OPTIONS\n.TP\n.B \\-h, \\-\\-help\nDisplay the help text.\n");
+
+    for opt in command.options.iter() {
+
+        write!(out, ".TP\n.B {}\n{}", roff(&spelling(opt)), roff_paragraphs(opt.help, ".IP")).unwrap();
+
+        if !opt.choices.is_empty() {
+            out.push_str(".RS\n");
+            for (choice, meaning) in opt.choices.iter() {
+                write!(out, ".TP\n.B {}\n{}", roff(choice), roff_paragraphs(meaning, ".IP")).unwrap();
+            }
+            out.push_str(".RE\n");
+        }
+
+        if let Some(default) = opt.default {
+            write!(out, ".IP\nDefault: {}\n", roff(default)).unwrap();
+        }
+
+    }
+
+    let env: Vec<&Opt> = command.options.iter().filter(|o| o.env.is_some()).collect();
+
+    if !env.is_empty() {
+        out.push_str(".SH ENVIRONMENT\n");
+        for opt in env {
+            write!(out, ".TP\n.B {}\nStands in for {}, if that isn't given.\n", opt.env.unwrap(), roff(&opt.long())).unwrap();
+        }
+    }
+
+    write!(out, ".SH SEE ALSO\n.BR {} (1)\n", PROGRAM).unwrap();
+
+    out
+
+}
+
+/// the man page of `birb` itself, with all the `commands`, as it's in `doc/man/birb.1`
+pub fn man_overview(commands:&[Command]) -> String {
+
+    let mut out = man_head(&PROGRAM.to_uppercase(), PROGRAM, "render Buddhabrots into birbs, and turn them into images");
+
+    write!(out, ".SH SYNOPSIS\n.B {} <command> [OPTIONS]\n.SH COMMANDS\n", PROGRAM).unwrap();
+
+    for c in commands.iter() {
+        write!(out, ".TP\n.B {}\n{}\n", c.name, roff(c.about)).unwrap();
+    }
+
+    out.push_str(".SH SEE ALSO\n");
+    let pages: Vec<String> = commands.iter().map(|c| format!(".BR {}\\-{} (1)", PROGRAM, c.name)).collect();
+    out.push_str(&pages.join(",\n"));
+    out.push('\n');
+
+    out
+
+}
+
+/// the table of the flags of `command`, in markdown, as it's in the README
+pub fn markdown(command:&Command) -> String {
+
+    let mut out = String::from("| Flag | Default | Environment | What it does |\n|------|---------|-------------|--------------|\n");
+
+    for opt in command.options.iter() {
+
+        let mut what = opt.summary();
+        if !opt.choices.is_empty() {
+            let choices: Vec<String> = opt.choices.iter().map(|(c, _)| format!("`{}`", c)).collect();
+            write!(what, " One of {}.", choices.join(", ")).unwrap();
+        }
+
+        writeln!(out, "| `{}` | {} | {} | {} |",
+            spelling(opt),
+            opt.default.map(|d| format!("`{}`", d)).unwrap_or_default(),
+            opt.env.map(|e| format!("`{}`", e)).unwrap_or_default(),
+            what.replace('|', "\\|").replace('<', "&lt;"),
+        ).unwrap();
+
+    }
+
+    out
+
+}
+
+
+/* Completions */
+
+/// The shells, that there are completions for
+pub const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+/// the completions of all the `commands` for `shell`, one of `SHELLS`, as a script to source
+pub fn completions(shell:&str, commands:&[Command]) -> Option<String> {
+    match shell {
+        "bash" => Some(bash(commands)),
+        "zsh"  => Some(zsh(commands)),
+        "fish" => Some(fish(commands)),
+        _      => None,
+    }
+}
+
+/// `text` without anything, that would end a quoted description
+fn quiet(text:&str) -> String {
+    text.chars().filter(|c| !"'\"[]:`$\\".contains(*c)).collect()
+}
+
+fn bash(commands:&[Command]) -> String {
+
+    let names: Vec<&str> = commands.iter().map(|c| c.name).collect();
+
+    let mut out = format!("# bash completions of {p}, source them, like: source <({p} completions bash)\n\n_{p}() {{\n", p = PROGRAM);
+    out.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\" prev=\"${COMP_WORDS[COMP_CWORD-1]}\" opts=\"\"\n\n");
+    writeln!(out, "    if [ \"$COMP_CWORD\" -eq 1 ]; then\n        COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )\n        return\n    fi\n", names.join(" ")).unwrap();
+
+    out.push_str("    case \"${COMP_WORDS[1]}\" in\n");
+    for c in commands.iter() {
+        let opts: Vec<String> = c.options.iter().flat_map(|o| o.short.map(|s| format!("-{}", s)).into_iter().chain(Some(o.long()))).collect();
+        writeln!(out, "        {}) opts=\"--help {}\" ;;", c.name, opts.join(" ")).unwrap();
+    }
+    out.push_str("    esac\n\n");
+
+    // The values with choices complete to them, all others to files. The commands share a lot of
+    // the options, each of them comes up once.
+    let mut cases: Vec<String> = Vec::new();
+    for o in commands.iter().flat_map(|c| c.options.iter()).filter(|o| !o.choices.is_empty()) {
+        let spellings: Vec<String> = o.short.map(|s| format!("-{}", s)).into_iter().chain(Some(o.long())).collect();
+        let choices: Vec<&str> = o.choices.iter().map(|(c, _)| *c).collect();
+        let case = format!("        {}) COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") ); return ;;\n", spellings.join("|"), choices.join(" "));
+        if !cases.contains(&case) { cases.push(case) }
+    }
+    out.push_str("    case \"$prev\" in\n");
+    cases.iter().for_each(|c| out.push_str(c));
+    out.push_str("    esac\n\n");
+
+    out.push_str("    if [[ \"$cur\" == -* ]]; then\n        COMPREPLY=( $(compgen -W \"$opts\" -- \"$cur\") )\n    else\n        COMPREPLY=( $(compgen -f -- \"$cur\") )\n    fi\n}\n\n");
+    writeln!(out, "complete -o filenames -F _{p} {p}", p = PROGRAM).unwrap();
+
+    out
+
+}
+
+fn zsh(commands:&[Command]) -> String {
+
+    let mut out = format!("#compdef {p}\n\n# zsh completions of {p}, put them into a file called _{p} somewhere on the $fpath\n\n_{p}() {{\n", p = PROGRAM);
+
+    out.push_str("    local -a commands\n    commands=(\n");
+    for c in commands.iter() {
+        writeln!(out, "        '{}:{}'", c.name, quiet(c.about)).unwrap();
+    }
+    out.push_str("    )\n\n    if (( CURRENT == 2 )); then\n        _describe 'command' commands\n        return\n    fi\n\n    case $words[2] in\n");
+
+    for c in commands.iter() {
+
+        write!(out, "        {})\n            _arguments -s '--help[Display the help text]'", c.name).unwrap();
+
+        for o in c.options.iter() {
+
+            let values: String = o.values.iter().map(|v| {
+                if o.choices.is_empty() {
+                    format!(":{}: ", quiet(v))
+                } else {
+                    format!(":{}:({})", quiet(v), o.choices.iter().map(|(c, _)| *c).collect::<Vec<_>>().join(" "))
+                }
+            }).collect();
+
+            let repeat = if o.repeats { "*" } else { "" };
+            for spelling in o.short.map(|s| format!("-{}", s)).into_iter().chain(Some(o.long())) {
+                write!(out, " \\\n                '{}{}[{}]{}'", repeat, spelling, quiet(&o.summary()), values).unwrap();
+            }
+
+        }
+
+        for p in c.positionals.iter() {
+            write!(out, " \\\n                '{}:{}:_files'", if p.many { "*" } else { "" }, quiet(p.name)).unwrap();
+        }
+
+        out.push_str("\n            ;;\n");
+
+    }
+
+    writeln!(out, "    esac\n}}\n\n_{} \"$@\"", PROGRAM).unwrap();
+
+    out
+
+}
+
+fn fish(commands:&[Command]) -> String {
+
+    let mut out = format!("# fish completions of {p}, source them, like: {p} completions fish | source\n\n", p = PROGRAM);
+
+    writeln!(out, "complete -c {} -f", PROGRAM).unwrap();
+
+    for c in commands.iter() {
+        writeln!(out, "complete -c {} -n __fish_use_subcommand -a {} -d '{}'", PROGRAM, c.name, quiet(c.about)).unwrap();
+    }
+
+    for c in commands.iter() {
+
+        let when = format!("-n '__fish_seen_subcommand_from {}'", c.name);
+
+        if !c.positionals.is_empty() { writeln!(out, "complete -c {} {} -F", PROGRAM, when).unwrap() }
+
+        for o in c.options.iter() {
+
+            let short = o.short.map(|s| format!(" -s {}", s)).unwrap_or_default();
+
+            let values = match (o.values.is_empty(), o.choices.is_empty()) {
+                (true, _)      => String::new(),
+                (false, true)  => " -r -F".to_string(),
+                (false, false) => format!(" -x -a '{}'", o.choices.iter().map(|(c, _)| *c).collect::<Vec<_>>().join(" ")),
+            };
+
+            writeln!(out, "complete -c {} {} -l {}{}{} -d '{}'", PROGRAM, when, o.name, short, values, quiet(&o.summary())).unwrap();
+
+        }
+
+    }
+
+    out
+
+}
+
+
+/* The Commands of birb */
+
+/// `birb completions`
+pub fn completions_command() -> Command {
+    Command {
+        name: "completions",
+        about: "Print the shell completions of birb",
+        help: "Prints the completions of all the commands and flags of birb for a shell. For bash, add 'source <(birb completions bash)' to the .bashrc, for fish 'birb completions fish | source' to the config.fish, and for zsh, write them to a file called _birb in a directory on the $fpath.",
+        options: Vec::new(),
+        positionals: vec![
+            Positional { name: "<shell>", required: true, many: false, help: "One of bash, zsh or fish." },
+        ],
+    }
+}
+
+/// `birb help`
+pub fn help_command() -> Command {
+    Command {
+        name: "help",
+        about: "Print the help of birb, or of one of its commands",
+        help: "Prints the help of the command, the same as 'birb <command> --help', or the list of all the commands.",
+        options: vec![
+            Opt::flag("man", "Print the man page instead, like the ones in doc/man, which are made with it."),
+            Opt::flag("markdown", "Print the flags of the command as a markdown table instead, like the one in the README, which is made with it."),
+        ],
+        positionals: vec![
+            Positional { name: "<command>", required: false, many: false, help: "The command to print the help of." },
+        ],
+    }
+}
+
+/// all the commands of `birb`, in the order of its help
+pub fn commands() -> Vec<Command> {
+    vec![
+        super::butterbrot::command(),
+        super::butterbrot::serve_command(),
+        super::butterbrot::worker_command(),
+        super::queue::command(),
+        super::birb2bmp::command(),
+        super::birb_combinator::command(),
+        super::info::command(),
+        super::birb2term::command(),
+        completions_command(),
+        help_command(),
+    ]
+}
@@ -0,0 +1,579 @@
+//! Configuration files of renders: `birb render --config render.toml`, and named presets
+//!
+//! A configuration file sets the options of a render, like the command line does, only with their
+//! long names as keys, and without the dashes:
+//!
+//! ```toml
+//! # A deep zoom of the seahorse valley
+//! width      = 4000
+//! height     = 4000
+//! center     = [-0.743643887037151, 0.131825904205330]
+//! zoom       = 2e6
+//! iterations = 5000
+//! deposit    = "trails"
+//! stats      = true
+//! rotate     = [["zr-cr", 0.3], ["zi-ci", 0.1]]
+//! ```
+//!
+//! Options with more than one value take an array, or a string of the values separated by spaces,
+//! like `center = "0 0"`, options, that repeat, an array of them, and flags `true` or `false`. A
+//! `_` in a key is a `-`, so `phase_len` is `phase-len`. The same as a JSON object works just as
+//! well, if the file ends in `.json`. Numbers are kept with all their digits, like on the command
+//! line.
+//!
+//! Presets are configuration files in `~/.config/butterbrot/presets` (or in
+//! `$XDG_CONFIG_HOME/butterbrot/presets`), like `deep.toml`, which `--preset deep` picks.
+//!
+//! Only what renders need of TOML is read: keys and values, arrays, inline tables, tables and
+//! arrays of tables, but no dates, multi-line strings or dotted keys.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use super::cli::{Command, Matches, Opt, Check};
+use super::log::fail;
+
+/// A value of a configuration file
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Text(String),
+    Number(String),                 // as it's written, without any '_'
+    Bool(bool),
+    Array(Vec<Value>),
+    Table(Vec<(String, Value)>),    // in the order of the file
+}
+
+impl Value {
+
+    /// the value under `key`, if this is a table, that has one
+    pub fn get(&self, key:&str) -> Option<&Value> {
+        match self {
+            Value::Table(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _                     => None,
+        }
+    }
+
+    /// the text of a string or a number, as it goes onto the command line
+    fn text(&self) -> Option<String> {
+        match self {
+            Value::Text(s) | Value::Number(s) => Some(s.clone()),
+            Value::Bool(b)                    => Some(b.to_string()),
+            _                                 => None,
+        }
+    }
+
+}
+
+
+/* Reading */
+
+/// Where in the text the reading is, for TOML and JSON alike
+struct Reader {
+    chars: Vec<char>,
+    at: usize,
+    line: usize,
+}
+
+type Parsed<T> = Result<T, String>;
+
+impl Reader {
+
+    fn new(text:&str) -> Reader {
+        Reader { chars: text.chars().collect(), at: 0, line: 1 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).cloned()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c == Some('\n') { self.line += 1 }
+        if c.is_some() { self.at += 1 }
+        c
+    }
+
+    fn error<T>(&self, what:&str) -> Parsed<T> {
+        Err(format!("{} in line {}", what, self.line))
+    }
+
+    fn expect(&mut self, c:char) -> Parsed<()> {
+        match self.next() {
+            Some(x) if x == c => Ok(()),
+            Some(x)           => self.error(&format!("Expected '{}', but found '{}'", c, x)),
+            None              => self.error(&format!("Expected '{}', but the file ended", c)),
+        }
+    }
+
+    /// skips spaces and tabs, and with `lines` also line breaks and comments
+    fn skip(&mut self, lines:bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r'          => { self.next(); },
+                '\n' if lines              => { self.next(); },
+                '#' if lines               => while self.peek().map(|c| c != '\n').unwrap_or(false) { self.next(); },
+                _                          => break,
+            }
+        }
+    }
+
+    /// the rest of a line, that should hold nothing but maybe a comment
+    fn end_of_line(&mut self) -> Parsed<()> {
+        self.skip(false);
+        if self.peek() == Some('#') { while self.peek().map(|c| c != '\n').unwrap_or(false) { self.next(); } }
+        match self.next() {
+            None | Some('\n') => Ok(()),
+            Some(c)           => self.error(&format!("Unexpected '{}'", c)),
+        }
+    }
+
+    /// a string in double quotes, with the escapes TOML and JSON share
+    fn string(&mut self) -> Parsed<String> {
+
+        self.expect('"')?;
+        let mut s = String::new();
+
+        loop {
+            match self.next() {
+                Some('"')  => return Ok(s),
+                Some('\\') => match self.next() {
+                    Some('n')  => s.push('\n'),
+                    Some('t')  => s.push('\t'),
+                    Some('r')  => s.push('\r'),
+                    Some('"')  => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/')  => s.push('/'),
+                    Some('u')  => {
+                        let hex: String = (0..4).filter_map(|_| self.next()).collect();
+                        match u32::from_str_radix(&hex, 16).ok().and_then(std::char::from_u32) {
+                            Some(c) => s.push(c),
+                            None    => return self.error(&format!("Broken escape '\\u{}'", hex)),
+                        }
+                    },
+                    _ => return self.error("Unknown escape in a string"),
+                },
+                Some('\n') | None => return self.error("Unterminated string"),
+                Some(c)           => s.push(c),
+            }
+        }
+
+    }
+
+    /// a number, or `true`, `false` and `null`, as the word it's written as
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() || "+-._".contains(c) { word.push(c); self.next(); } else { break }
+        }
+        word
+    }
+
+    /// a bare word as a value: a number or a boolean
+    fn scalar(&mut self) -> Parsed<Value> {
+        let word = self.word();
+        match word.as_ref() {
+            "true"  => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            ""      => self.error(&format!("Expected a value, but found '{}'", self.peek().map(|c| c.to_string()).unwrap_or_default())),
+            _       => {
+                let number = word.replace('_', "");
+                if number.parse::<f64>().is_ok() { Ok(Value::Number(number)) } else { self.error(&format!("'{}' is no value", word)) }
+            },
+        }
+    }
+
+}
+
+/// reads a TOML document
+pub fn toml(text:&str) -> Parsed<Value> {
+
+    let mut r = Reader::new(text);
+    let mut root: Vec<(String, Value)> = Vec::new();
+
+    // The table, that the keys go into: none for the root, the name of a table, or the name of an
+    // array of tables, whose last one it is
+    let mut current: Option<(String, bool)> = None;
+
+    fn toml_key(r:&mut Reader) -> Parsed<String> {
+        if r.peek() == Some('"') { return r.string() }
+        let mut key = String::new();
+        while let Some(c) = r.peek() {
+            if c.is_alphanumeric() || c == '_' || c == '-' { key.push(c); r.next(); } else { break }
+        }
+        if key.is_empty() { r.error("Expected a key") } else { Ok(key) }
+    }
+
+    fn toml_value(r:&mut Reader) -> Parsed<Value> {
+        match r.peek() {
+            Some('"')  => r.string().map(Value::Text),
+            Some('\'') => {
+                r.next();
+                let mut s = String::new();
+                loop {
+                    match r.next() {
+                        Some('\'')        => return Ok(Value::Text(s)),
+                        Some('\n') | None => return r.error("Unterminated string"),
+                        Some(c)           => s.push(c),
+                    }
+                }
+            },
+            Some('[') => {
+                r.next();
+                let mut items = Vec::new();
+                loop {
+                    r.skip(true);
+                    if r.peek() == Some(']') { r.next(); return Ok(Value::Array(items)) }
+                    items.push(toml_value(r)?);
+                    r.skip(true);
+                    match r.next() {
+                        Some(',') => (),
+                        Some(']') => return Ok(Value::Array(items)),
+                        _         => return r.error("Expected ',' or ']' in an array"),
+                    }
+                }
+            },
+            Some('{') => {
+                r.next();
+                let mut entries = Vec::new();
+                loop {
+                    r.skip(false);
+                    if r.peek() == Some('}') { r.next(); return Ok(Value::Table(entries)) }
+                    let key = toml_key(r)?;
+                    r.skip(false);
+                    r.expect('=')?;
+                    r.skip(false);
+                    entries.push((key, toml_value(r)?));
+                    r.skip(false);
+                    match r.next() {
+                        Some(',') => (),
+                        Some('}') => return Ok(Value::Table(entries)),
+                        _         => return r.error("Expected ',' or '}' in an inline table"),
+                    }
+                }
+            },
+            _ => r.scalar(),
+        }
+    }
+
+    loop {
+
+        r.skip(true);
+
+        match r.peek() {
+
+            None => break,
+
+            // [table] and [[array of tables]]
+            Some('[') => {
+                r.next();
+                let array = r.peek() == Some('[');
+                if array { r.next(); }
+                r.skip(false);
+                let name = toml_key(&mut r)?;
+                r.skip(false);
+                r.expect(']')?;
+                if array { r.expect(']')?; }
+                r.end_of_line()?;
+
+                match (root.iter_mut().find(|(k, _)| *k == name), array) {
+                    (Some((_, Value::Array(tables))), true) => tables.push(Value::Table(Vec::new())),
+                    (None, true)                            => root.push((name.clone(), Value::Array(vec![Value::Table(Vec::new())]))),
+                    (None, false)                           => root.push((name.clone(), Value::Table(Vec::new()))),
+                    (Some(_), _)                            => return r.error(&format!("'{}' is defined twice", name)),
+                }
+
+                current = Some((name, array));
+            },
+
+            // key = value
+            Some(_) => {
+                let line = r.line;
+                let key = toml_key(&mut r)?;
+                r.skip(false);
+                r.expect('=')?;
+                r.skip(false);
+                let value = toml_value(&mut r)?;
+                r.end_of_line()?;
+
+                let table = match &current {
+                    None              => &mut root,
+                    Some((name, array)) => match root.iter_mut().find(|(k, _)| k == name).map(|(_, v)| v) {
+                        Some(Value::Table(t))                   => t,
+                        Some(Value::Array(a)) if *array         => match a.last_mut() { Some(Value::Table(t)) => t, _ => unreachable!() },
+                        _                                       => unreachable!(),
+                    },
+                };
+
+                if table.iter().any(|(k, _)| *k == key) {
+                    return Err(format!("'{}' is set twice, the second time in line {}", key, line))
+                }
+                table.push((key, value));
+            },
+
+        }
+
+    }
+
+    Ok(Value::Table(root))
+
+}
+
+/// reads a JSON document, whose `null`s are left out
+pub fn json(text:&str) -> Parsed<Value> {
+
+    fn json_value(r:&mut Reader) -> Parsed<Option<Value>> {
+        r.skip(true);
+        match r.peek() {
+            Some('"') => r.string().map(|s| Some(Value::Text(s))),
+            Some('[') => {
+                r.next();
+                let mut items = Vec::new();
+                r.skip(true);
+                if r.peek() == Some(']') { r.next(); return Ok(Some(Value::Array(items))) }
+                loop {
+                    items.extend(json_value(r)?);
+                    r.skip(true);
+                    match r.next() {
+                        Some(',') => (),
+                        Some(']') => return Ok(Some(Value::Array(items))),
+                        _         => return r.error("Expected ',' or ']' in an array"),
+                    }
+                }
+            },
+            Some('{') => {
+                r.next();
+                let mut entries = Vec::new();
+                r.skip(true);
+                if r.peek() == Some('}') { r.next(); return Ok(Some(Value::Table(entries))) }
+                loop {
+                    r.skip(true);
+                    let key = r.string()?;
+                    r.skip(true);
+                    r.expect(':')?;
+                    if let Some(value) = json_value(r)? {
+                        if entries.iter().any(|(k, _)| *k == key) { return r.error(&format!("'{}' is set twice", key)) }
+                        entries.push((key, value));
+                    }
+                    r.skip(true);
+                    match r.next() {
+                        Some(',') => (),
+                        Some('}') => return Ok(Some(Value::Table(entries))),
+                        _         => return r.error("Expected ',' or '}' in an object"),
+                    }
+                }
+            },
+            _ => {
+                if r.chars[r.at..].starts_with(&['n', 'u', 'l', 'l']) {
+                    r.word();
+                    Ok(None)
+                } else {
+                    r.scalar().map(Some)
+                }
+            },
+        }
+    }
+
+    let mut r = Reader::new(text);
+    let value = json_value(&mut r)?;
+    r.skip(true);
+
+    match (value, r.peek()) {
+        (Some(Value::Table(t)), None) => Ok(Value::Table(t)),
+        (_, None)                     => Err("A configuration has to be a JSON object".to_string()),
+        (_, Some(c))                  => r.error(&format!("Unexpected '{}' after the object", c)),
+    }
+
+}
+
+/// reads the configuration file `path`, TOML, or JSON, if it ends in `.json`
+pub fn read(path:&str) -> Value {
+
+    let text = std::fs::read_to_string(path).unwrap_or_else(|e| fail(format!("Couldn't read the configuration '{}': {}.", path, e)));
+
+    let parsed = if path.ends_with(".json") { json(&text) } else { toml(&text) };
+
+    parsed.unwrap_or_else(|e| fail(format!("Couldn't read the configuration '{}': {}.", path, e)))
+
+}
+
+
+/* Presets */
+
+/// the directory of the presets, `~/.config/butterbrot/presets`
+pub fn presets_dir() -> PathBuf {
+    let config = std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()).map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from(".config"));
+    config.join("butterbrot").join("presets")
+}
+
+/// the names of all the presets there are
+pub fn presets() -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(presets_dir()).map(|entries| {
+        entries.filter_map(|e| e.ok()).map(|e| e.path())
+            .filter(|p| p.extension().map(|x| x == "toml" || x == "json").unwrap_or(false))
+            .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().into_owned()))
+            .collect()
+    }).unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// the file of the preset `name`
+pub fn preset(name:&str) -> String {
+
+    let dir = presets_dir();
+
+    for extension in ["toml", "json"].iter() {
+        let path = dir.join(format!("{}.{}", name, extension));
+        if path.is_file() { return path.to_string_lossy().into_owned() }
+    }
+
+    let there = presets();
+    if there.is_empty() {
+        fail(format!("There's no preset '{}', there are no presets in '{}' at all.", name, dir.display()))
+    } else {
+        fail(format!("There's no preset '{}' in '{}', only {}.", name, dir.display(), there.join(", ")))
+    }
+
+}
+
+
+/* Configurations as Options */
+
+/// The options, that are about a run of the command, not about what it computes, and that are only
+/// taken from the command line
+pub const COMMAND_LINE_ONLY: [&str; 5] = ["config", "preset", "print-config", "overwrite", "no-clobber"];
+
+/// the `table` of a configuration as the options of `command`, with their values as they'd be on
+/// the command line
+///
+/// `what` names the configuration in errors, like `'render.toml'`. Flags set to `false` are
+/// `["false"]`, so they can unset the flag of an earlier configuration, see `merge()`.
+pub fn options(table:&Value, command:&Command, what:&str) -> Vec<(String, Vec<String>)> {
+
+    let entries = match table {
+        Value::Table(entries) => entries,
+        _                     => fail(format!("{} isn't a table of options.", what)),
+    };
+
+    let mut options = Vec::new();
+
+    for (key, value) in entries.iter() {
+
+        let name = key.replace('_', "-");
+
+        let opt = command.options.iter().find(|o| o.name == name).unwrap_or_else(|| fail(format!("There's no option '{}', that {} could set, see 'birb {} --help' for the ones there are.", key, what, command.name)));
+
+        if COMMAND_LINE_ONLY.contains(&opt.name) {
+            fail(format!("'{}' can't be set in {}, only on the command line.", key, what))
+        }
+
+        let wrong = || -> ! { fail(format!("'{}' in {} should be {}.", key, what, expected(opt))) };
+
+        // One occurrence of the option from a value
+        let values = |value:&Value| -> Vec<String> {
+            match (value, opt.values.len()) {
+                (Value::Array(items), n) if n > 1 => items.iter().map(|v| v.text().unwrap_or_else(|| wrong())).collect(),
+                (Value::Text(s), n) if n > 1      => s.split_whitespace().map(|s| s.to_string()).collect(),
+                (Value::Bool(_), _)               => wrong(),
+                (v, 1)                            => vec![v.text().unwrap_or_else(|| wrong())],
+                _                                 => wrong(),
+            }
+        };
+
+        match value {
+            Value::Bool(b) if opt.values.is_empty() => options.push((name, if *b { Vec::new() } else { vec!["false".to_string()] })),
+            _ if opt.values.is_empty()              => wrong(),
+            Value::Array(items) if opt.repeats && items.iter().all(|i| matches!(i, Value::Array(_))) => {
+                options.extend(items.iter().map(|i| (name.clone(), values(i))))
+            },
+            _ => options.push((name, values(value))),
+        }
+
+    }
+
+    options
+
+}
+
+/// what the value of `opt` should look like in a configuration
+fn expected(opt:&Opt) -> String {
+    match opt.values.len() {
+        0                 => "true or false".to_string(),
+        1 if opt.repeats  => format!("{}, or an array of them", opt.values[0]),
+        1                 => opt.values[0].to_string(),
+        _ if opt.repeats  => format!("an array of {}, or an array of such arrays", opt.values.join(" ")),
+        _                 => format!("an array of {}, or a string of them", opt.values.join(" ")),
+    }
+}
+
+/// the options of several configurations, the later ones taking the place of the earlier ones,
+/// without the unset flags
+pub fn merge(layers:Vec<Vec<(String, Vec<String>)>>) -> Vec<(String, Vec<String>)> {
+
+    let mut merged: Vec<(String, Vec<String>)> = Vec::new();
+
+    for layer in layers {
+        merged.retain(|(name, _)| !layer.iter().any(|(n, _)| n == name));
+        merged.extend(layer);
+    }
+
+    merged.retain(|(_, values)| values.first().map(|v| v != "false").unwrap_or(true));
+    merged
+
+}
+
+
+/* Printing */
+
+/// `s` as a TOML string
+fn quote(s:&str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+/// the values of one occurrence of `opt`, as a TOML value
+fn toml_values(opt:&Opt, values:&[String]) -> String {
+    let number = opt.check != Check::Text;
+    let one = |v:&String| if number { v.clone() } else { quote(v) };
+    match values.len() {
+        1 => one(&values[0]),
+        _ => format!("[{}]", values.iter().map(one).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+/// the options of `command`, that `m` has values for, as a TOML configuration, that sets them all
+///
+/// Options without a value are commented out, so it's easy to see, what else there is. So are the
+/// ones `except`, that have a value, but don't mean anything together with the others, like the
+/// default `z0` of a render with `--julia`, which would read back as both of them set.
+pub fn to_toml(m:&Matches, command:&Command, except:&[&str]) -> String {
+
+    let mut out = format!("# The configuration of 'birb {}', for '--config <file>'\n", command.name);
+
+    let options = command.options.iter().filter(|o| !COMMAND_LINE_ONLY.contains(&o.name));
+    let column  = command.options.iter().map(|o| o.name.len()).max().unwrap_or(0);
+
+    for opt in options {
+
+        let value = if except.contains(&opt.name) {
+            None
+        } else if opt.values.is_empty() {
+            Some(m.flag(opt.name).to_string())
+        } else if opt.repeats {
+            let all = m.all(opt.name);
+            if all.is_empty() { None } else { Some(format!("[{}]", all.iter().map(|v| format!("[{}]", v.iter().map(|v| quote(v)).collect::<Vec<_>>().join(", "))).collect::<Vec<_>>().join(", "))) }
+        } else {
+            m.values(opt.name).map(|v| toml_values(opt, v))
+        };
+
+        match value {
+            Some(value) => writeln!(out, "{:w$} = {}", opt.name, value, w = column).unwrap(),
+            None        => writeln!(out, "# {} =", opt.name).unwrap(),
+        }
+
+    }
+
+    out
+
+}
@@ -0,0 +1,311 @@
+//! The counters, that a computation accumulates its hits in
+//!
+//!  - `CounterWidth` is the type of the single counters: `u16`, `u32` or `u64`
+//!  - `Counters` is a birb's worth of counters of one `CounterWidth`, that promotes itself to the
+//!    next wider one, whenever a counter would overflow
+//!  - `Accumulator` is what the write-back functions deposit hits into. Both `Counters` and plain
+//!    `Vec<u64>` birbs are `Accumulator`s.
+//!
+//! A 20000x20000 birb of `u64`s takes 3.2 GB, even though most of its counters never get past a
+//! few thousand hits. With `u32` counters it's half that, with `u16`s a quarter. And should a
+//! counter fill up after all, the whole buffer is promoted to the next wider type on the spot, so
+//! no hit is ever lost.
+
+/* Counter Widths */
+
+/// the type of the counters of a birb
+///
+/// Birbs declare it in their `Header`, as `counter = u32` and so on. Birbs without it are made of
+/// `u64`s.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum CounterWidth {
+    U16,
+    U32,
+    U64,
+}
+
+impl CounterWidth {
+
+    /// all of them, from the narrowest to the widest
+    pub const ALL: [CounterWidth; 3] = [CounterWidth::U16, CounterWidth::U32, CounterWidth::U64];
+
+    /// the name of the type, as used for `--counters` and in the `Header`
+    pub fn name(&self) -> &'static str {
+        match self {
+            CounterWidth::U16 => "u16",
+            CounterWidth::U32 => "u32",
+            CounterWidth::U64 => "u64",
+        }
+    }
+
+    pub fn from_name(name:&str) -> Option<CounterWidth> {
+        CounterWidth::ALL.iter().find(|w| w.name() == name).cloned()
+    }
+
+    /// how many bytes a counter takes up
+    pub fn bytes(&self) -> usize {
+        match self {
+            CounterWidth::U16 => 2,
+            CounterWidth::U32 => 4,
+            CounterWidth::U64 => 8,
+        }
+    }
+
+    /// the largest number a counter can hold
+    pub fn max(&self) -> u64 {
+        match self {
+            CounterWidth::U16 => u64::from(u16::MAX),
+            CounterWidth::U32 => u64::from(u32::MAX),
+            CounterWidth::U64 => u64::MAX,
+        }
+    }
+
+    /// the narrowest width, that holds `n`
+    pub fn for_max(n:u64) -> CounterWidth {
+        *CounterWidth::ALL.iter().find(|w| w.max() >= n).unwrap_or(&CounterWidth::U64)
+    }
+
+    /// the wider of the two
+    pub fn wider(self, other:CounterWidth) -> CounterWidth {
+        if other > self { other } else { self }
+    }
+
+}
+
+
+/* Accumulators */
+
+/// something, that hits can be deposited into
+///
+/// The `index`es are the ones of a birb `Vec<u64>`: width and height come first, so the first
+/// counter of the first channel is at 2, and every channel is `width * height` counters long.
+pub trait Accumulator {
+
+    fn width(&self) -> u64;
+
+    fn height(&self) -> u64;
+
+    /// adds `n` hits to the counter at `index`
+    fn add(&mut self, index:usize, n:u64);
+
+    /// adds the two's complement `n` to the counter at `index`, for the `SIGNED_CHANNELS`, which
+    /// wrap around instead of overflowing
+    fn wrapping_add(&mut self, index:usize, n:u64);
+
+}
+
+impl Accumulator for Vec<u64> {
+
+    fn width(&self) -> u64 { self[0] }
+
+    fn height(&self) -> u64 { self[1] }
+
+    #[inline]
+    fn add(&mut self, index:usize, n:u64) {
+        self[index] = self[index].saturating_add(n);
+    }
+
+    #[inline]
+    fn wrapping_add(&mut self, index:usize, n:u64) {
+        self[index] = self[index].wrapping_add(n);
+    }
+
+}
+
+/// adds the `band` of rows, starting at the row `first_row`, to `birb`
+///
+/// The `band` is a birb of its own, as wide as `birb`, with all the channels, like the bands of
+/// `io::BirbReader::bands()`, or a whole birb with `first_row` 0. `signed` says for every channel,
+/// whether it's one of the `SIGNED_CHANNELS`, which wrap around. Only the counters, that aren't 0,
+/// are added.
+pub fn add_band<A: Accumulator>(birb:&mut A, first_row:u64, band:&[u64], signed:&[bool]) {
+
+    let n    = (birb.width() * birb.height()) as usize;
+    let rows = (band[0] * band[1]).max(1) as usize;
+    let skip = (first_row * birb.width()) as usize;
+
+    for (i, &hits) in band.iter().enumerate().skip(2).filter(|&(_, &h)| h != 0) {
+
+        let (channel, p) = ((i - 2) / rows, (i - 2) % rows);
+        let index = 2 + channel * n + skip + p;
+
+        if signed[channel] { birb.wrapping_add(index, hits) } else { birb.add(index, hits) }
+
+    }
+
+}
+
+
+/* Promoting Counters */
+
+/// the counters of a birb, all of the same `CounterWidth`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cells {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    U64(Vec<u64>),
+}
+
+/// a birb's worth of counters, that start out narrow and get wider as needed
+///
+/// Adding to a counter checks, whether it would overflow. If it would, the whole buffer is
+/// promoted to the next wider `CounterWidth` first. That takes a moment and, while it lasts, the
+/// memory of both buffers, but it happens at most twice in a computation. `u64` counters saturate
+/// at `u64::MAX`.
+///
+/// The `SIGNED_CHANNELS` wrap around by design, which only works in `u64`s, so the first
+/// `wrapping_add()` promotes the buffer straight to those.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Counters {
+    width: u64,
+    height: u64,
+    cells: Cells,
+}
+
+impl Counters {
+
+    /// creates `channels` channels of `width * height` counters of `counter_width`, all 0
+    pub fn new(width:u64, height:u64, channels:u64, counter_width:CounterWidth) -> Counters {
+
+        let n = (width * height * channels) as usize;
+
+        let cells = match counter_width {
+            CounterWidth::U16 => Cells::U16(vec![0; n]),
+            CounterWidth::U32 => Cells::U32(vec![0; n]),
+            CounterWidth::U64 => Cells::U64(vec![0; n]),
+        };
+
+        Counters { width, height, cells }
+
+    }
+
+    /// the counters of the birb `birb`, starting out at `counter_width`, but as wide as they need
+    /// to be to hold them
+    pub fn from_birb(birb:&[u64], counter_width:CounterWidth) -> Counters {
+
+        let counter_width = counter_width.wider(CounterWidth::for_max(birb[2..].iter().cloned().max().unwrap_or(0)));
+
+        let cells = match counter_width {
+            CounterWidth::U16 => Cells::U16(birb[2..].iter().map(|&n| n as u16).collect()),
+            CounterWidth::U32 => Cells::U32(birb[2..].iter().map(|&n| n as u32).collect()),
+            CounterWidth::U64 => Cells::U64(birb[2..].to_vec()),
+        };
+
+        Counters { width: birb[0], height: birb[1], cells }
+
+    }
+
+    /// the `CounterWidth` the counters have right now
+    pub fn counter_width(&self) -> CounterWidth {
+        match self.cells {
+            Cells::U16(_) => CounterWidth::U16,
+            Cells::U32(_) => CounterWidth::U32,
+            Cells::U64(_) => CounterWidth::U64,
+        }
+    }
+
+    /// the counters themselves, without width and height
+    pub fn cells(&self) -> &Cells {
+        &self.cells
+    }
+
+    /// how many counters there are, across all channels
+    pub fn len(&self) -> usize {
+        match &self.cells {
+            Cells::U16(c) => c.len(),
+            Cells::U32(c) => c.len(),
+            Cells::U64(c) => c.len(),
+        }
+    }
+
+    /// whether there are no counters at all, as in a birb without pixels
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// the counter at `index`, see `Accumulator` for the indices
+    pub fn get(&self, index:usize) -> u64 {
+        match &self.cells {
+            Cells::U16(c) => u64::from(c[index - 2]),
+            Cells::U32(c) => u64::from(c[index - 2]),
+            Cells::U64(c) => c[index - 2],
+        }
+    }
+
+    /// the whole thing as a birb of `u64`s, width and height included
+    pub fn to_birb(&self) -> Vec<u64> {
+
+        let mut birb = Vec::with_capacity(self.len() + 2);
+        birb.push(self.width);
+        birb.push(self.height);
+
+        match &self.cells {
+            Cells::U16(c) => birb.extend(c.iter().map(|&n| u64::from(n))),
+            Cells::U32(c) => birb.extend(c.iter().map(|&n| u64::from(n))),
+            Cells::U64(c) => birb.extend_from_slice(c),
+        }
+
+        birb
+
+    }
+
+    /// makes the counters at least `counter_width` wide
+    pub fn promote(&mut self, counter_width:CounterWidth) {
+
+        if counter_width <= self.counter_width() { return }
+
+        note!("\x1B[33mWidening all the counters to {}\x1B[0m", counter_width.name());
+
+        let cells = std::mem::replace(&mut self.cells, Cells::U64(Vec::new()));
+
+        self.cells = match (cells, counter_width) {
+            (Cells::U16(c), CounterWidth::U32) => Cells::U32(c.into_iter().map(u32::from).collect()),
+            (Cells::U16(c), _)                 => Cells::U64(c.into_iter().map(u64::from).collect()),
+            (Cells::U32(c), _)                 => Cells::U64(c.into_iter().map(u64::from).collect()),
+            (cells, _)                         => cells,
+        };
+
+    }
+
+}
+
+impl Accumulator for Counters {
+
+    fn width(&self) -> u64 { self.width }
+
+    fn height(&self) -> u64 { self.height }
+
+    #[inline]
+    fn add(&mut self, index:usize, n:u64) {
+
+        let i = index - 2;
+
+        // Nothing fits into a counter, that's bigger than it can hold, so promote before adding
+        let sum = match &mut self.cells {
+            Cells::U16(c) => u64::from(c[i]).saturating_add(n),
+            Cells::U32(c) => u64::from(c[i]).saturating_add(n),
+            Cells::U64(c) => { c[i] = c[i].saturating_add(n); return },
+        };
+
+        if sum > self.counter_width().max() { self.promote(CounterWidth::for_max(sum)) }
+
+        match &mut self.cells {
+            Cells::U16(c) => c[i] = sum as u16,
+            Cells::U32(c) => c[i] = sum as u32,
+            Cells::U64(c) => c[i] = sum,
+        }
+
+    }
+
+    #[inline]
+    fn wrapping_add(&mut self, index:usize, n:u64) {
+
+        self.promote(CounterWidth::U64);
+
+        if let Cells::U64(c) = &mut self.cells {
+            c[index - 2] = c[index - 2].wrapping_add(n);
+        }
+
+    }
+
+}
@@ -0,0 +1,379 @@
+//! Distributed rendering: `birb serve` hands out the work, `birb worker`s compute it
+//!
+//! The coordinator cuts the samples of a render into work units of `--unit` samples each, and
+//! hands them out to whichever workers connect. Every unit comes with its own number, which is
+//! mixed with the `--seed` of the render, that the coordinator passes on to the workers, to make
+//! the number of the random stream its samples are drawn from, see `math::stream()`. The
+//! workers send back the birb of every unit, the coordinator sums them up into the final birb.
+//! Should a worker disappear with a unit, the unit goes back into the queue, and the next worker,
+//! that's free, gets it.
+//!
+//! ### The protocol
+//!
+//! Everything goes over one TCP connection per worker, in lines of text, and the birbs in binary:
+//!
+//!  - `config <n>`, coordinator to worker, right after connecting, followed by `n` lines, each an
+//!    argument of `butterbrot`, which describe the render
+//!  - `unit <id> <samples>`, coordinator to worker: compute `samples` samples from the stream `id`
+//!  - `done <id> <n>`, worker to coordinator, followed by the birb of the unit: `n` little endian
+//!    `u64`s, width and height included. Birbs of any other size, than the render's, are
+//!    refused, the coordinator hangs up.
+//!  - `working <id>`, worker to coordinator, every `HEARTBEAT` while computing the unit `id`
+//!  - `bye`, coordinator to worker: there's nothing left to do
+//!
+//! A worker, that doesn't send anything for `TIMEOUT`, is given up on, like one, that
+//! disappeared, even if the connection still stands.
+//!
+//! It's meant for the machines of a local network, there's no authentication or encryption.
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write, Result, Error, ErrorKind};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Sender, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use super::counters::{Accumulator, add_band};
+use super::cli::{self, Opt};
+use super::log::fail;
+
+/// Where `birb serve` listens, unless told otherwise
+pub const LISTEN: &str = "0.0.0.0:7878";
+
+/// How often a worker says, it's still at its unit
+pub const HEARTBEAT: Duration = Duration::from_secs(10);
+
+/// How long the coordinator waits for a word from a worker, before giving its unit to another one
+pub const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What a binary is asked to be
+#[derive(Clone, Debug, PartialEq)]
+pub enum Role {
+    Alone,
+    Serve { listen: String, unit: Option<u64> },
+    Worker { connect: String },
+}
+
+/// the flags of `birb serve`, besides the ones of the render
+pub fn serve_options() -> Vec<Opt> {
+    vec![
+        Opt::value("listen", &["<address>"], "The address to wait for workers on, like 0.0.0.0:7878 for every network the machine is on.")
+            .default(LISTEN).env("BIRB_LISTEN"),
+        Opt::value("unit", &["<samples>"], "How many samples a work unit has. Without it, it's the larger of a hundredth of the --samples and --phase-len.")
+            .integer(1, u64::MAX),
+    ]
+}
+
+/// the flags of `birb worker`, besides the ones of the render
+pub fn worker_options() -> Vec<Opt> {
+    vec![
+        Opt::value("connect", &["<address>"], "The address of the coordinator, like 192.168.0.2:7878.")
+            .env("BIRB_CONNECT"),
+    ]
+}
+
+/// figures out the `Role` of a render run as the command `name`, from its command line `args`,
+/// its name first, and returns the arguments left for `parse_args()`
+///
+/// `birb serve [--listen <address>] [--unit <samples>] [ARGUMENTS]` makes a coordinator,
+/// `birb worker --connect <address> [ARGUMENTS]` a worker, see `serve_options()` and
+/// `worker_options()`. Anything else is a plain old render.
+pub fn role(name:&str, args:Vec<String>) -> (Role, Vec<String>) {
+
+    let (serving, options) = match name {
+        "serve"  => (true, serve_options()),
+        "worker" => (false, worker_options()),
+        _        => return (Role::Alone, args),
+    };
+
+    let mut args = args.into_iter();
+    let mut rest: Vec<String> = args.by_ref().take(1).collect();
+
+    let (m, left) = cli::extract(&options, args.collect());
+    rest.extend(left);
+
+    let role = match (serving, m.value("connect")) {
+        (true, _)              => Role::Serve { listen: m.value("listen").unwrap_or(LISTEN).to_string(), unit: m.get("unit") },
+        (false, Some(connect)) => Role::Worker { connect: connect.to_string() },
+        (false, None)          => fail("A worker needs to know, where to '--connect' to."),
+    };
+
+    (role, rest)
+
+}
+
+/// a piece of work: `samples` samples from the random stream `id`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Unit {
+    pub id: u64,
+    pub samples: u64,
+}
+
+/// the units of `sample_count` samples, `unit` samples each, the last one takes the rest
+pub fn units(sample_count:u64, unit:u64) -> VecDeque<Unit> {
+    (0..sample_count.div_ceil(unit))
+        .map(|id| Unit { id, samples: unit.min(sample_count - id * unit) })
+        .collect()
+}
+
+
+/* The Coordinator */
+
+/// the units, that are left to hand out, and how many are done
+#[derive(Debug)]
+struct Queue {
+    left: VecDeque<Unit>,
+    done: u64,
+    total: u64,
+}
+
+/// Hands out the `sample_count` samples in units of `unit` to the workers, that connect to
+/// `listener`, and sums the birbs, they send back, into `birb`, until all of them are done
+///
+/// `config` are the arguments of `butterbrot`, that the workers compute with. `signed` says for
+/// every channel of the birb, whether it's one of the `SIGNED_CHANNELS`, which wrap around.
+/// Every worker gets a thread of its own, which re-queues its unit, should the worker disappear,
+/// or not be heard of for `timeout`, see `TIMEOUT`.
+pub fn serve<A: Accumulator + Send + 'static>(listener:TcpListener, config:Vec<String>, birb:Arc<Mutex<A>>, signed:Vec<bool>, sample_count:u64, unit:u64, timeout:Duration) {
+
+    let left  = units(sample_count, unit);
+    let total = left.len() as u64;
+    let queue = Arc::new(Mutex::new(Queue { left, done: 0, total }));
+
+    let config = Arc::new(config);
+    let signed = Arc::new(signed);
+
+    note!("Waiting for workers on \x1B[34m{}\x1B[0m, with {} units of up to {} samples", listener.local_addr().map(|a| a.to_string()).unwrap_or_default(), total, unit);
+
+    error!(listener.set_nonblocking(true), "Couldn't listen for workers.", full);
+
+    loop {
+
+        let finished = { let q = error!(queue.lock(), "Couldn't acquire Mutex lock"); q.done == q.total };
+        if finished { break }
+
+        match listener.accept() {
+
+            Ok((stream, address)) => {
+
+                let (queue, config, signed, birb) = (Arc::clone(&queue), Arc::clone(&config), Arc::clone(&signed), Arc::clone(&birb));
+
+                std::thread::spawn(move || {
+                    if let Err(e) = coordinate(stream, &queue, &config, &signed, &birb, timeout) {
+                        warning!("Lost the worker {}: {}", address, e);
+                    }
+                });
+
+            },
+
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(100)),
+
+            Err(e) => warning!("A worker couldn't connect: {}", e),
+
+        }
+
+    }
+
+    note!("\x1B[32mAll units done.\x1B[0m");
+
+}
+
+/// Talks to one worker, until there's nothing left to do, or it disappears, or goes silent for
+/// `timeout`
+fn coordinate<A: Accumulator>(stream:TcpStream, queue:&Mutex<Queue>, config:&[String], signed:&[bool], birb:&Mutex<A>, timeout:Duration) -> Result<()> {
+
+    let address = stream.peer_addr()?;
+
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(timeout))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+
+    // The size of every birb, the worker sends back
+    let size = { let birb = error!(birb.lock(), "Couldn't acquire Mutex lock"); birb.width() * birb.height() * signed.len() as u64 + 2 };
+
+    note!("Worker \x1B[34m{}\x1B[0m connected", address);
+
+    writeln!(stream, "config {}", config.len())?;
+    config.iter().try_for_each(|arg| writeln!(stream, "{}", arg))?;
+
+    loop {
+
+        let next = {
+            let mut q = error!(queue.lock(), "Couldn't acquire Mutex lock");
+            if q.done == q.total { None } else { Some(q.left.pop_front()) }
+        };
+
+        let unit = match next {
+            None          => { stream.write_all(b"bye\n")?; return Ok(()) },
+            Some(None)    => { std::thread::sleep(Duration::from_millis(100)); continue }, // others are still at it
+            Some(Some(u)) => u,
+        };
+
+        // Whatever goes wrong, the unit goes back into the queue for someone else
+        let partial = writeln!(stream, "unit {} {}", unit.id, unit.samples).and_then(|_| receive(&mut reader, unit.id, size));
+
+        let partial = match partial {
+            Ok(p)  => p,
+            Err(e) => { error!(queue.lock(), "Couldn't acquire Mutex lock").left.push_back(unit); return Err(e) },
+        };
+
+        let mut birb = error!(birb.lock(), "Couldn't acquire Mutex lock");
+
+        if partial[0] != birb.width() || partial[1] != birb.height() {
+            error!(queue.lock(), "Couldn't acquire Mutex lock").left.push_back(unit);
+            return Err(Error::new(ErrorKind::InvalidData, "the worker's birb doesn't fit"))
+        }
+
+        add_band(&mut *birb, 0, &partial, signed);
+        drop(birb);
+
+        let mut q = error!(queue.lock(), "Couldn't acquire Mutex lock");
+        q.done += 1;
+        note!("Unit {} done by \x1B[34m{}\x1B[0m, {} of {} units done", unit.id, address, q.done, q.total);
+
+    }
+
+}
+
+/// Reads the `done` message of the unit `id`, and the birb of `size` `u64`s, that comes with it
+///
+/// A birb of any other size is an error, before any memory is set aside for it. The `working`
+/// messages before it are skipped, they only keep the connection from timing out.
+fn receive(reader:&mut impl BufRead, id:u64, size:u64) -> Result<Vec<u64>> {
+
+    let n: u64 = loop {
+
+        let line = read_line(reader)?;
+
+        match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["working", i] if i.parse() == Ok(id) => continue,
+            ["done", i, n] if i.parse() == Ok(id) => break n.parse().map_err(|_| Error::new(ErrorKind::InvalidData, "a broken message"))?,
+            _                                     => return Err(Error::new(ErrorKind::InvalidData, "an unexpected message")),
+        }
+
+    };
+
+    if n != size {
+        return Err(Error::new(ErrorKind::InvalidData, "the worker's birb doesn't fit"))
+    }
+
+    let mut bytes = vec![0u8; n as usize * 8];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(bytes.chunks(8).map(super::io::word).collect())
+
+}
+
+/// Reads a line, without the line break, an ended connection is an error
+fn read_line(reader:&mut impl BufRead) -> Result<String> {
+
+    let mut line = String::new();
+
+    if reader.read_line(&mut line)? == 0 {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "the connection ended"))
+    }
+
+    Ok(line.trim_end().to_string())
+
+}
+
+
+/* The Worker */
+
+/// A worker's connection to the coordinator
+#[derive(Debug)]
+pub struct Connection {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl Connection {
+
+    /// connects to the coordinator at `address`, and receives the arguments of the render
+    pub fn open(address:&str) -> (Connection, Vec<String>) {
+
+        let stream = error!(TcpStream::connect(address), "Couldn't connect to the coordinator.", full);
+        let reader = BufReader::new(error!(stream.try_clone(), "Couldn't connect to the coordinator.", full));
+
+        let mut connection = Connection { stream, reader };
+
+        let config = error!(connection.config(), "The coordinator didn't send the arguments of the render.", full);
+
+        (connection, config)
+
+    }
+
+    fn config(&mut self) -> Result<Vec<String>> {
+
+        let line = read_line(&mut self.reader)?;
+
+        let n: usize = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["config", n] => n.parse().map_err(|_| Error::new(ErrorKind::InvalidData, "a broken message"))?,
+            _             => return Err(Error::new(ErrorKind::InvalidData, "an unexpected message")),
+        };
+
+        (0..n).map(|_| read_line(&mut self.reader)).collect()
+
+    }
+
+    /// the next unit to compute, `None` once there's nothing left to do
+    ///
+    /// A coordinator, that's gone, has nothing left to do either.
+    pub fn next_unit(&mut self) -> Option<Unit> {
+
+        let line = read_line(&mut self.reader).ok()?;
+
+        match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["unit", id, samples] => Some(Unit { id: id.parse().ok()?, samples: samples.parse().ok()? }),
+            _                     => None,
+        }
+
+    }
+
+    /// tells the coordinator every `HEARTBEAT`, that the `unit` is still being computed, until the
+    /// `Heartbeat` is dropped
+    pub fn heartbeat(&self, unit:Unit) -> Heartbeat {
+
+        let mut stream = error!(self.stream.try_clone(), "Couldn't talk to the coordinator.", full);
+        let (stop, stopped) = channel::<()>();
+
+        // Nothing is ever sent, dropping the Heartbeat hangs up the channel instead
+        let thread = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(HEARTBEAT) {
+                if writeln!(stream, "working {}", unit.id).is_err() { break }
+            }
+        });
+
+        Heartbeat { stop: Some(stop), thread: Some(thread) }
+
+    }
+
+    /// sends the `birb` of the `unit` back
+    pub fn send(&mut self, unit:Unit, birb:&[u64]) {
+
+        let bytes: Vec<u8> = birb.iter().flat_map(|n| n.to_le_bytes()).collect();
+
+        error!(writeln!(self.stream, "done {} {}", unit.id, birb.len()).and_then(|_| self.stream.write_all(&bytes)), "Couldn't send the birb to the coordinator.", full);
+
+    }
+
+}
+
+/// Keeps saying, that a unit is still being computed, see `Connection::heartbeat()`
+#[derive(Debug)]
+pub struct Heartbeat {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Heartbeat {
+
+    /// stops, before the birb of the unit is sent, so the two don't mix
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() { let _ = thread.join(); }
+    }
+
+}
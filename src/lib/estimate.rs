@@ -0,0 +1,181 @@
+//! Estimates of what a computation is going to take, before it starts
+//!
+//!  - `Estimate` adds up the memory of the birb, the `Orbit`s the threads collect during a phase,
+//!    and the reference `Orbit`s of `--perturbation`
+//!  - `available_memory()` asks the system, how much memory there is to be had
+//!  - `calibrate()` and `calibrate_escape_time()` time the computation for a few seconds, to guess
+//!    how long the whole thing will run
+//!
+//! A `-w 40000 -h 40000` birb of `u64`s takes 12.8 GB, and with `--phase_len 10000` and
+//! `--iterations 100000` every thread may hold on to 16 GB of `Orbit`s before writing them back.
+//! Neither is much fun to find out about after an hour of computing, so `butterbrot` checks the
+//! `Estimate` against `available_memory()` before it starts, and `--dry-run` prints all of it.
+
+use std::time::{Duration, Instant};
+
+use super::counters::CounterWidth;
+use super::math;
+use super::tiles;
+
+/// How long `calibrate()` computes for
+pub const CALIBRATION: Duration = Duration::from_secs(3);
+
+/// the memory a computation takes, in bytes, at the most, see `birb()`, `orbits()` and
+/// `reference()` for the parts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Estimate {
+    pub birb: u64,          // the counters, or the tiles in memory and the collected deposits
+    pub orbits: u64,        // the Orbits of a thread's phase
+    pub reference: u64,     // a reference Orbit, which every thread has a copy of
+    pub threads: u64,
+}
+
+impl Estimate {
+
+    /// all of it together
+    pub fn total(&self) -> u64 {
+        self.birb
+            .saturating_add(self.threads.saturating_mul(self.orbits.saturating_add(self.reference)))
+            .saturating_add(self.reference)
+    }
+
+}
+
+/// the memory of the birb's counters
+///
+/// `memory` is the `--memory` limit in bytes, if any. Statistics channels widen the counters to
+/// `u64`s right away, so they're counted as such. Should the counters widen during the computation,
+/// there's a moment, where the old and the new ones are both in memory, that's not included.
+pub fn birb(width:u64, height:u64, channels:u64, counter_width:CounterWidth, stats:bool, memory:Option<u64>) -> u64 {
+
+    let counter_bytes = if stats { 8 } else { counter_width.bytes() as u64 };
+
+    let counters = width.saturating_mul(height).saturating_mul(channels);
+
+    // The tiles are u64s, but there's at least one of them
+    match memory {
+        None         => counters.saturating_mul(counter_bytes),
+        Some(memory) => counters.saturating_mul(8)
+            .min(memory.max((tiles::TILE * tiles::TILE).saturating_mul(channels).saturating_mul(8)))
+            .saturating_add(tiles::batch_bytes()),
+    }
+
+}
+
+/// the bytes of a number of an `Orbit` in `precision`
+fn number_bytes(precision:math::Precision) -> u64 {
+    match precision {
+        math::Precision::F32          => 8,
+        math::Precision::F64          => 16,
+        math::Precision::DoubleDouble => 32,
+    }
+}
+
+/// the memory of the `Orbit`s a thread collects during a phase
+///
+/// Without whole `Orbit`s, only the numbers inside the frame are kept, but that may be all of them.
+pub fn orbits(phase_len:u64, iterations:u64, precision:math::Precision) -> u64 {
+    phase_len.saturating_mul(iterations.saturating_add(1)).saturating_mul(number_bytes(precision))
+}
+
+/// the memory of a reference `Orbit`: in full precision, and twice in f64, see `math::Reference`
+pub fn reference(iterations:u64, precision:math::Precision) -> u64 {
+    iterations.saturating_add(1).saturating_mul(number_bytes(precision) + 32)
+}
+
+/// how much memory the system has available, in bytes, if it says so
+///
+/// That's `MemAvailable` of `/proc/meminfo` on Linux. Elsewhere there's no telling.
+pub fn available_memory() -> Option<u64> {
+
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+
+    let line = meminfo.lines().find(|l| l.starts_with("MemAvailable:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    Some(kilobytes * 1024)
+
+}
+
+/// `bytes` in kB, MB, GB and so on, whichever reads best
+pub fn human_bytes(bytes:u64) -> String {
+
+    let units = ["B", "kB", "MB", "GB", "TB", "PB", "EB"];
+
+    let mut n = bytes as f64;
+    let mut unit = 0;
+
+    while n >= 1000f64 && unit < units.len() - 1 {
+        n /= 1000f64;
+        unit += 1;
+    }
+
+    if unit == 0 { format!("{} B", bytes) } else { format!("{:.2} {}", n, units[unit]) }
+
+}
+
+/// `seconds` in days, hours, minutes and seconds, like `2h 05m 09s`, leaving out the parts, that
+/// are 0 in front, and the seconds, once it's days
+pub fn human_duration(seconds:f64) -> String {
+
+    if !seconds.is_finite() { return "forever".to_string() }
+    if seconds < 10f64 { return format!("{:.1}s", seconds.max(0f64)) }
+
+    let s = seconds.round() as u64;
+    let (d, h, m, s) = (s / 86400, s / 3600 % 24, s / 60 % 60, s % 60);
+
+    match (d, h, m) {
+        (0, 0, 0) => format!("{}s", s),
+        (0, 0, _) => format!("{}m {:02}s", m, s),
+        (0, _, _) => format!("{}h {:02}m {:02}s", h, m, s),
+        _         => format!("{}d {:02}h {:02}m", d, h, m),
+    }
+
+}
+
+/// how many samples a single thread computes per second, measured for `CALIBRATION`
+///
+/// The samples are drawn just like a thread of `butterbrot_run()` draws them, but not written back,
+/// which takes very little time in comparison.
+pub fn calibrate<T: math::Scalar>(frame:math::Frame<T>, generator:&math::OrbitGenerator<T>, iterations:u64, whole:bool, projection:math::Projection) -> f64 {
+
+    let timestamp = Instant::now();
+
+    let mut mh_orbits = math::MHOrbits::new(u64::MAX, 0, iterations, frame, whole, generator.clone(), projection);
+    let mut samples = 0u64;
+
+    while timestamp.elapsed() < CALIBRATION {
+        mh_orbits.next();
+        samples += 1;
+    }
+
+    samples as f64 / timestamp.elapsed().as_secs_f64()
+
+}
+
+/// how many counters of an escape-time image a single thread computes per second, measured for
+/// `CALIBRATION` or until the whole image is done
+///
+/// The rows are taken from all over the frame, as some parts of it take a lot longer than others.
+pub fn calibrate_escape_time<T: math::Scalar>(frame:&math::Frame<T>, generator:&math::OrbitGenerator<T>, width:u64, height:u64, iterations:u64) -> f64 {
+
+    let timestamp = Instant::now();
+    let mut counters = 0u64;
+
+    for k in 0..height {
+
+        // 7919 is a prime, so unless the height is a multiple of it, every row comes up once
+        let row = (k * 7919) % height;
+
+        let samples: Vec<math::Complex<T>> = (0..width).map(|column| frame.center_of(column, row)).collect();
+        generator.escape_batch(&samples, iterations);
+
+        counters += width;
+
+        if timestamp.elapsed() >= CALIBRATION { break }
+
+    }
+
+    counters as f64 / timestamp.elapsed().as_secs_f64().max(1e-9)
+
+}
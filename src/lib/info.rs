@@ -0,0 +1,86 @@
+//! `birb info`: what's in a birb, without turning it into an image first
+//!
+//! That's its size, channels and counters, everything its `Header` records about the render, and,
+//! unless only the header is asked for, the total and the largest count of every channel, which
+//! takes reading through all of the birb, a band of rows at a time.
+
+use super::cli::{self, Command, Opt, Positional};
+use super::estimate::human_bytes;
+use super::io::BirbReader;
+use super::SIGNED_CHANNELS;
+
+/// `birb info`
+pub fn command() -> Command {
+    Command {
+        name: "info",
+        about: "Print, what a birb records about its render, and its counts",
+        help: "Prints the size, the channels and the type of the counters of every birb, everything it records about how it was rendered, like its frame, --iterations, --samples and --seed, and the sum and the largest value of every channel. Birbs without a header only know their size.",
+        options: vec![
+            Opt::flag("header", "Only print what the header says, without reading through all the counters, which takes a while for huge birbs."),
+        ],
+        positionals: vec![
+            Positional { name: "<birb>", required: true, many: true, help: "The birbs to describe." },
+        ],
+    }
+}
+
+/// describes the birbs, that the command line `args` name, the name of the program first
+pub fn run(args:Vec<String>) {
+
+    let m = cli::parse(&command(), &args[1..]);
+
+    for (k, filename) in m.positionals.iter().enumerate() {
+
+        if k > 0 { out!() }
+
+        let mut reader = BirbReader::open(filename);
+        let header = reader.header().clone();
+
+        let bytes = std::fs::metadata(filename).map(|f| f.len()).unwrap_or(0);
+
+        out!("\x1B[34m{}\x1B[0m, {}", filename, human_bytes(bytes));
+        out!("  size:        \x1B[33m{}\x1B[0mx\x1B[33m{}\x1B[0m", reader.width(), reader.height());
+        out!("  counters:    {}{}", header.counter_width().name(), header.tile().map(|t| format!(", in tiles of {}x{}", t, t)).unwrap_or_default());
+        out!("  channels:    {}", header.channels.join(", "));
+
+        for (key, value) in header.meta.iter().filter(|(k, _)| k != "counter" && k != "tile") {
+            out!("  {:12} {}", format!("{}:", key), value);
+        }
+
+        if m.flag("header") { continue }
+
+        // The sums of the signed channels are two's complements, and wrap around
+        let channels = header.channels.len();
+        let signed: Vec<bool> = header.channels.iter().map(|c| SIGNED_CHANNELS.contains(&c.as_ref())).collect();
+
+        let mut sums = vec![0u64; channels];
+        let mut maxs = vec![0u64; channels];
+
+        for (_, band) in reader.bands() {
+
+            let n = (band[0] * band[1]) as usize;
+
+            for c in 0..channels {
+                for &x in band[2 + c * n..2 + (c + 1) * n].iter() {
+                    if signed[c] {
+                        sums[c] = sums[c].wrapping_add(x);
+                    } else {
+                        sums[c] = sums[c].saturating_add(x);
+                        maxs[c] = maxs[c].max(x);
+                    }
+                }
+            }
+
+        }
+
+        for (c, name) in header.channels.iter().enumerate() {
+            if signed[c] {
+                out!("  {:12} sum \x1B[33m{}\x1B[0m", format!("{}:", name), sums[c] as i64);
+            } else {
+                out!("  {:12} sum \x1B[33m{}\x1B[0m, largest \x1B[33m{}\x1B[0m", format!("{}:", name), sums[c], maxs[c]);
+            }
+        }
+
+    }
+
+}
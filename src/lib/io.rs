@@ -1,48 +1,333 @@
 //! Helper functions for IO operations
 //!
 //!  - `gen_filename()` generates a partially random filename, for cases, where the user didn't
-//!  specify an output filename
+//!    specify an output filename
+//!  - `fill_template()` fills in output filenames like `{formula}_{width}x{height}_{date}.birb`
+//!    with what's known about a birb, see `template_values()`
 //!  - `read_birb()` reads and validates a `.birb` file
-//!  - `write_birb()` takes a filename and a buffer and attempts to write it to disk
+//!  - `read_header()` reads just the `Header` of a `.birb` file
+//!  - `write_birb()` takes a filename, a buffer and a `Header` and attempts to write it to disk
+//!  - `write_counters()` does the same for `Counters`, in whatever width they are
+//!  - `write_tiled()` does the same for `TiledCounters`, one tile at a time
+//!  - `BirbReader` reads a birb, plain or tiled, a band of rows at a time, so tools can stream
+//!    through birbs, that don't fit into memory, and `BirbWriter` writes one that way
+//!  - `read_rows()` reads just one band of rows of a birb
+//!  - `read_into()` adds a birb to the counters of a computation, for `butterbrot --append`
+//!  - `AtomicFile` is what all of the writing goes through: the file is only put in its place, once
+//!    it's complete, so a crash never leaves half a file behind
+//!  - `Reservation` keeps two renders of the same process, like the jobs of a queue, from writing
+//!    the same file at once
+//!  - `clobber()` and `check_output()` decide, what to do about output files, that exist already,
+//!    `clobber_options()` are the flags, that ask for it
+//!
+//! ### The birb format
+//!
+//! A birb with a header starts with the `u64` `MAGIC`, followed by a `u64` holding the length of
+//! the header text in bytes, followed by the header text itself, padded with spaces to a whole
+//! number of `u64`s. After that comes the old-school birb: width, height and then the channels,
+//! each `width * height` counters long. Width and height are `u64`s, the counters are of the
+//! `CounterWidth` the `counter` key of the header names, `u64` if there is none. All of the numbers
+//! are little endian.
+//!
+//! The header text is a list of `key = value` lines. The `channels` key names the channels, in
+//! order, the `counter` key names the width of the counters, every other key is metadata. Birbs
+//! without a header are still read fine, they simply have a single `count` channel of `u64`s.
+//!
+//! A *tiled* birb has a `tile` key, the width and height of its square tiles. Its counters don't
+//! come channel by channel, but tile by tile, the tiles row by row. Every tile holds all the
+//! channels of its part of the birb, one after the other, each of them `tile * tile` counters,
+//! row by row. The tiles at the right and bottom edges stick out over the birb's edges, those
+//! counters are 0. That way each tile can be read and written on its own, see `TiledCounters`.
 
 use std::io::*;
 use std::fs::File;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use super::counters::{CounterWidth, Counters, Cells, Accumulator, add_band};
+use super::tiles::TiledCounters;
+use super::cli::Opt;
+use super::log::fail;
 extern crate rand;
 
-/// generates a partially random filename
+/// The first `u64` of a birb with a header: the bytes of "birb", then the format version
+pub const MAGIC: u64 = u64::from_le_bytes(*b"birb\x02\0\0\0");
+
+/// The header of a birb
+///
+/// `channels` are the names of the channels stored in the birb. The first channel always is
+/// `count`, the number of hits. `meta` is a list of `key = value` pairs, that describe how the
+/// birb came to be.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Header {
+    pub channels: Vec<String>,
+    pub meta: Vec<(String, String)>,
+}
+
+impl Header {
+
+    /// creates a `Header` with the named `channels` and no metadata
+    pub fn new(channels:&[&str]) -> Header {
+        Header { channels: channels.iter().map(|c| c.to_string()).collect(), meta: Vec::new() }
+    }
+
+    /// the value of the metadata `key`, if there is one
+    pub fn get(&self, key:&str) -> Option<&str> {
+        self.meta.iter().find(|(k,_)| k == key).map(|(_,v)| v.as_ref())
+    }
+
+    /// sets the metadata `key` to `value`, replacing the old value, if there was one
+    pub fn set(&mut self, key:&str, value:String) {
+        match self.meta.iter_mut().find(|(k,_)| k == key) {
+            Some(pair) => pair.1 = value,
+            None       => self.meta.push((key.to_string(), value)),
+        }
+    }
+
+    /// the index of the channel called `name`, if there is one
+    pub fn channel(&self, name:&str) -> Option<usize> {
+        self.channels.iter().position(|c| c == name)
+    }
+
+    /// removes the metadata `key`, if it's there
+    pub fn remove(&mut self, key:&str) {
+        self.meta.retain(|(k,_)| k != key)
+    }
+
+    /// the size of the tiles, if the birb is tiled
+    pub fn tile(&self) -> Option<u64> {
+        self.get("tile").map(|t| error!(t.parse::<u64>(), "The birb's tile size isn't a number."))
+    }
+
+    /// the width of the birb's counters, see `CounterWidth`
+    pub fn counter_width(&self) -> CounterWidth {
+        match self.get("counter") {
+            None       => CounterWidth::U64,
+            Some(name) => CounterWidth::from_name(name).unwrap_or_else(|| fail(format!("The birb's counters are of an unknown type '{}'.", name))),
+        }
+    }
+
+    /// turns the header into its text representation
+    fn to_text(&self) -> String {
+
+        let mut text = format!("channels = {}\n", self.channels.join(", "));
+
+        self.meta.iter().for_each(|(k,v)| text.push_str(&format!("{} = {}\n", k, v)));
+
+        text
+
+    }
+
+    /// parses the text representation of a header, lines without a '=' are ignored
+    fn from_text(text:&str) -> Header {
+
+        let mut header = Header::new(&[]);
+
+        text.lines()
+            .filter_map(|l| {
+                let mut kv = l.splitn(2, '=');
+                match (kv.next(), kv.next()) {
+                    (Some(k), Some(v)) => Some((k.trim().to_string(), v.trim().to_string())),
+                    _ => None,
+                }
+            })
+            .for_each(|(k,v)| {
+                if k == "channels" {
+                    header.channels = v.split(',').map(|c| c.trim().to_string()).collect();
+                } else {
+                    header.meta.push((k,v));
+                }
+            });
+
+        header
+
+    }
+
+}
+
+/// generates a partially random filename, that isn't taken yet
 ///
-/// `gen_filename()` generates a filename along the pattern `birb_XXXX.birb` where `XXXX` are four
-/// randomly selected uppercase letters from the ASCII set.
+/// `gen_filename()` generates a filename along the pattern `birb_XXXXXXXX.birb` where `XXXXXXXX`
+/// are eight randomly selected uppercase letters and digits from the ASCII set. That's 36^8, almost
+/// three trillion names, and should one of them be taken anyway, another one is picked.
 ///
 /// Takes an extension as argument, that will be added as ".extension" to the end of the random
 /// filename.
 #[inline]
 pub fn gen_filename(ext:&str) -> String {
 
+    loop {
+
+        let filename = format!("birb_{}.{}", random_symbols(8), ext);
+
+        if !std::path::Path::new(&filename).exists() { return filename }
+
+    }
+
+}
+
+/// `n` randomly selected uppercase letters and digits
+fn random_symbols(n:usize) -> String {
+
     use rand::distributions::Distribution;
 
+    const SYMBOLS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
     let mut r = rand::thread_rng();
-    let dist  = rand::distributions::Uniform::from(65..90u8);
+    let dist  = rand::distributions::Uniform::from(0..SYMBOLS.len());
+
+    (0..n).map(|_| SYMBOLS[dist.sample(&mut r)] as char).collect()
+
+}
+
+/// the values of a birb, that filename templates can use, see `fill_template()`
+///
+/// That's every key of its `Header`, its `width` and `height`, and its `formula`, which is the first
+/// word of its `fractal`, like `mandelbrot` or `julia`.
+pub fn template_values(header:&Header, width:u64, height:u64) -> Vec<(String, String)> {
+
+    let mut values = header.meta.clone();
+
+    values.push(("width".to_string(), width.to_string()));
+    values.push(("height".to_string(), height.to_string()));
 
-    let a = dist.sample(&mut r) as char;
-    let b = dist.sample(&mut r) as char;
-    let c = dist.sample(&mut r) as char;
-    let d = dist.sample(&mut r) as char;
+    if let Some(formula) = header.get("fractal").and_then(|f| f.split_whitespace().next()) {
+        values.push(("formula".to_string(), formula.to_string()));
+    }
 
-    format!("birb_{}{}{}{}.{}", a,b,c,d,ext)
+    values
+
+}
+
+/// fills in the placeholders of the filename `template`, like `{width}x{height}_{date}.birb`
+///
+/// Every `{key}` is replaced with the value of `key` in `values`, `{date}` with the date and time
+/// in UTC, like `20261018-153012`, and `{rand}` with eight random letters and digits. Whitespace in
+/// the values becomes `_`, and slashes become `-`, so no value ever makes for another directory. An
+/// unknown placeholder is an error, which lists the known ones.
+///
+/// Should the name be taken, and `clobber` doesn't say what to do about that, `_2`, `_3` and so on
+/// is added in front of its extension, until it isn't. Filenames without any placeholders are
+/// returned as they are, `check_output()` takes care of them.
+pub fn fill_template(template:&str, values:&[(String, String)], clobber:Clobber) -> String {
+
+    if !template.contains('{') { return template.to_string() }
+
+    let mut name = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+
+        let end = rest[start..].find('}').map(|e| start + e).unwrap_or_else(|| fail(format!("The filename template '{}' has a '{{' without a '}}'.", template)));
+
+        let key = &rest[start + 1..end];
+
+        let value = match key {
+            "date" => date(),
+            "rand" => random_symbols(8),
+            _      => values.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone()).unwrap_or_else(|| {
+                let known: Vec<&str> = values.iter().map(|(k, _)| k.as_ref()).chain(vec!["date", "rand"]).collect();
+                fail(format!("The filename template '{}' asks for '{{{}}}', which isn't known here. Known are: {}.", template, key, known.join(", ")))
+            }),
+        };
+
+        name.push_str(&rest[..start]);
+        name.push_str(&value.split_whitespace().collect::<Vec<_>>().join("_").replace('/', "-"));
+
+        rest = &rest[end + 1..];
+
+    }
+
+    name.push_str(rest);
+
+    let path = std::path::Path::new(&name);
+
+    if clobber != Clobber::Refuse || !path.exists() { return name }
+
+    // Taken, so count up, until a name is free
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let free = (2..).map(|n| match path.extension() {
+        Some(ext) => path.with_file_name(format!("{}_{}.{}", stem, n, ext.to_string_lossy())),
+        None      => path.with_file_name(format!("{}_{}", stem, n)),
+    }).find(|p| !p.exists());
+
+    free.map(|p| p.to_string_lossy().into_owned()).unwrap_or(name)
+
+}
+
+/// the date and time right now, in UTC, like `20261018-153012`
+fn date() -> String {
+
+    let seconds = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, time) = (seconds / 86400, seconds % 86400);
+
+    // The days since 1970-01-01 to year, month and day, see Howard Hinnant's civil_from_days()
+    let z   = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp  = (5 * doy + 2) / 153;
+
+    let day   = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year  = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, time / 3600, time % 3600 / 60, time % 60)
+
+}
+
+/// Reads the `Header` of the birb, that `f` is at the start of
+///
+/// Returns the `Header` and the bytes, that had to be read to figure out, there is no header, if
+/// the birb doesn't have one -- those belong to the birb's data. Birbs without a header get a
+/// `Header` with just the `count` channel.
+fn read_header_from(f:&mut File) -> (Header, Vec<u8>) {
+
+    let mut word = [0u8; 8];
+
+    if f.read_exact(&mut word).is_err() || u64::from_le_bytes(word) != MAGIC {
+        return (Header::new(&["count"]), word.to_vec())
+    }
+
+    error!(f.read_exact(&mut word), "There was an error while reading the birb header.", full);
+    let length = u64::from_le_bytes(word);
+
+    // A header longer than the whole file is something else, that just starts with the MAGIC
+    let size = error!(f.metadata(), "There was an error while reading the birb header.", full).len();
+    if length > size { fail("The read birb file is malformed.") }
+
+    let mut text = vec![0u8; length as usize];
+    error!(f.read_exact(&mut text), "There was an error while reading the birb header.", full);
+
+    let text = error!(String::from_utf8(text), "The birb header isn't valid text.");
+
+    (Header::from_text(&text), Vec::new())
+
+}
+
+/// Reads just the `Header` of a `.birb` file
+pub fn read_header(filename: &str) -> Header {
+
+    let mut f = error!(File::open(filename), "Couldn't open file. The specified birb-file doesn't exist or is inaccessible.", full);
+
+    read_header_from(&mut f).0
 
 }
 
 /// Reads a `.birb` file to `Vec<u64>`.
 /// Takes the `filename` to read from.
 ///
+/// The returned `Vec` holds the width, the height and then all of the birb's channels, the header
+/// is skipped -- use `read_header()` to get it.
+///
 /// ### Possible Errors and Panics
 ///
 /// If the `.birb` file doesn't contain full u64 numbers, it will be truncated to fit.
 ///
 /// If the `.birb` file isn't formatted properly, that is, contains as many `u64` values as the
-/// product of the first two `u64`s plus 2 (width * height + 2 (for the width and the height)),
-/// this function exits the process with an error message.
+/// product of the first two `u64`s and the number of channels plus 2 (width * height * channels +
+/// 2 (for the width and the height)), this function exits the process with an error message.
 ///
 /// If the `.birb` file couldn't be read, this function also exits the process, with a different
 /// error message.
@@ -52,10 +337,22 @@ pub fn read_birb(filename: &str) -> Vec<u64> {
 
     let mut f = error!(File::open(filename), "Couldn't open file. The specified birb-file doesn't exist or is inaccessible.", full);
 
-    let mut birb_raw: Vec<u8> = Vec::new();
+    let (header, mut birb_raw) = read_header_from(&mut f);
+
+    // Tiled birbs are put back together band by band
+    if header.tile().is_some() {
+        return read_rows(filename, 0..u64::MAX)
+    }
 
     error!(f.read_to_end(&mut birb_raw), "There was an error while reading the birb file.", full);
 
+    // Narrower counters are widened to u64 one by one, only u64 birbs can be taken as they are
+    let counter_width = header.counter_width();
+
+    if counter_width != CounterWidth::U64 {
+        return widen(&birb_raw, counter_width, header.channels.len() as u64)
+    }
+
 
     /* Convert to u64 */
 
@@ -107,10 +404,10 @@ pub fn read_birb(filename: &str) -> Vec<u64> {
     /* Validate birb format */
 
     // The first two numbers in a valid birb are its width and height, so their product is the
-    // number of numbers stored in the rest of the birb. That product plus 2 for the first two
-    // should equal the buffer's size exactly.
+    // number of numbers stored in each channel of the birb. That product times the number of
+    // channels plus 2 for the first two should equal the buffer's size exactly.
     // If there are less than two numbers, you obviously screwed up.
-    if birb.len() < 2 || birb.len() as u64 != birb[0] * birb[1] + 2 {
+    if birb.len() < 2 || birb.len() as u64 != birb[0] * birb[1] * header.channels.len() as u64 + 2 {
 
         error!(Err("honk"), "The read birb file is malformed.");
 
@@ -120,13 +417,107 @@ pub fn read_birb(filename: &str) -> Vec<u64> {
 
 }
 
+/// the little endian number in `bytes`, which are 8 at most
+pub fn word(bytes:&[u8]) -> u64 {
+    let mut w = [0u8; 8];
+    w[..bytes.len()].copy_from_slice(bytes);
+    u64::from_le_bytes(w)
+}
+
+/// turns the raw bytes of a birb with narrow counters into a birb of `u64`s
+///
+/// Exits the process with an error message, if there aren't exactly as many counters, as width,
+/// height and the number of `channels` call for.
+fn widen(raw:&[u8], counter_width:CounterWidth, channels:u64) -> Vec<u64> {
+
+    if raw.len() < 16 { error!(Err("honk"), "The read birb file is malformed."); }
+
+    let width  = word(&raw[0..8]);
+    let height = word(&raw[8..16]);
+
+    let bytes = counter_width.bytes();
+    let cells = &raw[16..];
+
+    if cells.len() as u64 != width * height * channels * bytes as u64 {
+        error!(Err("honk"), "The read birb file is malformed.");
+    }
+
+    let mut birb = Vec::with_capacity(cells.len() / bytes + 2);
+    birb.push(width);
+    birb.push(height);
+    birb.extend(cells.chunks(bytes).map(word));
+
+    birb
+
+}
+
+/// Writes the `MAGIC`, the `header` and its length to the start of a birb file
+fn write_header_to(f:&mut impl Write, header:&Header) {
+
+    let mut text = header.to_text();
+
+    // Pad, so the data starts at a whole u64
+    while !text.len().is_multiple_of(8) { text.push(' ') }
+
+    error!(f.write_all(&MAGIC.to_le_bytes()), "There was an error while writing the birb file", full);
+    error!(f.write_all(&(text.len() as u64).to_le_bytes()), "There was an error while writing the birb file", full);
+    error!(f.write_all(text.as_bytes()), "There was an error while writing the birb file", full);
+
+}
+
+/// Writes a `.birb` file from `Counters`, in their current `CounterWidth`, which is recorded in the
+/// `header`
+///
+/// The counters are written as they are, so there's no need for a second buffer of `u64`s.
+pub fn write_counters(filename: &str, counters: &Counters, header: &Header) {
+
+    let mut header = header.clone();
+    header.set("counter", counters.counter_width().name().to_string());
+    header.remove("tile");
+
+    let mut f = error!(AtomicFile::create(filename), "Couldn't open birb file to write.", full);
+
+    write_header_to(&mut f, &header);
+
+    error!(f.write_all(&counters.width().to_le_bytes()), "There was an error while writing the birb file", full);
+    error!(f.write_all(&counters.height().to_le_bytes()), "There was an error while writing the birb file", full);
+
+    let written = match counters.cells() {
+        Cells::U16(c) => c.iter().try_for_each(|n| f.write_all(&n.to_le_bytes())),
+        Cells::U32(c) => c.iter().try_for_each(|n| f.write_all(&n.to_le_bytes())),
+        Cells::U64(c) => c.iter().try_for_each(|n| f.write_all(&n.to_le_bytes())),
+    };
+
+    error!(written.and_then(|_| f.commit()), "There was an error while writing the birb file", full);
+
+}
+
 /// Writes a `.birb` file from an existing birb buffer.
-/// Takes a `filename` to write to and a borrow of a `birb`, which is the data to write.
-pub fn write_birb(filename: &str, birb: &Vec<u64>) {
+/// Takes a `filename` to write to, a borrow of a `birb`, which is the data to write, and the
+/// `header` to write in front of it.
+///
+/// If the `header` names a narrower `CounterWidth` than `u64`, the birb is written with counters of
+/// that width, as `Counters`, see `write_counters()`. They're widened, if they don't fit.
+pub fn write_birb(filename: &str, birb: &[u64], header: &Header) {
+
+    let counter_width = header.counter_width();
+
+    if counter_width != CounterWidth::U64 {
+        return write_counters(filename, &Counters::from_birb(birb, counter_width), header)
+    }
+
 
     /* Open file to write to */
 
-    let mut f = error!(File::create(filename), "Couldn't open birb file to write.", full);
+    let mut f = error!(AtomicFile::create(filename), "Couldn't open birb file to write.", full);
+
+
+    /* Write the header, it's not tiled anymore */
+
+    let mut header = header.clone();
+    header.remove("tile");
+
+    write_header_to(&mut f, &header);
 
 
     /* Convert from u64 to u8 */
@@ -147,7 +538,7 @@ pub fn write_birb(filename: &str, birb: &Vec<u64>) {
         for offset in 0..8 {
 
             unsafe {
-                let ptr = ptr8.clone().offset(offset);
+                let ptr = ptr8.offset(offset);
                 birb_raw.push(*ptr);
             }
 
@@ -158,6 +549,529 @@ pub fn write_birb(filename: &str, birb: &Vec<u64>) {
 
     /* Write */
 
-    error!(f.write_all(&birb_raw), "There was an error while writing the birb file", full);
+    error!(f.write_all(&birb_raw).and_then(|_| f.commit()), "There was an error while writing the birb file", full);
+
+}
+
+/// Writes `cells` as counters of `counter_width`
+fn write_cells(f:&mut impl Write, cells:&[u64], counter_width:CounterWidth) -> Result<()> {
+    match counter_width {
+        CounterWidth::U16 => cells.iter().try_for_each(|&n| f.write_all(&(n as u16).to_le_bytes())),
+        CounterWidth::U32 => cells.iter().try_for_each(|&n| f.write_all(&(n as u32).to_le_bytes())),
+        CounterWidth::U64 => cells.iter().try_for_each(|&n| f.write_all(&n.to_le_bytes())),
+    }
+}
+
+/// Writes a tiled `.birb` file from `TiledCounters`, one tile at a time
+///
+/// The counters are written in the narrowest `CounterWidth`, that holds all of them, which is
+/// recorded in the `header`, as is the size of the tiles.
+pub fn write_tiled(filename: &str, counters: &mut TiledCounters, header: &Header) {
+
+    use super::tiles::TILE;
+
+    counters.flush();
+
+    let counter_width = match counters.max() {
+        (_, true)    => CounterWidth::U64,  // the two's complements need all the bits
+        (max, false) => CounterWidth::for_max(max),
+    };
+
+    let mut header = header.clone();
+    header.set("counter", counter_width.name().to_string());
+    header.set("tile", TILE.to_string());
+
+    let mut f = error!(AtomicFile::create(filename), "Couldn't open birb file to write.", full);
+
+    write_header_to(&mut f, &header);
+
+    error!(f.write_all(&counters.width().to_le_bytes()), "There was an error while writing the birb file", full);
+    error!(f.write_all(&counters.height().to_le_bytes()), "There was an error while writing the birb file", full);
+
+    let mut written = Ok(());
+
+    for t in 0..counters.tile_count() {
+        if written.is_err() { break }
+        counters.with_tile(t, |cells| written = write_cells(&mut f, cells, counter_width));
+    }
+
+    error!(written.and_then(|_| f.commit()), "There was an error while writing the birb file", full);
+
+}
+
+
+/* Streaming */
+
+/// How many bytes of counters a band of `BirbReader::bands()` holds, about
+const BAND_BYTES: u64 = 4 << 20;
+
+/// Reads a birb a band of rows at a time, so the whole birb never needs to be in memory
+///
+/// Every band is a birb of `u64`s of its own: as wide as the birb, as high as the band, with all
+/// the channels. Plain and tiled birbs of any `CounterWidth` read the same. Converting or combining
+/// birbs band by band takes a few MB, however large the birbs are.
+///
+/// ```no_run
+/// # use butterbrot_rs::io::BirbReader;
+/// let mut reader = BirbReader::open("huge.birb");
+///
+/// for (first_row, band) in reader.bands() {
+///     // band[0] is the width, band[1] the number of rows, the counters follow
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BirbReader {
+    header: Header,
+    file: File,
+    data: u64,          // where in the file the counters start
+    width: u64,
+    height: u64,
+    bytes: u64,         // of a counter
+}
+
+impl BirbReader {
+
+    /// opens the birb `filename`, and reads its `Header` and dimensions
+    pub fn open(filename: &str) -> BirbReader {
+
+        let mut file = error!(File::open(filename), "Couldn't open file. The specified birb-file doesn't exist or is inaccessible.", full);
+
+        let (header, leftover) = read_header_from(&mut file);
+
+        // Without a header, the width was read already
+        let start = if leftover.is_empty() { error!(file.stream_position(), "There was an error while reading the birb file.", full) } else { 0 };
+
+        let mut dimensions = [0u8; 16];
+        error!(file.seek(SeekFrom::Start(start)).and_then(|_| file.read_exact(&mut dimensions)), "The read birb file is malformed.");
+
+        let bytes = header.counter_width().bytes() as u64;
+        let (width, height) = (word(&dimensions[..8]), word(&dimensions[8..]));
+
+        // The counters fill the rest of the file, no more and no less, or it's cut off, or not a
+        // birb at all. Tiles are whole, even at the edges.
+        let cells = match header.tile() {
+            None       => width.checked_mul(height),
+            Some(0)    => None,
+            Some(tile) => width.div_ceil(tile).checked_mul(height.div_ceil(tile)).and_then(|n| n.checked_mul(tile)).and_then(|n| n.checked_mul(tile)),
+        };
+
+        let size = cells
+            .and_then(|n| n.checked_mul(header.channels.len() as u64))
+            .and_then(|n| n.checked_mul(bytes))
+            .and_then(|n| n.checked_add(start + 16));
+
+        if size != Some(error!(file.metadata(), "There was an error while reading the birb file.", full).len()) {
+            fail("The read birb file is malformed.")
+        }
+
+        BirbReader { header, file, data: start + 16, width, height, bytes }
+
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn width(&self) -> u64 {
+        self.width
+    }
+
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    pub fn channels(&self) -> u64 {
+        self.header.channels.len() as u64
+    }
+
+    /// how many rows go into a band of about `BAND_BYTES`, one at least
+    pub fn band_rows(&self) -> u64 {
+        (BAND_BYTES / self.width.saturating_mul(self.channels()).saturating_mul(8).max(1)).max(1)
+    }
+
+    /// all the bands of the birb, from the top, with the index of their first row
+    pub fn bands(&mut self) -> Bands<'_> {
+        let rows = self.band_rows();
+        Bands { reader: self, next: 0, rows }
+    }
+
+    fn read_at(&mut self, offset: u64, buffer: &mut [u8]) {
+        error!(self.file.seek(SeekFrom::Start(offset)).and_then(|_| self.file.read_exact(buffer)), "The read birb file is malformed.");
+    }
+
+    /// reads the `rows`, which are cut off at the birb's height, as a birb of `u64`s
+    pub fn rows(&mut self, rows: std::ops::Range<u64>) -> Vec<u64> {
+
+        let (width, height, bytes) = (self.width, self.height, self.bytes);
+        let channels = self.channels();
+
+        let rows = rows.start.min(height)..rows.end.min(height);
+        let len  = rows.end - rows.start;
+
+        let mut band = vec![0u64; (width * len * channels + 2) as usize];
+        band[0] = width;
+        band[1] = len;
+
+        if len == 0 { return band }
+
+        match self.header.tile() {
+
+            // Every channel is one piece
+            None => {
+
+                let mut buffer = vec![0u8; (len * width * bytes) as usize];
+
+                for c in 0..channels {
+
+                    self.read_at(self.data + (c * height + rows.start) * width * bytes, &mut buffer);
+
+                    let cells = &mut band[(2 + c * len * width) as usize..(2 + (c + 1) * len * width) as usize];
+                    cells.iter_mut().zip(buffer.chunks(bytes as usize)).for_each(|(n, b)| *n = word(b));
+
+                }
+
+            },
+
+            // Every channel of every tile, that overlaps the rows, has a piece
+            Some(tile) => {
+
+                let columns = width.div_ceil(tile);
+                let mut buffer = Vec::new();
+
+                for ty in rows.start / tile..(rows.end - 1) / tile + 1 {
+
+                    let ys = (ty * tile).max(rows.start)..((ty + 1) * tile).min(rows.end);
+
+                    for tx in 0..columns {
+
+                        let xs = tx * tile..((tx + 1) * tile).min(width);
+
+                        for c in 0..channels {
+
+                            // The rows of a channel of a tile follow each other
+                            let offset = ((ty * columns + tx) * channels + c) * tile * tile + (ys.start - ty * tile) * tile;
+
+                            buffer.resize(((ys.end - ys.start) * tile * bytes) as usize, 0);
+                            self.read_at(self.data + offset * bytes, &mut buffer);
+
+                            for y in ys.clone() {
+                                for x in xs.clone() {
+                                    let cell = ((y - ys.start) * tile + x - tx * tile) * bytes;
+                                    band[(2 + c * len * width + (y - rows.start) * width + x) as usize] = word(&buffer[cell as usize..(cell + bytes) as usize]);
+                                }
+                            }
+
+                        }
+
+                    }
+
+                }
+
+            },
+
+        }
+
+        band
+
+    }
+
+}
+
+/// the bands of a birb, see `BirbReader::bands()`
+#[derive(Debug)]
+pub struct Bands<'a> {
+    reader: &'a mut BirbReader,
+    next: u64,
+    rows: u64,
+}
+
+impl<'a> Iterator for Bands<'a> {
+
+    type Item = (u64, Vec<u64>);
+
+    fn next(&mut self) -> Option<(u64, Vec<u64>)> {
+
+        if self.next >= self.reader.height() { return None }
+
+        let first = self.next;
+        self.next += self.rows;
+
+        Some((first, self.reader.rows(first..first + self.rows)))
+
+    }
+
+}
+
+/// Writes a plain birb a band of rows at a time, the counterpart of `BirbReader`
+///
+/// The `CounterWidth` has to be known up front, counters that don't fit into it are cut off.
+#[derive(Debug)]
+pub struct BirbWriter {
+    file: AtomicFile,
+    data: u64,
+    width: u64,
+    height: u64,
+    counter_width: CounterWidth,
+}
+
+impl BirbWriter {
+
+    /// creates the birb `filename` of `width * height` counters of `counter_width` per channel of
+    /// the `header`, and writes the header and dimensions
+    pub fn create(filename: &str, header: &Header, width: u64, height: u64, counter_width: CounterWidth) -> BirbWriter {
+
+        let mut header = header.clone();
+        header.set("counter", counter_width.name().to_string());
+        header.remove("tile");
+
+        let mut file = error!(AtomicFile::create(filename), "Couldn't open birb file to write.", full);
+
+        write_header_to(&mut file, &header);
+
+        error!(file.write_all(&width.to_le_bytes()).and_then(|_| file.write_all(&height.to_le_bytes())), "There was an error while writing the birb file", full);
+
+        let data = error!(file.stream_position(), "There was an error while writing the birb file", full);
+
+        BirbWriter { file, data, width, height, counter_width }
+
+    }
+
+    /// writes the `band`, a birb of `u64`s as returned by `BirbReader`, starting at row `first`
+    pub fn write_rows(&mut self, first: u64, band: &[u64]) {
+
+        let (width, len) = (band[0], band[1]);
+        let bytes = self.counter_width.bytes() as u64;
+
+        for (c, cells) in band[2..].chunks((width * len).max(1) as usize).enumerate() {
+
+            let offset = self.data + ((c as u64 * self.height + first) * self.width) * bytes;
+
+            error!(self.file.seek(SeekFrom::Start(offset)).and_then(|_| write_cells(&mut self.file, cells, self.counter_width)), "There was an error while writing the birb file", full);
+
+        }
+
+    }
+
+    /// writes out everything, that's still buffered, and puts the birb in its place, see
+    /// `AtomicFile`
+    pub fn finish(self) {
+        error!(self.file.commit(), "There was an error while writing the birb file", full);
+    }
+
+}
+
+/// Reads the width and the height of a birb
+pub fn read_dimensions(filename: &str) -> (u64, u64) {
+    let reader = BirbReader::open(filename);
+    (reader.width(), reader.height())
+}
+
+/// Reads the `rows` of a birb, plain or tiled and of any `CounterWidth`, as a birb of `u64`s, see
+/// `BirbReader::rows()`
+pub fn read_rows(filename: &str, rows: std::ops::Range<u64>) -> Vec<u64> {
+    BirbReader::open(filename).rows(rows)
+}
+
+/// Adds the counters of the birb `filename` to `birb`, a band at a time, see `counters::add_band()`
+///
+/// `signed` says for every channel, whether it wraps around. The birb needs to be just as large as
+/// `birb`, and have as many channels.
+pub fn read_into<A: Accumulator>(filename: &str, birb: &mut A, signed: &[bool]) {
+
+    let mut reader = BirbReader::open(filename);
+
+    for (first, band) in reader.bands() {
+        add_band(birb, first, &band, signed);
+    }
+
+}
+
+
+/* Writing Safely */
+
+/// A file, that's written under a temporary name next to the one it's meant to have, and only
+/// takes its place, once it's complete
+///
+/// `commit()` flushes it, syncs it to the disk and renames it, which replaces whatever had the
+/// name before in one go. Should the process crash or the disk fill up before that, an old file of
+/// the name is still there, untouched. The temporary file is removed again, if it's dropped without
+/// being committed.
+#[derive(Debug)]
+pub struct AtomicFile {
+    file: BufWriter<File>,
+    temp: PathBuf,
+    target: PathBuf,
+    committed: bool,
+}
+
+impl AtomicFile {
+
+    /// creates the temporary file for `filename`, hidden in the same directory, so the rename
+    /// doesn't have to move it across file systems
+    ///
+    /// Every one gets a name of its own, made of the process and a count, so files, that are
+    /// written for the same name at once, say by the renders of a queue, or `check_output()`
+    /// trying, whether it can be written, never get into each other's way.
+    pub fn create(filename: &str) -> Result<AtomicFile> {
+
+        static COUNT: AtomicU64 = AtomicU64::new(0);
+
+        let target = PathBuf::from(filename);
+
+        let name = target.file_name().ok_or_else(|| Error::new(ErrorKind::InvalidInput, "not the name of a file"))?;
+        let temp = target.with_file_name(format!(".{}.{}.{}.tmp", name.to_string_lossy(), std::process::id(), COUNT.fetch_add(1, Ordering::SeqCst)));
+
+        // Not one of another process either, whose number was the same
+        let file = BufWriter::new(File::options().write(true).create_new(true).open(&temp)?);
+
+        Ok(AtomicFile { file, temp, target, committed: false })
+
+    }
+
+    /// makes the file take its place, once everything has been written
+    pub fn commit(mut self) -> Result<()> {
+
+        self.file.flush()?;
+        self.file.get_ref().sync_all()?;
+
+        std::fs::rename(&self.temp, &self.target)?;
+        self.committed = true;
+
+        // The rename itself only lasts, once the directory is synced, too. Not every system can.
+        let directory = self.target.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or_else(|| std::path::Path::new("."));
+        if let Ok(directory) = File::open(directory) { let _ = directory.sync_all(); }
+
+        Ok(())
+
+    }
+
+}
+
+impl Write for AtomicFile {
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.file.flush()
+    }
+
+}
+
+impl Seek for AtomicFile {
+
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.file.seek(pos)
+    }
+
+}
+
+impl Drop for AtomicFile {
+
+    fn drop(&mut self) {
+        if !self.committed { let _ = std::fs::remove_file(&self.temp); }
+    }
+
+}
+
+/// A file, that a render of this process is going to write, which no other render of it may
+/// write at the same time, say another job of a queue, that came to the same name
+///
+/// Both would find the name free, and one would replace the birb of the other in the end. The
+/// name is free again, once the reservation is dropped, when the render is done or has failed.
+#[derive(Debug)]
+pub struct Reservation(PathBuf);
+
+/// The files, that are reserved
+static RESERVED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+impl Reservation {
+
+    /// reserves `filename`, or stops with an error, if another render of the process has already
+    pub fn new(filename: &str) -> Reservation {
+
+        // The same file may be named in more than one way
+        let path = std::path::absolute(filename).unwrap_or_else(|_| PathBuf::from(filename));
+
+        let mut reserved = RESERVED.lock().unwrap_or_else(|e| e.into_inner());
+        if reserved.contains(&path) {
+            fail(format!("The file '{}' is being written by another render already, give them names of their own.", filename))
+        }
+        reserved.push(path.clone());
+
+        Reservation(path)
+
+    }
+
+}
+
+impl Drop for Reservation {
+
+    fn drop(&mut self) {
+        RESERVED.lock().unwrap_or_else(|e| e.into_inner()).retain(|p| *p != self.0);
+    }
+
+}
+
+/// What to do about an output file, that exists already
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Clobber {
+    Refuse,         // stop with an error, before anything is computed
+    Overwrite,      // replace it, --overwrite
+    Keep,           // leave it be, and do nothing at all, --no-clobber
+}
+
+/// the flags, that `clobber()` takes out of the command line, for the help and the completions
+pub fn clobber_options() -> Vec<Opt> {
+    vec![
+        Opt::flag("overwrite", "Replace the output file, if it exists already. Without it, nothing is done, so an earlier file isn't lost by accident."),
+        Opt::flag("no-clobber", "Leave the output file be, if it exists already, and exit right away without doing anything. Handy for scripts, that are run again, after they've been interrupted."),
+    ]
+}
+
+/// takes `--overwrite` and `--no-clobber` out of the command line `args`, and returns, what they
+/// ask for, and the arguments left
+///
+/// Every binary, that writes a file, understands them, wherever they are on the command line.
+pub fn clobber(args: Vec<String>) -> (Clobber, Vec<String>) {
+
+    let overwrite  = args.iter().any(|a| a == "--overwrite");
+    let no_clobber = args.iter().any(|a| a == "--no-clobber");
+
+    let clobber = match (overwrite, no_clobber) {
+        (false, false) => Clobber::Refuse,
+        (true,  false) => Clobber::Overwrite,
+        (false, true)  => Clobber::Keep,
+        (true,  true)  => error!(Err(()), "The flags '--overwrite' and '--no-clobber' can't be used together."),
+    };
+
+    (clobber, args.into_iter().filter(|a| a != "--overwrite" && a != "--no-clobber").collect())
+
+}
+
+/// checks, whether the output `filename` may be written, right at the start, so it isn't found out
+/// after hours of computing
+///
+/// An existing file is an error, unless it may be overwritten. With `Clobber::Keep` it ends the
+/// process right away, successfully, so scripts can simply skip whatever has been done already.
+/// And a file, that can't be created, say in a directory, that doesn't exist, is an error, too.
+pub fn check_output(filename: &str, clobber: Clobber) {
+
+    if std::path::Path::new(filename).exists() {
+        match clobber {
+            Clobber::Overwrite => (),
+            Clobber::Keep      => {
+                note!("\x1B[33m{}\x1B[0m exists already, leaving it be", filename);
+                std::process::exit(0);
+            },
+            Clobber::Refuse    => fail(format!("The file '{}' exists already. Use '--overwrite' to replace it, or '--no-clobber' to leave it be.", filename)),
+        }
+    }
+
+    // The temporary file is removed again right away
+    if let Err(e) = AtomicFile::create(filename) {
+        fail(format!("Can't write the file '{}': {}", filename, e))
+    }
 
 }
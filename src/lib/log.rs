@@ -0,0 +1,156 @@
+//! How the birb tools talk: the progress of a render as text or JSON, notes, warnings and errors,
+//! and whether any of it is coloured
+//!
+//! Everything, that isn't what a command is run for, goes to stderr: notes like "Now writing to
+//! file", warnings and errors. That leaves stdout to the progress of a render, which
+//! `--log-format json` turns into one JSON object a line, and to what commands like `birb info`
+//! print. `--quiet` leaves out the progress and the notes, but never the warnings and errors.
+//!
+//! Colours are only used on terminals, and not at all, if `NO_COLOR` is set, so log files and
+//! pipes get plain text. The messages are written with their colours anyway, `paint()` takes them
+//! out again, where they don't belong. See the `note!()`, `warning!()`, `out!()` and `errln!()`
+//! macros, and `fail()`, which all errors stop with.
+
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Once, OnceLock};
+
+/// How the progress of a render is logged, `--log-format`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Text,       // for people, every interval
+    Json,       // one JSON object a line, for scripts
+}
+
+static JSON:  AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// sets how the whole process logs, the `--log-format` and `--quiet` of the render
+pub fn set(format:Format, quiet:bool) {
+    JSON.store(format == Format::Json, Ordering::SeqCst);
+    QUIET.store(quiet, Ordering::SeqCst);
+}
+
+pub fn format() -> Format {
+    if JSON.load(Ordering::SeqCst) { Format::Json } else { Format::Text }
+}
+
+/// whether nothing but warnings and errors should be printed
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::SeqCst)
+}
+
+/// What a thread panics with, once `fail()` has said what went wrong
+#[derive(Debug)]
+pub struct Failed;
+
+/// prints the error `msg`, with a red `Error:` in front, and stops the thread, which is the whole
+/// program, unless it's a job of a queue
+///
+/// It panics with `Failed`, which the panic hook leaves quiet, as the error has been said already.
+/// Any other panic is a bug, that's still reported as usual, even in another job of a queue.
+pub fn fail<M: std::fmt::Display>(msg:M) -> ! {
+
+    static HOOK: Once = Once::new();
+
+    HOOK.call_once(|| {
+        let default = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| if !info.payload().is::<Failed>() { default(info) }));
+    });
+
+    errln!("\x1B[31;1mError:\x1B[0m {}", msg);
+    std::panic::panic_any(Failed)
+
+}
+
+/// Where a message goes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stream {
+    Out,
+    Err,
+}
+
+/// whether the `stream` gets colours: it's a terminal, and `NO_COLOR` isn't set
+pub fn colours(stream:Stream) -> bool {
+
+    static OUT: OnceLock<bool> = OnceLock::new();
+    static ERR: OnceLock<bool> = OnceLock::new();
+
+    let wanted = || std::env::var_os("NO_COLOR").map(|v| v.is_empty()).unwrap_or(true);
+
+    match stream {
+        Stream::Out => *OUT.get_or_init(|| wanted() && std::io::stdout().is_terminal()),
+        Stream::Err => *ERR.get_or_init(|| wanted() && std::io::stderr().is_terminal()),
+    }
+
+}
+
+/// `text` as it should be written to the `stream`: as it is, or without its colours
+pub fn paint(text:&str, stream:Stream) -> String {
+    if colours(stream) { text.to_string() } else { plain(text) }
+}
+
+/// `text` without the escape sequences of its colours, like `\x1B[31;1m`
+pub fn plain(text:&str) -> String {
+
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1B' {
+            // Everything up to the letter, that ends the sequence
+            for c in chars.by_ref() { if c.is_ascii_alphabetic() { break } }
+        } else {
+            out.push(c);
+        }
+    }
+
+    out
+
+}
+
+/// `s` as a JSON string, quotes included
+pub fn json_string(s:&str) -> String {
+
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"'  => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c    => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+
+}
+
+/// `x` as a JSON number, `null` for the ones JSON doesn't have, like infinity
+pub fn json_number(x:f64) -> String {
+    if x.is_finite() { format!("{}", x) } else { "null".to_string() }
+}
+
+/// prints the JSON object of the `fields` to stdout, one line, as the log of a render, unless it's
+/// `--quiet`
+///
+/// The `fields` are the keys, and their values, which are JSON already, see `json_string()` and
+/// `json_number()`. The `event` comes first, it says what the object is about.
+pub fn event(event:&str, fields:&[(&str, String)]) {
+
+    if quiet() { return }
+
+    let mut line = format!("{{\"event\":{}", json_string(event));
+    for (key, value) in fields.iter() {
+        line.push_str(&format!(",{}:{}", json_string(key), value));
+    }
+    line.push('}');
+
+    println!("{}", line);
+
+}
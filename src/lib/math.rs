@@ -3,26 +3,311 @@
 
 extern crate rand;
 use rand::Rng;
-use rand::prelude::ThreadRng;
+use rand::{FromEntropy, SeedableRng};
+use rand::rngs::SmallRng;
+use std::cell::Cell;
+use std::fmt::Debug;
+use std::ops::{Add, Sub, Mul, Div, Neg};
+use std::str::FromStr;
+
+/* The Scalar Types */
+
+/// the kind of number, that the parts of a `Complex` number are made of
+///
+/// All the maths in here is generic over the `Scalar`, so the very same code computes with `f32`,
+/// `f64` or `DoubleDouble` numbers. The wider the type, the deeper one can zoom, before neighbouring
+/// counters of a birb can't be told apart anymore, see `Precision`. The narrower the type, the
+/// faster the computation.
+///
+/// `DoubleDouble` is the widest of them, so it is what numbers are read and passed around as, until
+/// the computation narrows them to the `Scalar` it runs with.
+pub trait Scalar:
+    Copy + Debug + PartialEq + PartialOrd + Send + Sync + 'static
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self> + Neg<Output = Self>
+{
+
+    /// the name of the type, as used for `--precision`
+    const NAME: &'static str;
+
+    /// the distance from 1 to the next larger number of this type
+    const EPSILON: f64;
+
+    fn from_f64(x:f64) -> Self;
+    fn to_f64(self) -> f64;
+
+    /// turns a `DoubleDouble` into this type, rounding if necessary
+    fn narrow(x:DoubleDouble) -> Self;
+
+    /// turns this into a `DoubleDouble`, which is always exact
+    fn widen(self) -> DoubleDouble;
+
+    fn sqrt(self) -> Self;
+    fn is_finite(self) -> bool;
+
+}
+
+impl Scalar for f64 {
+
+    const NAME: &'static str = "f64";
+    const EPSILON: f64 = f64::EPSILON;
+
+    #[inline] fn from_f64(x:f64) -> f64 { x }
+    #[inline] fn to_f64(self) -> f64 { self }
+    #[inline] fn narrow(x:DoubleDouble) -> f64 { x.hi }
+    #[inline] fn widen(self) -> DoubleDouble { DoubleDouble::new(self) }
+    #[inline] fn sqrt(self) -> f64 { f64::sqrt(self) }
+    #[inline] fn is_finite(self) -> bool { f64::is_finite(self) }
+
+}
+
+impl Scalar for f32 {
+
+    const NAME: &'static str = "f32";
+    const EPSILON: f64 = f32::EPSILON as f64;
+
+    #[inline] fn from_f64(x:f64) -> f32 { x as f32 }
+    #[inline] fn to_f64(self) -> f64 { self as f64 }
+    #[inline] fn narrow(x:DoubleDouble) -> f32 { x.hi as f32 }
+    #[inline] fn widen(self) -> DoubleDouble { DoubleDouble::new(self as f64) }
+    #[inline] fn sqrt(self) -> f32 { f32::sqrt(self) }
+    #[inline] fn is_finite(self) -> bool { f32::is_finite(self) }
+
+}
+
+/// a number with about twice the precision of an `f64`, made from two `f64`s
+///
+/// The number is `hi + lo`, where `lo` is the rounding error of `hi`, so `|lo|` is at most half an
+/// ulp of `hi`. The arithmetic is the classic error-free transformation business (Dekker, Knuth and
+/// the QD library by Hida, Li and Bailey): every operation on the `hi`s also computes its exact
+/// rounding error, which is then folded into the `lo`s. That gives about 106 bits of mantissa, at
+/// about ten to twenty times the cost of an `f64`.
+///
+/// The exponent range is the one of `f64`, which is plenty for the Buddhabrot.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct DoubleDouble {
+    pub hi: f64,
+    pub lo: f64,
+}
+
+impl DoubleDouble {
+
+    pub fn new(x:f64) -> DoubleDouble {
+        DoubleDouble { hi: x, lo: 0f64 }
+    }
+
+    /// `a + b` and its exact rounding error, if `|a| >= |b|`
+    #[inline]
+    fn quick_two_sum(a:f64, b:f64) -> DoubleDouble {
+        let hi = a + b;
+        DoubleDouble { hi, lo: b - (hi - a) }
+    }
+
+    /// `a + b` and its exact rounding error
+    #[inline]
+    fn two_sum(a:f64, b:f64) -> DoubleDouble {
+        let hi = a + b;
+        let v  = hi - a;
+        DoubleDouble { hi, lo: (a - (hi - v)) + (b - v) }
+    }
+
+    /// splits `a` into two halves of 26 bits each, whose products are exact
+    #[inline]
+    fn split(a:f64) -> (f64, f64) {
+        let t  = 134_217_729f64 * a;    // 2^27 + 1
+        let hi = t - (t - a);
+        (hi, a - hi)
+    }
+
+    /// `a * b` and its exact rounding error
+    ///
+    /// `f64::mul_add()` would do this in one go, but without a hardware FMA it's a painfully slow
+    /// library call, so this splits the numbers instead.
+    #[inline]
+    fn two_prod(a:f64, b:f64) -> DoubleDouble {
+        let hi = a * b;
+        let (a_hi, a_lo) = DoubleDouble::split(a);
+        let (b_hi, b_lo) = DoubleDouble::split(b);
+        DoubleDouble { hi, lo: ((a_hi * b_hi - hi) + a_hi * b_lo + a_lo * b_hi) + a_lo * b_lo }
+    }
+
+}
+
+impl Add for DoubleDouble {
+    type Output = DoubleDouble;
+
+    #[inline]
+    fn add(self, b:DoubleDouble) -> DoubleDouble {
+        let s = DoubleDouble::two_sum(self.hi, b.hi);
+        let t = DoubleDouble::two_sum(self.lo, b.lo);
+        let s = DoubleDouble::quick_two_sum(s.hi, s.lo + t.hi);
+        DoubleDouble::quick_two_sum(s.hi, s.lo + t.lo)
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = DoubleDouble;
+
+    #[inline]
+    fn sub(self, b:DoubleDouble) -> DoubleDouble {
+        self + -b
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = DoubleDouble;
+
+    #[inline]
+    fn neg(self) -> DoubleDouble {
+        DoubleDouble { hi: -self.hi, lo: -self.lo }
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = DoubleDouble;
+
+    #[inline]
+    fn mul(self, b:DoubleDouble) -> DoubleDouble {
+        let p = DoubleDouble::two_prod(self.hi, b.hi);
+        DoubleDouble::quick_two_sum(p.hi, p.lo + (self.hi * b.lo + self.lo * b.hi))
+    }
+}
+
+impl Div for DoubleDouble {
+    type Output = DoubleDouble;
+
+    /// long division, one `f64` digit at a time
+    #[inline]
+    fn div(self, b:DoubleDouble) -> DoubleDouble {
+        let q1 = self.hi / b.hi;
+        let r  = self - b * DoubleDouble::new(q1);
+        let q2 = r.hi / b.hi;
+        let r  = r - b * DoubleDouble::new(q2);
+        let q3 = r.hi / b.hi;
+        DoubleDouble::quick_two_sum(q1, q2) + DoubleDouble::new(q3)
+    }
+}
+
+impl Scalar for DoubleDouble {
+
+    const NAME: &'static str = "double-double";
+    const EPSILON: f64 = f64::EPSILON * f64::EPSILON;
+
+    #[inline] fn from_f64(x:f64) -> DoubleDouble { DoubleDouble::new(x) }
+    #[inline] fn to_f64(self) -> f64 { self.hi }
+    #[inline] fn narrow(x:DoubleDouble) -> DoubleDouble { x }
+    #[inline] fn widen(self) -> DoubleDouble { self }
+    #[inline] fn is_finite(self) -> bool { self.hi.is_finite() && self.lo.is_finite() }
+
+    /// one Newton step on top of the `f64` square root
+    #[inline]
+    fn sqrt(self) -> DoubleDouble {
+
+        if self.hi <= 0f64 { return DoubleDouble::new(self.hi.sqrt()) }
+
+        let x  = 1f64 / self.hi.sqrt();
+        let ax = self.hi * x;
+
+        DoubleDouble::two_sum(ax, (self - DoubleDouble::two_prod(ax, ax)).hi * (x * 0.5))
+
+    }
+
+}
+
+/// the largest power of ten `DoubleDouble::from_str()` shifts a number by, beyond that it
+/// overflows to infinity or underflows to zero
+const MAX_SHIFT: i32 = 700;
+
+impl FromStr for DoubleDouble {
+    type Err = ();
+
+    /// parses decimal numbers like `-0.743643887037158704752191506114774` or `1.5e-20`, keeping all
+    /// the digits a `DoubleDouble` can hold, not just the ones an `f64` can
+    ///
+    /// `inf`, `infinity` and `nan` are taken, like `f64` takes them.
+    fn from_str(s:&str) -> Result<DoubleDouble, ()> {
+
+        let word = s.trim_start_matches(['+', '-']).to_ascii_lowercase();
+        if word == "inf" || word == "infinity" || word == "nan" {
+            return s.parse::<f64>().map(DoubleDouble::new).map_err(|_| ())
+        }
+
+        let (negative, s) = match s.as_bytes().first() {
+            Some(b'-') => (true,  &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _          => (false, s),
+        };
+
+        let (mantissa, exponent) = match s.find(['e', 'E']) {
+            Some(e) => (&s[..e], s[e + 1..].parse::<i32>().map_err(|_| ())?),
+            None    => (s, 0),
+        };
+
+        // Collect the digits into one big integer, remembering where the point was
+        let ten = DoubleDouble::new(10f64);
+        let mut x = DoubleDouble::new(0f64);
+        let mut digits = 0;
+        let mut point  = None;
+
+        for (k, c) in mantissa.chars().enumerate() {
+            match c {
+                '0'..='9' => {
+                    x = x * ten + DoubleDouble::new(c.to_digit(10).unwrap() as f64);
+                    digits += 1;
+                },
+                '.' if point.is_none() => point = Some(k),
+                _ => return Err(()),
+            }
+        }
+
+        if digits == 0 { return Err(()) }
+
+        // Then shift the point to where it belongs. The digits make an integer of at most 1e308, so
+        // beyond a shift of MAX_SHIFT either way there's only infinity or zero left.
+        let decimals = point.map(|k| mantissa.len() - k - 1).unwrap_or(0) as i32;
+        let shift = exponent.saturating_sub(decimals);
+
+        let sign = if negative { -1f64 } else { 1f64 };
+
+        if x.hi == 0f64 || shift < -MAX_SHIFT { return Ok(DoubleDouble::new(sign * 0f64)) }
+        if shift > MAX_SHIFT                  { return Ok(DoubleDouble::new(sign * f64::INFINITY)) }
+
+        let mut power = DoubleDouble::new(1f64);
+        for _ in 0..shift.abs() { power = power * ten }
+
+        let x = if shift < 0 { x / power } else { x * power };
+
+        Ok(if negative { -x } else { x })
+
+    }
+}
+
 
 /* The Complex Number Type */
 
 /// represents a complex number, use field `r` to access real part and field `i` for imaginairy
 /// part.
-#[derive(Clone, Copy, Debug)]
-pub struct Complex {
-    pub r: f64,
-    pub i: f64,
+///
+/// The parts are `f64`s, unless some other `Scalar` is asked for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Complex<T = f64> {
+    pub r: T,
+    pub i: T,
 }
 
-impl Complex {
+impl<T: Scalar> Complex<T> {
     /// well, what could this function possibly do?
-    pub fn new(r:f64, i:f64) -> Complex {
+    pub fn new(r:T, i:T) -> Complex<T> {
         Complex { r, i }
     }
 
+    /// the same number with parts of another `Scalar` type
     #[inline]
-    pub fn multiply(&self, c:&Complex) -> Complex {
+    pub fn cast<U: Scalar>(&self) -> Complex<U> {
+        Complex::new(U::narrow(self.r.widen()), U::narrow(self.i.widen()))
+    }
+
+    #[inline]
+    pub fn multiply(&self, c:&Complex<T>) -> Complex<T> {
 
         let r = self.r * c.r - self.i * c.i;
         let i = self.r * c.i + self.i * c.r;
@@ -32,19 +317,19 @@ impl Complex {
     }
 
     #[inline]
-    pub fn abs(&self) -> f64 {
+    pub fn abs(&self) -> T {
 
-        (self.r.powi(2) + self.i.powi(2)).sqrt()
+        (self.r * self.r + self.i * self.i).sqrt()
 
     }
 
     #[inline]
-    pub fn squared(&self) -> Complex {
+    pub fn squared(&self) -> Complex<T> {
         self.multiply(self)
     }
 
     #[inline]
-    pub fn add(&self, c:&Complex) -> Complex {
+    pub fn add(&self, c:&Complex<T>) -> Complex<T> {
 
         let r = self.r + c.r;
         let i = self.i + c.i;
@@ -54,7 +339,7 @@ impl Complex {
     }
 
     #[inline]
-    pub fn sub(&self, c:&Complex) -> Complex {
+    pub fn sub(&self, c:&Complex<T>) -> Complex<T> {
 
         let r = self.r - c.r;
         let i = self.i - c.i;
@@ -72,23 +357,29 @@ impl Complex {
 /// mandelbrot equation to the iterator's internal "last complex number", starting from the complex
 /// number passed to `Orbit::new()`
 #[derive(Clone, Copy)]
-pub struct Orbit {
-    c: Complex,
-    z: Complex,
-    n: i32,
+pub struct Orbit<T = f64> {
+    c: Complex<T>,
+    z: Complex<T>,
+    n: u64,
 }
 
-impl Orbit {
+impl<T: Scalar> Orbit<T> {
 
     /// creates a new `Orbit` starting at `c`, which yields `n` complex numbers
-    pub fn new(c:Complex, n:i32) -> Orbit {
-        let z = Complex::new(0f64, 0f64);
+    pub fn new(c:Complex<T>, n:u64) -> Orbit<T> {
+        let z = Complex::new(T::from_f64(0f64), T::from_f64(0f64));
         Orbit { c, z, n }
     }
 
+    /// creates a new `Orbit` of `c`, that starts from `z0` instead of 0, which yields `n` complex
+    /// numbers
+    pub fn starting_at(c:Complex<T>, z0:Complex<T>, n:u64) -> Orbit<T> {
+        Orbit { c, z: z0, n }
+    }
+
     /// the actual mandelbrot function
     #[inline]
-    fn mandelbrot(c:&Complex, z:&Complex) -> Complex {
+    fn mandelbrot(c:&Complex<T>, z:&Complex<T>) -> Complex<T> {
 
         let zz = z.squared();
 
@@ -98,9 +389,9 @@ impl Orbit {
 
 }
 
-impl Iterator for Orbit {
+impl<T: Scalar> Iterator for Orbit<T> {
 
-    type Item = Complex;
+    type Item = Complex<T>;
 
     /// returns the next `Complex` number of this `Orbit`'s sequence, unless the `Orbit` has been
     /// consumed entirely
@@ -122,8 +413,769 @@ impl Iterator for Orbit {
 }
 
 
+/* Mandelbrot and Julia */
+
+/// what the samples of `MHOrbits` are
+///
+/// An `Orbit` is decided by two numbers: the `c` in the mandelbrot equation and the `z0` it starts
+/// from. The Buddhabrot samples `c` and starts every `Orbit` at a fixed `z0`, which usually is 0.
+/// The Julia-style Buddhabrot is the dual: it fixes `c` and samples `z0`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fractal<T = f64> {
+    Mandelbrot { z0: Complex<T> },
+    Julia { c: Complex<T> },
+}
+
+impl<T: Scalar> Fractal<T> {
+
+    /// the classic Buddhabrot, sampling `c` and starting from 0
+    pub fn mandelbrot() -> Fractal<T> {
+        Fractal::Mandelbrot { z0: Complex::new(T::from_f64(0f64), T::from_f64(0f64)) }
+    }
+
+    /// the same `Fractal` with numbers of another `Scalar` type
+    pub fn cast<U: Scalar>(&self) -> Fractal<U> {
+        match *self {
+            Fractal::Mandelbrot { z0 } => Fractal::Mandelbrot { z0: z0.cast() },
+            Fractal::Julia { c }       => Fractal::Julia { c: c.cast() },
+        }
+    }
+
+    /// the `Orbit` of the `sample`, which yields `n` complex numbers
+    #[inline]
+    pub fn orbit(&self, sample:Complex<T>, n:u64) -> Orbit<T> {
+        Orbit::starting_at(self.c(&sample), self.z0(&sample), n)
+    }
+
+    /// the `z0` of the `Orbit` of the `sample`
+    #[inline]
+    pub fn z0(&self, sample:&Complex<T>) -> Complex<T> {
+        match *self {
+            Fractal::Mandelbrot { z0 } => z0,
+            Fractal::Julia { .. }      => *sample,
+        }
+    }
+
+    /// the `c` of the `Orbit` of the `sample`
+    #[inline]
+    pub fn c(&self, sample:&Complex<T>) -> Complex<T> {
+        match *self {
+            Fractal::Mandelbrot { .. } => *sample,
+            Fractal::Julia { c }       => c,
+        }
+    }
+
+}
+
+
+/* Projections of the four dimensional Orbit space */
+
+/// The names of the four axes of the `(z, c)` space, in the order `Projection` uses them
+pub const AXES: [&str; 4] = ["zr", "zi", "cr", "ci"];
+
+/// maps the numbers of an `Orbit` from the four dimensional `(z, c)` space onto a plane
+///
+/// The Buddhabrot only shows `z`, but every number of an `Orbit` really is a point
+/// `(z.r, z.i, c.r, c.i)` in four dimensions, and the Buddhabrot is just one of its shadows. A
+/// `Projection` is a 2x4 matrix, whose first row makes the real part and whose second row makes the
+/// imaginairy part of the projected number, so other shadows can be rendered through the very same
+/// frame and birb.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Projection {
+    pub m: [[f64; 4]; 2],
+}
+
+impl Projection {
+
+    /// the classic Buddhabrot, the `zr-zi` plane
+    pub fn buddhabrot() -> Projection {
+        Projection { m: [[1f64, 0f64, 0f64, 0f64], [0f64, 1f64, 0f64, 0f64]] }
+    }
+
+    /// the projection onto the plane of two of the `AXES`, written like `zr-cr`, the first axis
+    /// becomes the real part, the second the imaginairy part
+    pub fn plane(name:&str) -> Option<Projection> {
+
+        let (a, b) = Projection::axes(name)?;
+
+        let mut m = [[0f64; 4]; 2];
+        m[0][a] = 1f64;
+        m[1][b] = 1f64;
+
+        Some(Projection { m })
+
+    }
+
+    /// parses a pair of `AXES` written like `zr-cr` into their indices
+    pub fn axes(name:&str) -> Option<(usize, usize)> {
+
+        let mut names = name.splitn(2, '-');
+        let (first, second) = (names.next()?, names.next()?);
+
+        let a = AXES.iter().position(|&x| x == first)?;
+        let b = AXES.iter().position(|&x| x == second)?;
+
+        if a == b { return None }
+
+        Some((a, b))
+
+    }
+
+    /// rotates the four dimensional space by `angle` radians in the plane of the axes `a` and `b`,
+    /// *before* projecting it
+    ///
+    /// That's multiplying the projection matrix with a rotation matrix from the right. Rotating
+    /// through e.g. the `zr-cr` plane in small steps smoothly turns the Buddhabrot into its
+    /// `zr-cr` shadow, which makes for nice animations.
+    pub fn rotated(&self, a:usize, b:usize, angle:f64) -> Projection {
+
+        let (sin, cos) = angle.sin_cos();
+
+        let mut m = self.m;
+
+        for row in m.iter_mut() {
+            let (x, y) = (row[a], row[b]);
+            row[a] = x *  cos + y * sin;
+            row[b] = x * -sin + y * cos;
+        }
+
+        Projection { m }
+
+    }
+
+    /// projects the number `z` of the `Orbit` of `c`
+    #[inline]
+    pub fn project<T: Scalar>(&self, z:&Complex<T>, c:&Complex<T>) -> Complex<T> {
+
+        let p = [z.r, z.i, c.r, c.i];
+        let m = &self.m;
+        let f = T::from_f64;
+
+        Complex::new(
+            f(m[0][0]) * p[0] + f(m[0][1]) * p[1] + f(m[0][2]) * p[2] + f(m[0][3]) * p[3],
+            f(m[1][0]) * p[0] + f(m[1][1]) * p[1] + f(m[1][2]) * p[2] + f(m[1][3]) * p[3],
+        )
+
+    }
+
+}
+
+
+/* The Frame */
+
+/// the rectangle of the complex plane, that a birb shows, cut into `width` times `height` counters
+///
+/// `lower_bound` is the corner with the SMALLEST coordinates, `upper_bound` the corner with the
+/// LARGEST coordinates, and `step_size` is the size of a single counter in either direction.
+///
+/// The corners are of the `Scalar` type, the computation runs with, but the `step_size` always is
+/// an `f64`. Differences between numbers inside the frame are small enough for it, even at deep
+/// zooms, as long as they are taken before converting to `f64`, see `pixel()`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame<T = f64> {
+    pub lower_bound: Complex<T>,
+    pub upper_bound: Complex<T>,
+    pub step_size: [f64; 2],
+}
+
+impl<T: Scalar> Frame<T> {
+
+    /// creates the `Frame` spanned by two diagonally opposite corners, cut into `width` times
+    /// `height` counters
+    pub fn new(corner_1:Complex<T>, corner_2:Complex<T>, width:u64, height:u64) -> Frame<T> {
+
+        let step_size: [f64;2] = [
+            (corner_1.r - corner_2.r).to_f64().abs() / (width  as f64),  // stepsize in x direction
+            (corner_1.i - corner_2.i).to_f64().abs() / (height as f64)   // stepsize in y direction
+            ];
+
+        // The Frame, we explore, has four corners.
+        let (a, b, c, d)  = {
+            match (corner_1.r < corner_2.r, corner_1.i < corner_2.i) {
+                ( true,  true) => (corner_1.r, corner_1.i, corner_2.r, corner_2.i),
+                ( true, false) => (corner_1.r, corner_2.i, corner_2.r, corner_1.i),
+                (false,  true) => (corner_2.r, corner_1.i, corner_1.r, corner_2.i),
+                (false, false) => (corner_2.r, corner_2.i, corner_1.r, corner_1.i),
+            }
+        };
+
+        Frame { lower_bound: Complex::new(a, b), upper_bound: Complex::new(c, d), step_size }
+
+    }
+
+    /// Whether a complex number is in the range, we want to explore, or not
+    #[inline]
+    pub fn contains(&self, c:&Complex<T>) -> bool {
+
+        self.lower_bound.r <= c.r && c.r < self.upper_bound.r
+        && self.lower_bound.i <= c.i && c.i < self.upper_bound.i
+
+    }
+
+    /// the complex number in the middle of the counter in `column` and `row`
+    #[inline]
+    pub fn center_of(&self, column:u64, row:u64) -> Complex<T> {
+
+        Complex::new(
+            self.lower_bound.r + T::from_f64((column as f64 + 0.5) * self.step_size[0]),
+            self.lower_bound.i + T::from_f64((row    as f64 + 0.5) * self.step_size[1]),
+        )
+
+    }
+
+    /// the complex number in the middle of the frame
+    pub fn center(&self) -> Complex<T> {
+
+        Complex::new(
+            self.lower_bound.r + T::from_f64((self.upper_bound.r - self.lower_bound.r).to_f64() / 2f64),
+            self.lower_bound.i + T::from_f64((self.upper_bound.i - self.lower_bound.i).to_f64() / 2f64),
+        )
+
+    }
+
+    /// the pixel coordinates of a complex number, that is how many counters it is away from the
+    /// `lower_bound` in either direction, *not* floored
+    ///
+    /// The offset from the `lower_bound` is taken in the full precision of `T`, only the result is
+    /// an `f64`.
+    #[inline]
+    pub fn pixel(&self, c:&Complex<T>) -> (f64, f64) {
+
+        (
+            (c.r - self.lower_bound.r).to_f64() / self.step_size[0],
+            (c.i - self.lower_bound.i).to_f64() / self.step_size[1],
+        )
+
+    }
+
+}
+
+
+/* Clipping */
+
+/// Clips the line segment from `a` to `b` to the `frame`
+///
+/// This is the Liang-Barsky algorithm: the segment is written as `a + t * (b - a)` with `t` in
+/// `[0, 1]`, and each of the frame's four edges cuts away a part of that interval. Whatever is left
+/// of the interval is the part of the segment inside the frame.
+///
+/// Returns `None`, if no part of the segment lies inside the frame, or if either end isn't a finite
+/// number (escaped `Orbit`s like to run off to infinity).
+pub fn clip_segment<T: Scalar>(a:&Complex<T>, b:&Complex<T>, frame:&Frame<T>) -> Option<(Complex<T>, Complex<T>)> {
+
+    let lower_bound = &frame.lower_bound;
+    let upper_bound = &frame.upper_bound;
+
+    if !(a.r.is_finite() && a.i.is_finite() && b.r.is_finite() && b.i.is_finite()) {
+        return None
+    }
+
+    let d = b.sub(a);
+
+    let zero = T::from_f64(0f64);
+
+    let mut t0 = zero;
+    let mut t1 = T::from_f64(1f64);
+
+    // One pair per edge: (direction towards the outside, distance from a to the edge)
+    let edges = [
+        (-d.r, a.r - lower_bound.r),
+        ( d.r, upper_bound.r - a.r),
+        (-d.i, a.i - lower_bound.i),
+        ( d.i, upper_bound.i - a.i),
+    ];
+
+    for &(p, q) in edges.iter() {
+
+        if p == zero {
+            // Parallel to the edge and outside of it?
+            if q < zero { return None }
+            continue
+        }
+
+        let t = q / p;
+
+        if p < zero {
+            if t > t1 { return None }
+            if t > t0 { t0 = t }
+        } else {
+            if t < t0 { return None }
+            if t < t1 { t1 = t }
+        }
+
+    }
+
+    let start = Complex::new(a.r + t0 * d.r, a.i + t0 * d.i);
+    let end   = Complex::new(a.r + t1 * d.r, a.i + t1 * d.i);
+
+    Some((start, end))
+
+}
+
+
+/* Batched Orbits */
+
+/// how many `Orbit`s `escape_batch()` iterates in lockstep
+pub const LANES: usize = 8;
+
+/// how many steps `escape_batch()` takes in one go, before looking at the lanes
+const BLOCK: usize = 8;
+
+/// the index of the first escaped number of the `Orbit` of each sample, if it escapes within `n`
+/// numbers, just like `orbit().position()` would tell
+///
+/// The `Orbit` iterator steps through one `Complex` number at a time, which leaves most of the
+/// vector units of the CPU idle. This iterates `LANES` samples in lockstep instead, and keeps their
+/// numbers as a structure of arrays: one array for every part of `z` and `c`, with one lane per
+/// sample. The lanes take `BLOCK` steps at a time, doing the very same arithmetic on all of them
+/// without any branching, so the compiler turns the loop over the lanes into SIMD instructions.
+///
+/// Only after a block the lanes are looked at. A lane, that escaped somewhere in the block (its
+/// largest `|z|²` in the block is at least 4), is stepped through the block once more on its own
+/// from where it started, to find the exact index.
+/// Lanes, that are done, are refilled with the next sample right away, so a sample inside the set
+/// doesn't keep the others waiting.
+pub fn escape_batch<T: Scalar>(fractal:&Fractal<T>, samples:&[Complex<T>], n:u64) -> Vec<Option<usize>> {
+
+    let n = n as usize;
+
+    let zero = T::from_f64(0f64);
+    let four = T::from_f64(4f64);
+
+    let mut result = vec![None; samples.len()];
+
+    /* Set up the lanes */
+
+    // Idle lanes sit at 0 with c = 0, which stays 0 forever
+    let mut zr = [zero; LANES];
+    let mut zi = [zero; LANES];
+    let mut cr = [zero; LANES];
+    let mut ci = [zero; LANES];
+
+    let mut sample = [None; LANES];     // which sample a lane is working on
+    let mut k      = [0usize; LANES];   // how many numbers of it are done
+
+    let mut next = 0;                   // the next sample to hand out
+
+    // Puts the next sample (if any) into lane l
+    let mut refill = |l:usize, zr:&mut [T; LANES], zi:&mut [T; LANES], cr:&mut [T; LANES], ci:&mut [T; LANES], sample:&mut [Option<usize>; LANES]| {
+
+        let (z, c) = match samples.get(next) {
+            Some(s) => (fractal.z0(s), fractal.c(s)),
+            None    => (Complex::new(zero, zero), Complex::new(zero, zero)),
+        };
+
+        zr[l] = z.r;
+        zi[l] = z.i;
+        cr[l] = c.r;
+        ci[l] = c.i;
+
+        sample[l] = if next < samples.len() { Some(next) } else { None };
+        next += 1;
+
+    };
+
+    for l in 0..LANES { refill(l, &mut zr, &mut zi, &mut cr, &mut ci, &mut sample) }
+
+
+    /* Iterate in lockstep */
+
+    while sample.iter().any(|s| s.is_some()) {
+
+        let (start_r, start_i) = (zr, zi);
+        let mut peak = [zero; LANES];
+
+        for _ in 0..BLOCK {
+            for l in 0..LANES {
+                let (r, i) = (zr[l], zi[l]);
+                zr[l] = r * r - i * i + cr[l];
+                zi[l] = (r + r) * i   + ci[l];
+                let norm = zr[l] * zr[l] + zi[l] * zi[l];
+                peak[l] = if norm > peak[l] { norm } else { peak[l] };
+            }
+        }
+
+        for l in 0..LANES {
+
+            let s = match sample[l] {
+                Some(s) => s,
+                None    => continue,
+            };
+
+            // Escaped somewhere in the block? Find out where exactly.
+            let mut escape = None;
+
+            if peak[l] >= four {
+
+                let (mut r, mut i) = (start_r[l], start_i[l]);
+
+                for j in 0..BLOCK.min(n - k[l]) {
+                    let t = r * r - i * i + cr[l];
+                    i = (r + r) * i + ci[l];
+                    r = t;
+                    if r * r + i * i >= four { escape = Some(k[l] + j); break }
+                }
+
+            }
+
+            k[l] += BLOCK;
+
+            if escape.is_some() || k[l] >= n || peak[l] >= four {
+                result[s] = escape;
+                k[l] = 0;
+                refill(l, &mut zr, &mut zi, &mut cr, &mut ci, &mut sample);
+            }
+
+        }
+
+    }
+
+    result
+
+}
+
+
+/* Perturbation */
+
+/// an `Orbit` computed once in full precision, that the `Orbit`s of nearby samples are computed
+/// relative to, see `Perturbed`
+///
+/// The numbers `Z_0, Z_1, ...` of the reference `Orbit` are kept in the full precision of `T`, and
+/// rounded to `f64`, which is all the iteration of the differences needs. Their distances to `Z_0`
+/// are kept as well, rounded *after* taking the difference, for rebasing. The reference stops at
+/// its first escaped number, there's nothing to be learned from it after that.
+#[derive(Clone, Debug)]
+pub struct Reference<T = f64> {
+    sample: Complex<T>,
+    fractal: Fractal<T>,
+    z: Vec<Complex<T>>,
+    z_low: Vec<Complex<f64>>,
+    offset: Vec<Complex<f64>>,
+}
+
+impl<T: Scalar> Reference<T> {
+
+    /// computes the reference `Orbit` of the `sample`, `n` numbers long at most
+    ///
+    /// The `sample` should be somewhere in the middle of what's going to be sampled, the view
+    /// centre is a good choice. It's best, if its `Orbit` doesn't escape, cause the longer the
+    /// reference, the less often `Perturbed` needs to rebase.
+    pub fn new(fractal:Fractal<T>, sample:Complex<T>, n:u64) -> Reference<T> {
+
+        let two = T::from_f64(2f64);
+
+        let start = match fractal {
+            Fractal::Mandelbrot { z0 } => z0,
+            Fractal::Julia { .. }      => sample,
+        };
+
+        let mut z = vec![start];
+
+        for x in fractal.orbit(sample, n.max(1)) {
+            z.push(x);
+            if x.abs() >= two { break }
+        }
+
+        let z_low  = z.iter().map(|x| x.cast()).collect();
+        let offset = z.iter().map(|x| x.sub(&start).cast()).collect();
+
+        Reference { sample, fractal, z, z_low, offset }
+
+    }
+
+    /// the `Fractal` of the reference
+    pub fn fractal(&self) -> &Fractal<T> {
+        &self.fractal
+    }
+
+    /// the `Orbit` of `sample`, computed relative to the reference, which yields `n` complex
+    /// numbers
+    pub fn orbit(&self, sample:Complex<T>, n:u64) -> Perturbed<'_, T> {
+
+        let zero  = Complex::new(0f64, 0f64);
+        let delta = sample.sub(&self.sample).cast();
+
+        // The difference of the samples either is a difference of the c's, or of the z0's
+        let (dc, delta) = match self.fractal {
+            Fractal::Mandelbrot { .. } => (delta, zero),
+            Fractal::Julia { .. }      => (zero, delta),
+        };
+
+        Perturbed { reference: self, dc, delta, m: 0, n }
+
+    }
+
+}
+
+/// iterator, that yields the same numbers as an `Orbit`, but computes them as small differences
+/// `δ` to the numbers `Z_m` of a `Reference`
+///
+/// With `z = Z + δ` and `c = C + Δc` the mandelbrot equation turns into
+///
+/// `δ_m+1 = 2 Z_m δ_m + δ_m² + Δc`
+///
+/// which only involves the *differences*. Those are tiny at deep zooms, but an `f64` holds tiny
+/// numbers just as precisely as any others, so only the `Reference` needs to be computed in full
+/// precision, once. The yielded numbers are `Z_m + δ`, the only operation in full precision per
+/// step.
+///
+/// The approximation breaks down, when the difference gets as large as the number itself, that is
+/// when `z` comes close to where the reference started -- the differences then lose all their
+/// precision to cancellation, and the `Orbit` glitches. It also runs out, when the reference
+/// escapes. In both cases the `Orbit` is *rebased*: the difference is taken to the start of the
+/// reference instead, `δ = z - Z_0`, and it carries on from `Z_0` (this is Zhuoran's rebasing, which
+/// keeps the differences small without needing more than one reference).
+pub struct Perturbed<'a, T> {
+    reference: &'a Reference<T>,
+    dc: Complex<f64>,
+    delta: Complex<f64>,
+    m: usize,
+    n: u64,
+}
+
+impl<'a, T: Scalar> Perturbed<'a, T> {
+
+    /// iterates the difference once, and returns the index `m` and the difference `δ`, that make
+    /// up the new number `Z_m + δ`, rebasing afterwards, if necessary
+    #[inline]
+    fn step(&mut self) -> (usize, Complex<f64>) {
+
+        let reference = self.reference;
+        let delta = self.delta;
+
+        // δ = 2 Z_m δ + δ² + Δc
+        let t = reference.z_low[self.m].multiply(&delta);
+        let delta = Complex::new(2f64 * t.r, 2f64 * t.i).add(&delta.squared()).add(&self.dc);
+
+        let m = self.m + 1;
+
+        // Glitched, or out of reference? Rebase!
+        let distance = reference.offset[m].add(&delta);     // z - Z_0
+
+        if distance.abs() < delta.abs() || m + 1 == reference.z.len() {
+            self.delta = distance;
+            self.m = 0;
+        } else {
+            self.delta = delta;
+            self.m = m;
+        }
+
+        (m, delta)
+
+    }
+
+    /// the index of the first escaped number, if any, like `position()` on the `Orbit` would tell
+    ///
+    /// This only needs the `f64` approximations of the numbers, so it doesn't bother computing
+    /// them in full precision.
+    pub fn escape(mut self) -> Option<usize> {
+
+        for k in 0..self.n as usize {
+
+            let (m, delta) = self.step();
+
+            if self.reference.z_low[m].add(&delta).abs() >= 2f64 { return Some(k) }
+
+        }
+
+        None
+
+    }
+
+}
+
+impl<'a, T: Scalar> Iterator for Perturbed<'a, T> {
+
+    type Item = Complex<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        if self.n == 0 { return None }
+
+        self.n -= 1;
+
+        let (m, delta) = self.step();
+
+        Some(self.reference.z[m].add(&delta.cast()))
+
+    }
+
+}
+
+/// how `MHOrbits` computes the `Orbit`s of its samples
+///
+/// `Direct` iterates every `Orbit` from scratch in the precision of `T`. `Perturbation` iterates
+/// them relative to a `Reference`, which is much faster than a wide `T` for deep zooms, see
+/// `Perturbed`.
+#[derive(Clone, Debug)]
+pub enum OrbitGenerator<T = f64> {
+    Direct(Fractal<T>),
+    Perturbation(Reference<T>),
+}
+
+impl<T: Scalar> OrbitGenerator<T> {
+
+    /// the `Fractal`, whose `Orbit`s are generated
+    pub fn fractal(&self) -> &Fractal<T> {
+        match self {
+            OrbitGenerator::Direct(fractal)         => fractal,
+            OrbitGenerator::Perturbation(reference) => reference.fractal(),
+        }
+    }
+
+    /// the `n` numbers of the `Orbit` of the `sample`
+    #[inline]
+    pub fn orbit(&self, sample:Complex<T>, n:u64) -> Vec<Complex<T>> {
+        match self {
+            OrbitGenerator::Direct(fractal)         => fractal.orbit(sample, n).collect(),
+            OrbitGenerator::Perturbation(reference) => reference.orbit(sample, n).collect(),
+        }
+    }
+
+    /// the index of the first escaped number of the `Orbit` of the `sample`, if it escapes within
+    /// `n` numbers
+    #[inline]
+    pub fn escape(&self, sample:Complex<T>, n:u64) -> Option<usize> {
+
+        let two = T::from_f64(2f64);
+
+        match self {
+            OrbitGenerator::Direct(fractal)         => fractal.orbit(sample, n).position(|z| z.abs() >= two),
+            OrbitGenerator::Perturbation(reference) => reference.orbit(sample, n).escape(),
+        }
+
+    }
+
+    /// `escape()` for many samples at once, see `escape_batch()`
+    ///
+    /// Perturbation already does its iterating in `f64`, so it just goes through the samples one by
+    /// one.
+    #[inline]
+    pub fn escape_batch(&self, samples:&[Complex<T>], n:u64) -> Vec<Option<usize>> {
+        match self {
+            OrbitGenerator::Direct(fractal)         => escape_batch(fractal, samples, n),
+            OrbitGenerator::Perturbation(reference) => samples.iter().map(|s| reference.orbit(*s, n).escape()).collect(),
+        }
+    }
+
+}
+
+
+/* Precision */
+
+/// How many ulps of the coordinates a counter of a birb needs to span at least, so that rounding
+/// errors, which pile up along an `Orbit`, don't smear the numbers across neighbouring counters
+pub const PIXEL_ULPS: f64 = 4096.0;
+
+/// the `Scalar` type, a computation runs with
+///
+/// Zooming in makes the counters of a birb smaller, while the coordinates stay about the same
+/// size. Beyond a zoom of about 1e12 an `f64` can't tell the numbers in neighbouring counters
+/// apart anymore, and the render collapses into a few smeared pixels. A `DoubleDouble` gets about
+/// as far as 1e27, at a much higher cost.
+///
+/// An `f32` only gets to zooms of about 1e3, but it's the fastest of them. It is meant for quick
+/// previews only, so it's never picked automatically, and birbs computed with it are marked as
+/// previews, see `is_preview`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Precision {
+    F32,
+    F64,
+    DoubleDouble,
+}
+
+impl Precision {
+
+    /// all of them, from the narrowest (and fastest) to the widest
+    pub const ALL: [Precision; 3] = [Precision::F32, Precision::F64, Precision::DoubleDouble];
+
+    /// the ones good enough for production renders, from the narrowest to the widest
+    pub const AUTO: [Precision; 2] = [Precision::F64, Precision::DoubleDouble];
+
+    /// the name of the `Scalar` type, as used for `--precision`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Precision::F32          => f32::NAME,
+            Precision::F64          => f64::NAME,
+            Precision::DoubleDouble => DoubleDouble::NAME,
+        }
+    }
+
+    pub fn from_name(name:&str) -> Option<Precision> {
+        Precision::ALL.iter().find(|p| p.name() == name).cloned()
+    }
+
+    pub fn epsilon(&self) -> f64 {
+        match self {
+            Precision::F32          => f32::EPSILON as f64,
+            Precision::F64          => f64::EPSILON,
+            Precision::DoubleDouble => DoubleDouble::EPSILON,
+        }
+    }
+
+    /// whether a computation with this precision is a low-precision preview, that mustn't be mixed
+    /// with production renders
+    pub fn is_preview(&self) -> bool {
+        *self == Precision::F32
+    }
+
+    /// whether this precision can tell the numbers in neighbouring counters of the `frame` apart,
+    /// see `PIXEL_ULPS`
+    pub fn resolves<T: Scalar>(&self, frame:&Frame<T>) -> bool {
+
+        let magnitude = [frame.lower_bound.r, frame.lower_bound.i, frame.upper_bound.r, frame.upper_bound.i]
+            .iter()
+            .fold(0f64, |m, x| m.max(x.to_f64().abs()));
+
+        let step = frame.step_size[0].min(frame.step_size[1]);
+
+        step >= magnitude * self.epsilon() * PIXEL_ULPS
+
+    }
+
+    /// the narrowest precision, that resolves the `frame`, if there is any
+    ///
+    /// Previews aren't considered, an `f32` has to be asked for explicitly.
+    pub fn for_frame<T: Scalar>(frame:&Frame<T>) -> Option<Precision> {
+        Precision::AUTO.iter().find(|p| p.resolves(frame)).cloned()
+    }
+
+}
+
+
 /* The Metropolis-Hastings Orbit Collection Type */
 
+thread_local! {
+    static SEED: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// makes the `MHOrbits` created on this thread from now on draw their samples from the random
+/// stream `seed`, or from entropy again with `None`
+///
+/// A distributed render hands out its work with the number of a stream, so every piece of work is
+/// drawn from a different one, see `distributed`.
+pub fn seed_thread(seed:Option<u64>) {
+    SEED.with(|s| s.set(seed))
+}
+
+/// the number of the random stream `n` of the stream `seed`, like the one of a thread of a render,
+/// or of a unit of a distributed one
+///
+/// Both are run through splitmix64, so every pair gets a stream of its own. Adding or shifting
+/// them, say, would give unit 1 of seed 5 the samples of unit 0 of seed 6.
+pub fn stream(seed:u64, n:u64) -> u64 {
+
+    let mix = |z:u64| {
+        let z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        let z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+
+    mix(mix(seed) ^ n)
+
+}
+
 /// Metropolis-Hastings Orbits Iterator.
 /// This iterator yields Buddahbrot `Orbit`s for a specified number of samples, where a sample is a
 /// complex number. The samples are chosen using an adapted Metropolis-Hastings method, hence the
@@ -132,25 +1184,33 @@ impl Iterator for Orbit {
 ///
 /// Note that due to implementation and the functionality of Metropolis-Hastings running through
 /// MHOrbits requires computing possibly quite a bit of `Orbit`s, that are ultimately discarded...
-pub struct MHOrbits {
+pub struct MHOrbits<T = f64> {
 
-    sample_count: i32,
-    sample: Complex,    // the previous sample
-    length: i32,        // length of the previous sample
+    sample_count: u64,
+    sample: Complex<T>, // the previous sample
+    length: u64,        // length of the previous sample
 
-    iterations: i32,    // how long to make each singular orbit at max
+    iterations: u64,    // how long to make each singular orbit at max
+    whole: bool,        // whether to yield entire orbits, or only the numbers inside the frame
+    generator: OrbitGenerator<T>,   // whether the samples are c or z0, and how to iterate them
+    projection: Projection,
 
     // the rectangle of the complex plane, we wish to explore
-    lower_bound: Complex,
-    upper_bound: Complex,
-    step_size: [f64; 2],
+    frame: Frame<T>,
+
+    // random samples, that are known to escape, see rnd_sample()
+    restarts: Vec<Complex<T>>,
+
+    // samples proposed from the current one, that are known to escape, and where, see propose()
+    pending: Vec<(Complex<T>, usize)>,
 
-    rng: ThreadRng,
+    // seedable, unlike the ThreadRng it used to be, see seed_thread()
+    rng: SmallRng,
 
 }
 
 
-impl MHOrbits {
+impl<T: Scalar> MHOrbits<T> {
 
     /// Creates a new MHOrbits Iterator
     ///
@@ -162,39 +1222,58 @@ impl MHOrbits {
     ///
     /// `iterations` is the number of iterations, each orbit will test for
     ///
-    /// `corner_1` is the complex number of one of the corners of the rectangular segment of
-    /// Buddahbrot, we'd like to explore
+    /// `frame` is the rectangular segment of Buddahbrot, we'd like to explore
+    ///
+    /// `whole` makes the iterator yield the entire `Orbit` up to and including its first escaped
+    /// number, instead of only the numbers inside the frame. Consecutive numbers of the yielded
+    /// `Vec` are then consecutive numbers of the `Orbit`, which is what drawing trails needs.
+    ///
+    /// `generator` decides, whether the samples are the `c`s or the `z0`s of the `Orbit`s, see
+    /// `Fractal`, and whether their `Orbit`s are computed directly or by perturbation, see
+    /// `OrbitGenerator`.
+    ///
+    /// `projection` maps the numbers of the `Orbit`s onto the plane, the frame is on. All the
+    /// yielded numbers are projected, see `Projection`.
     ///
-    /// `corner_2 is the complex nuymber of the corner diagonally opposite of `corner_1`
+    /// All the numbers are of the `Scalar` type `T`, which decides how deep the frame may be
+    /// zoomed in, see `Precision`.
     ///
     /// **Note:** The Orbits this iterator yields, will be *computed*, that is, they aren't actually
     /// `Orbit`-type Iterators, but the results of such, collected into `Vec<Complex>`-type
     /// Vectors!
-    pub fn new(sample_count:i32, warmup:i32, iterations:i32, step_size:[f64;2], lower_bound: Complex, upper_bound: Complex) -> MHOrbits {
+    pub fn new(sample_count:u64, warmup:u64, iterations:u64, frame: Frame<T>, whole: bool, generator: OrbitGenerator<T>, projection: Projection) -> MHOrbits<T> {
 
         /* Create a new MHOrbits */
 
-        // Create a random number generator for choosing samples
-        let mut rng = rand::thread_rng();
+        // Create a random number generator for choosing samples. Every iterator gets its own, so
+        // a small and fast one does. Unless the thread is seeded, see seed_thread(), it's random.
+        let mut rng = SEED.with(|s| s.get()).map(SmallRng::seed_from_u64).unwrap_or_else(SmallRng::from_entropy);
 
         // Create orbit, filter it for 'interesting' numbers, and figure out its length (the actual numbers don't matter, cause warm-up)
-        let sample: Complex = MHOrbits::rnd_sample(&mut rng);
-        let length = Orbit::new(sample, iterations)
-            .filter(|c| MHOrbits::in_range(&c, &lower_bound, &upper_bound))
-            .collect::<Vec<Complex>>()              // TURBOOOO FIIIIIISH, YAY =)
-            .len() as i32;
+        let fractal = *generator.fractal();
+        let mut restarts = Vec::with_capacity(LANES);
+        let sample: Complex<T> = MHOrbits::rnd_sample(&mut rng, &fractal, &mut restarts);
+        let length = fractal.orbit(sample, iterations)
+            .map(|z| projection.project(&z, &fractal.c(&sample)))
+            .filter(|c| frame.contains(c))
+            .collect::<Vec<Complex<T>>>()           // TURBOOOO FIIIIIISH, YAY =)
+            .len() as u64;
 
         let mut mho = MHOrbits {
 
-            sample_count: sample_count + warmup,
+            sample_count: sample_count.saturating_add(warmup),
 
             sample,
             length,
 
             iterations,
-            lower_bound,
-            upper_bound,
-            step_size,
+            whole,
+            generator,
+            projection,
+            frame,
+
+            restarts,
+            pending: Vec::with_capacity(LANES),
 
             rng,
 
@@ -208,10 +1287,11 @@ impl MHOrbits {
         // started sampling from a low-interest position and need to make your way to a
         // high-interest position first...
 
-        // nth() **consumes** the first (n-1) elements and yields the nth, which we can promptly ignore!
-        mho.nth(warmup as usize);
+        // nth(n) **consumes** the first n elements and yields the (n+1)th, so warmup - 1 it is,
+        // otherwise one sample too many would be thrown away
+        if warmup > 0 { mho.nth(warmup as usize - 1); }
 
-        println!("Got through Warmup");
+        note!("Got through Warmup");
 
 
         /* Yield the Warmed-Up Iterator */
@@ -221,28 +1301,15 @@ impl MHOrbits {
     }
 
 
-    /// Whether a complex number is in the range, we want to explore, or not
-    #[inline]
-    fn in_range(c:&Complex, lower_bound:&Complex, upper_bound:&Complex) -> bool {
-
-        if lower_bound.r <= c.r && c.r < upper_bound.r
-        && lower_bound.i <= c.i && c.i < upper_bound.i {
-            return true
-        }
-
-        false
-
-    }
-
     /// Whether or not to discard the current Metropolis-Hastings sample
     /// This is a front-end to the TransitionProbability function from the reference
     #[inline]
-    fn discard(rng:&mut ThreadRng, iterations:i32, len1:i32, len2:i32) -> bool {
+    fn discard(rng:&mut SmallRng, iterations:u64, len1:u64, len2:u64) -> bool {
 
-        let t_prob_1  = MHOrbits::transition_probability(iterations, len1, len2);
-        let t_prob_2  = MHOrbits::transition_probability(iterations, len2, len1);
-        let contrib_1 = MHOrbits::contribution(iterations, len1);
-        let contrib_2 = MHOrbits::contribution(iterations, len2);
+        let t_prob_1  = Self::transition_probability(iterations, len1, len2);
+        let t_prob_2  = Self::transition_probability(iterations, len2, len1);
+        let contrib_1 = Self::contribution(iterations, len1);
+        let contrib_2 = Self::contribution(iterations, len2);
 
         let a = (contrib_1 * t_prob_1).log10();
         let b = (contrib_2 * t_prob_2).log10();
@@ -257,48 +1324,62 @@ impl MHOrbits {
 
     /// How strongly a particular `Orbit` contributes, as a percentage from its maximum length
     #[inline]
-    fn contribution(iterations:i32, len:i32) -> f64 {
+    fn contribution(iterations:u64, len:u64) -> f64 {
 
         len as f64 / iterations as f64
 
     }
 
     #[inline]
-    fn transition_probability(iterations:i32, len1:i32, len2:i32) -> f64 {
+    fn transition_probability(iterations:u64, len1:u64, len2:u64) -> f64 {
 
-        (1f64 - (iterations - len1) as f64 / iterations as f64) /
-        (1f64 - (iterations - len2) as f64 / iterations as f64)
+        // In f64, cause the differences may well be negative
+        let iterations = iterations as f64;
+
+        (1f64 - (iterations - len1 as f64) / iterations) /
+        (1f64 - (iterations - len2 as f64) / iterations)
 
     }
 
-    /// Chooses a random complex number not in the Mandelbrot set, but somewhere in its vicinity
+    /// Chooses a random complex number not in the Mandelbrot set (or Julia set, depending on the
+    /// `fractal`), but somewhere in its vicinity
+    ///
+    /// The uniformly random candidates are tested `LANES` at a time with `escape_batch()`, which
+    /// also stops on the ones, that escape early, instead of iterating them all the way. All the
+    /// ones, that pass, are equally good random samples, so the spare ones are kept in `restarts`
+    /// for next time.
     #[inline]
-    fn rnd_sample(rng:&mut ThreadRng) -> Complex {
-        loop {
+    fn rnd_sample(rng:&mut SmallRng, fractal:&Fractal<T>, restarts:&mut Vec<Complex<T>>) -> Complex<T> {
 
-            let real = rng.gen_range(-2f64, 2f64);
-            let imag = rng.gen_range(-2f64, 2f64);
-            let c = Complex::new(real, imag);
+        while restarts.is_empty() {
 
-            // Figure out, if c is inside the mandelbrot set:
-            let o = Orbit::new(c, 400);
-            if o.last().unwrap().abs() < 2f64 { continue }
-            else { return c }
+            let candidates: Vec<Complex<T>> = (0..LANES).map(|_| {
+                let real = rng.gen_range(-2f64, 2f64);
+                let imag = rng.gen_range(-2f64, 2f64);
+                Complex::new(T::from_f64(real), T::from_f64(imag))
+            }).collect();
+
+            // Figure out, which ones are outside the mandelbrot set:
+            let escapes = escape_batch(fractal, &candidates, 400);
+
+            restarts.extend(candidates.iter().zip(escapes.iter()).filter(|(_, e)| e.is_some()).map(|(c, _)| *c));
 
         }
 
+        restarts.pop().unwrap()
+
     }
 
     /// Creates a random complex number not in the Mandelbrot set, by randomly offseting the
     /// complex number `c`
     #[inline]
-    fn sample_from(rng:&mut ThreadRng, step_size:[f64;2], c:&Complex) -> Complex {
+    fn sample_from(rng:&mut SmallRng, fractal:&Fractal<T>, restarts:&mut Vec<Complex<T>>, step_size:[f64;2], c:&Complex<T>) -> Complex<T> {
 
         // Randomness every now and so often!
         // NOTE gen_range(low,high) with inclusive low and exclusive high
         if rng.gen_range(0,6) > 4 {
 
-            return MHOrbits::rnd_sample(rng);
+            return MHOrbits::rnd_sample(rng, fractal, restarts);
 
         }
 
@@ -319,33 +1400,50 @@ impl MHOrbits {
 
 
         // Create the new Complex number
-        let real = c.r + r_real * phi.cos();
-        let imag = c.i + r_imag * phi.sin();
+        let real = c.r + T::from_f64(r_real * phi.cos());
+        let imag = c.i + T::from_f64(r_imag * phi.sin());
 
         Complex::new(real, imag)
 
     }
 
+    /// Proposes the next `LANES` samples from the current one, and keeps the ones, that escape
+    ///
+    /// Most of the samples close to the border of the set are inside of it, and those are rejected
+    /// anyway, so they're tested `LANES` at a time with `escape_batch()`, instead of computing
+    /// their whole `Orbit`s one by one. All of them are proposed from the same sample, so they
+    /// are only good until one of them is accepted, see `next()`.
+    fn propose(&mut self) {
+
+        let fractal = *self.generator.fractal();
+
+        let candidates: Vec<Complex<T>> = (0..LANES)
+            .map(|_| MHOrbits::sample_from(&mut self.rng, &fractal, &mut self.restarts, self.frame.step_size, &self.sample))
+            .collect();
+
+        let escapes = self.generator.escape_batch(&candidates, self.iterations);
+
+        // In reverse, so pop() hands them out in the order they were proposed in
+        self.pending.extend(candidates.iter().zip(escapes.iter()).rev().filter_map(|(s, e)| e.map(|k| (*s, k))));
+
+    }
+
     /// Tells you how many samples are still left from this `MHOrbits`
-    pub fn remaining(&self) -> i32 {
+    pub fn remaining(&self) -> u64 {
         self.sample_count
     }
 
-    /// Tells you whether this `MHOrbits` has finished its computation, without you needing to
-    /// create a peekable Iterator or -- god fobid -- pattern match against `next()`
-    pub fn finished(&self) -> bool {
-        if self.sample_count > 0 {
-            return false;
-        } else {
-            return true;
-        }
+    /// Gives this `MHOrbits` `n` more samples to yield, so one warmed up iterator can carry on
+    /// computing, whenever a thread gets another chunk of samples to do
+    pub fn add_samples(&mut self, n:u64) {
+        self.sample_count = self.sample_count.saturating_add(n);
     }
 
 }
 
-impl Iterator for MHOrbits {
+impl<T: Scalar> Iterator for MHOrbits<T> {
 
-    type Item = Vec<Complex>;
+    type Item = Vec<Complex<T>>;
 
     /// returns a `Vec<Complex>`, which contains the numbers of the next successfull `Orbit`
     fn next(&mut self) -> Option<Self::Item> {
@@ -356,30 +1454,46 @@ impl Iterator for MHOrbits {
 
             loop {
 
-                /* Compute a new sample and orbit */
+                /* Take a new sample, that isn't in the Mandelbrot (or Julia) set, and compute its orbit */
+
+                let (s, escape) = match self.pending.pop() {
+                    Some(p) => p,
+                    None    => { self.propose(); continue },
+                };
+
+                let c = self.generator.fractal().c(&s);
+
+                let projection = self.projection;
+
+                let (o, l) = if self.whole {
 
-                let s = MHOrbits::sample_from(&mut self.rng, self.step_size, &self.sample);
+                    // Only up to the first escaped number, everything after that is just noise on its way to infinity
+                    let orbit = self.generator.orbit(s, escape as u64 + 1);
 
-                let mut o = Orbit::new(s, self.iterations)
-                    .enumerate()
-                    .filter(|(i,c)| {
-                        if i+1 == self.iterations as usize { return true }          // Keep the very last number of the orbit, whether it's in_range or not
-                        MHOrbits::in_range(c, &self.lower_bound, &self.upper_bound)
-                    })
-                    .map(|(_,c)| c)
-                    .collect::<Vec<Complex>>();
+                    let o = orbit.iter().map(|z| projection.project(z, &c)).collect::<Vec<Complex<T>>>();
+                    let l = o.iter().filter(|c| self.frame.contains(c)).count() as u64;
 
-                // Check the orbit's last number: was the sample in the Mandelbrot set, or not?
-                let last = o[o.len()-1];
-                if last.abs() < 2f64 { continue }
-                else if !MHOrbits::in_range(&last, &self.lower_bound, &self.upper_bound) { o.pop(); }
+                    (o, l)
 
-                let l = o.len() as i32;
+                } else {
+
+                    let orbit = self.generator.orbit(s, self.iterations);
+
+                    let o = orbit.iter()
+                        .map(|z| projection.project(z, &c))
+                        .filter(|c| self.frame.contains(c))
+                        .collect::<Vec<Complex<T>>>();
+
+                    let l = o.len() as u64;
+
+                    (o, l)
+
+                };
 
 
                 /* Maybe discard it? */
 
-                if MHOrbits::discard(&mut self.rng, self.iterations, l, self.length) {
+                if Self::discard(&mut self.rng, self.iterations, l, self.length) {
                     // println!("discarded");
                     continue;
                 } else {
@@ -387,6 +1501,9 @@ impl Iterator for MHOrbits {
                     self.sample = s;
                     self.length = l;
 
+                    // The pending samples were proposed from the previous sample
+                    self.pending.clear();
+
                     return Some(o);
                 }
 
@@ -1,8 +1,13 @@
-//! This module re-exports the `io`, `maths` and `butterbrot` modules.
+//! This module re-exports the `io`, `maths`, `counters`, `tiles`, `estimate`, `distributed`, `cli`
+//! and `config` modules, and the modules of the commands of `birb`: `butterbrot` (`birb render`),
+//! `birb2bmp` (`birb convert`), `birb_combinator` (`birb combine`), `birb2term` (`birb view`),
+//! `info` (`birb info`) and `queue` (`birb queue`), which the old binaries of the same names simply
+//! call.
 //!
-//! This module hosts the `butterbrot_run()` function, which computes a Buddhabrot render.  
+//! This module hosts the `butterbrot_run()` function, which computes a Buddhabrot render, and the
+//! `escape_time_run()` function, which computes a classic escape-time image of the same frame.  
 //! This module also contains and exports the `error!()` macro, which calls `.unwrap_or_else()` on
-//! a `Result` and kills the program with a custom `panic!` should the `Err(e)` variant come to
+//! a `Result` and stops the program with `log::fail()` should the `Err(e)` variant come to
 //! pass. This macro is used in all sorts of places mostly for IO related operations.  
 //! This module also contains several functions related to and including the `logging()` function,
 //! which generates the CLI output, that tells the user information about the status of the
@@ -21,8 +26,8 @@ const BLUE   :&str = "\x1B[34m";
 
 /// Lil' helper with the errors
 ///
-/// This Macro does an orderly `panic!`, if it is provided with an Error variant of a Result or
-/// yields the Ok value of the Result, by calling `unwrap_or_else()`.
+/// This Macro stops with an error through `log::fail()`, if it is provided with an Error variant of
+/// a Result or yields the Ok value of the Result, by calling `unwrap_or_else()`.
 ///
 /// Use `error!( Err(42), "Oh no, It gone bad!" )` to output something like:
 /// <pre><code><b style="color:red">Error:</b> Oh no, It gone bad!
@@ -36,198 +41,322 @@ const BLUE   :&str = "\x1B[34m";
 macro_rules! error {
 
     ( $result:expr, $msg:expr, full ) => {
-
-        { // I wish to run multiple statements, so I need a code block. Last statement will be return value
-
-            $result.unwrap_or_else(|e| {
-                // For some reason the adapted panic! used in the next rule doesn't work with format!,
-                // so just clear the panic from any messaging
-                std::panic::set_hook(Box::new(|_| { }));
-
-                // Print custom error msg
-                eprintln!("\x1B[31;1mError:\x1B[0m {}", $msg);
-                eprintln!("\tSystem Error: {:?}", e);
-
-                // Panic without msg
-                panic!("");
-
-            })
-
-        }
+        $result.unwrap_or_else(|e| $crate::log::fail(format!("{}\n\tSystem Error: {:?}", $msg, e)))
     };
 
     ( $result:expr, $msg:expr ) => {
+        $result.unwrap_or_else(|_| $crate::log::fail($msg))
+    };
 
-        {
-            // Set custom panic message
-            std::panic::set_hook(Box::new(|panic_info| {
+}
 
-                // Print red 'Error: ' followed by panic payload
-                eprintln!("\x1B[31;1mError:\x1B[0m {}", panic_info.payload().downcast_ref::<&str>().unwrap());
+/// prints a note about what's going on to stderr, like `eprintln!()`, unless the render is
+/// `--quiet`, see the `log` module
+#[macro_export]
+macro_rules! note {
+    ( $($arg:tt)* ) => {
+        if !$crate::log::quiet() {
+            eprintln!("{}", $crate::log::paint(&format!($($arg)*), $crate::log::Stream::Err))
+        }
+    };
+}
 
-            }));
+/// prints a warning to stderr, with a yellow `Warning:` in front, even if the render is `--quiet`
+#[macro_export]
+macro_rules! warning {
+    ( $($arg:tt)* ) => {
+        eprintln!("{}", $crate::log::paint(&format!("\x1B[33;1mWarning:\x1B[0m {}", format!($($arg)*)), $crate::log::Stream::Err))
+    };
+}
 
-            // Panic.
-            $result.unwrap_or_else(|_| {
-                panic!($msg);
-            })
+/// `println!()`, with the colours taken out, unless stdout is a terminal
+#[macro_export]
+macro_rules! out {
+    () => { println!() };
+    ( $($arg:tt)* ) => {
+        println!("{}", $crate::log::paint(&format!($($arg)*), $crate::log::Stream::Out))
+    };
+}
 
-        }
-    }
+/// `eprintln!()`, with the colours taken out, unless stderr is a terminal, for errors
+#[macro_export]
+macro_rules! errln {
+    ( $($arg:tt)* ) => {
+        eprintln!("{}", $crate::log::paint(&format!($($arg)*), $crate::log::Stream::Err))
+    };
 }
 
+pub mod log;
 pub mod io;
 pub mod math;
+pub mod counters;
+pub mod tiles;
+pub mod estimate;
+pub mod distributed;
+pub mod cli;
+pub mod config;
 pub mod butterbrot;
+pub mod birb2bmp;
+pub mod birb_combinator;
+pub mod birb2term;
+pub mod info;
+pub mod queue;
 
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::thread;
 use std::sync::mpsc::{channel, Receiver};
 use std::time::{Instant, Duration};
+use rayon::prelude::*;
+use counters::Accumulator;
 
 
-/// Computes the Buddahbrot Set multi-threadedly
+/// What kind of render `butterbrot` computes
 ///
-/// This function looks more complicated than it is. It simply creates a number of threads,
-/// runs `MHOrbits` iterators in each of them and has the threads write their computed `Orbits` to
-/// `supreme_birb` -- the buffer from the main thread -- after a couple of orbits were computed.
+/// `Buddhabrot` is what this is all about, see `butterbrot_run()`. `EscapeTime` is the classic
+/// Mandelbrot image, see `escape_time_run()`, which is good for a quick preview of a frame, before
+/// spending hours on a Buddhabrot of it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Buddhabrot,
+    EscapeTime,
+}
+
+/// How the numbers of an `Orbit` are deposited into a birb
+///
+/// `Points` increases the counter of every number the `Orbit` visits, which is the classic
+/// Buddhabrot. At high zooms that makes for scattered dots, so `Trails` instead draws the line
+/// segments between consecutive numbers of the `Orbit` into the birb. With `weighted` set, every
+/// segment deposits the same total of `TRAIL_WEIGHT` hits, spread out evenly along its length, so
+/// long jumps fade out instead of dominating the image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Deposit {
+    Points,
+    Trails { weighted: bool },
+}
+
+impl Deposit {
+
+    /// the name of the mode, as used for `--deposit` and in the `Header`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Deposit::Points                      => "points",
+            Deposit::Trails { weighted: false }  => "trails",
+            Deposit::Trails { weighted: true }   => "weighted-trails",
+        }
+    }
+
+}
+
+/// The total number of hits a single segment deposits in `Deposit::Trails { weighted: true }`
+pub const TRAIL_WEIGHT: u64 = 256;
+
+/// The channels of a birb computed with statistics, see `butterbrot_run()`
 ///
-/// ### This function takes a boat-load of arguments:
+/// Besides the `count` of hits, every counter then also sums up
+/// - `iteration`: the iteration index, at which the numbers landed in the counter
+/// - `arg_r`, `arg_i`: the argument of the numbers as a unit vector, in fixed-point with
+///   `ARG_SCALE` as one, stored as two's complement `i64`s (so add them with `wrapping_add()`)
+/// - `length`: the total length of the `Orbit`s, the numbers belonged to
 ///
-/// `supreme_birb` is an `Arc` reference to the main function's birb
+/// Dividing any of these by the `count` gives the mean, so renderers can colour by when and in
+/// which direction `Orbit`s pass through a counter.
+pub const STATS_CHANNELS: [&str; 5] = ["count", "iteration", "arg_r", "arg_i", "length"];
+
+/// The fixed-point one of the `arg_r` and `arg_i` channels
+pub const ARG_SCALE: f64 = 65536.0;
+
+/// The channels, that hold two's complement `i64`s, which need to be summed with `wrapping_add()`
+pub const SIGNED_CHANNELS: [&str; 2] = ["arg_r", "arg_i"];
+
+/// The metadata of a birb, that describes what was rendered
 ///
-/// `timeout` is the maximum `Duration` the computation should run, **needs** to be provided,
-/// though the argument parser will set this value to u64.MAX secs, if no timeout was provided from
-/// the command line
+/// `butterbrot` records all of these in the `Header`. Two birbs only add up to a larger render of
+/// the same thing, if all of them are the same, see `--append`. The corners, the `Fractal` and the
+/// `Projection` are written with all their digits, so they compare exactly.
+pub const RENDER_KEYS: [&str; 7] = ["mode", "complex1", "complex2", "fractal", "projection", "iterations", "deposit"];
+
+/// Everything a render is asked to do, as `butterbrot::parse_args()` makes it out of the command
+/// line
 ///
-/// `thread_count` is the number of threads to use for computation
+/// For what most of it means, see the help of `birb render`.
 ///
-/// `sample_count` is the total number of `Orbits` to compute  
-/// `iterations` is the maximum length each `Orbit` should have  
-/// `warmup` is the warmup length for the `MHOrbits` iterators
+/// `complex1` is one of the boundairy points of the frame of the complex plane, that we want to
+/// observe, `complex2` is the other point, diagonally opposite. *Note,* that the corner points are
+/// converted internally into the "lower left" and "upper right" corner, i.e. the points with the
+/// smallest and largest coordinates of the four corners, respectively. They're `DoubleDouble`s,
+/// like the `fractal`, so they keep all the digits given on the command line, and are narrowed to
+/// the `precision`, when the computation starts.
 ///
-/// `width` is the width of supreme_birb  
-/// `height` is the height of supreme_birb
+/// `timeout` is the maximum `Duration` the computation should run, without one it's `u64::MAX`
+/// seconds. `threads` is the number of threads to use for computation. They all take their
+/// samples in chunks of `phase_len` from one shared `SampleBudget`, so fast threads do more of
+/// them. `phase_len` is also the number of `Orbit`s each thread computes before writing them back.
 ///
-/// `corner_1` is one of the boundairy points of the frame of the complex plane, that we want to observe  
-/// `corner_2` is the other point, diagonally opposite  
-/// *Note,* that the corner points are converted internally into the "lower left" and "upper right"
-/// corner, i.e. the points with the smallest and largest coordinates of the four corners,
-/// respectively.
+/// `deposit` decides, whether `Orbit`s are deposited as points or as trails, see `Deposit`.
+/// `stats` makes the threads also accumulate the `STATS_CHANNELS`. `projection` is the plane of
+/// the four dimensional `(z, c)` space, that is rendered, see `math::Projection`. The corners are
+/// corners on that plane.
 ///
-/// `filename` is the filename...
-pub fn butterbrot_run(
+/// `seed` is the number of the random stream to draw the samples from, see
+/// `math::seed_thread()`, or `None` for random ones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderSettings {
+
+    pub width: u64,
+    pub height: u64,
+    pub complex1: math::Complex<math::DoubleDouble>,
+    pub complex2: math::Complex<math::DoubleDouble>,
+
+    pub filename: String,
+    pub threads: i32,
+
+    pub samples: u64,
+    pub iterations: u64,
+    pub warmup: u64,
+    pub phase_len: u64,
+
+    pub timeout: Duration,
+    pub interval: Duration,
+
+    pub deposit: Deposit,
+    pub stats: bool,
+    pub fractal: math::Fractal<math::DoubleDouble>,
+    pub projection: math::Projection,
+
+    pub mode: Mode,
+    pub precision: math::Precision,
+    pub perturbation: bool,
+    pub counter_width: counters::CounterWidth,
+    pub memory: Option<u64>,
+
+    pub dry_run: bool,
+    pub force: bool,
+    pub append: Option<String>,
+    pub seed: Option<u64>,
 
-    supreme_birb:Arc<Mutex<Vec<u64>>>,
-
-    timeout: Duration,
-    logging_interval: Duration,
-
-    thread_count:i32,
+}
 
-    sample_count:i32,
-    iterations:i32,
-    warmup:i32,
-    phase_len:i32,
 
-    width: u64,
-    height: u64,
+/// Computes the Buddahbrot Set multi-threadedly
+///
+/// This function looks more complicated than it is. It simply creates a pool of threads (with
+/// rayon), runs `MHOrbits` iterators in each of them and has the threads write their computed `Orbits` to
+/// `supreme_birb` -- the buffer from the main thread -- after a couple of orbits were computed.
+///
+/// ### The arguments
+///
+/// `supreme_birb` is an `Arc` reference to the main function's birb, any `counters::Accumulator`, so
+/// they may start out narrow and widen themselves, when they fill up. With `stats` it needs to
+/// have room for the `STATS_CHANNELS`.
+///
+/// `settings` are the ones of the render, see `RenderSettings`. The `filename` is only logged.
+///
+/// `generator` decides, whether the `c`s or the `z0`s of the `Orbit`s are sampled, and whether
+/// the `Orbit`s are computed directly or by perturbation, see `math::OrbitGenerator`. All its
+/// numbers are of the `math::Scalar` type `T`, which is what the whole computation runs with, see
+/// `math::Precision`.
+///
+/// `sample_count` is the total number of `Orbits` to compute, exactly that many are computed,
+/// unless the `timeout` is reached first. That's the `samples` of the render, or the ones of a
+/// unit of a distributed render, which also comes with a `seed` of its own, see `distributed`.
+///
+/// Returns how many samples were computed, which is fewer than `sample_count`, if the `timeout`
+/// was reached first.
+pub fn butterbrot_run<T: math::Scalar, A: Accumulator + Send + 'static>(supreme_birb:Arc<Mutex<A>>, settings:&RenderSettings, generator:math::OrbitGenerator<T>, sample_count:u64, seed:Option<u64>) -> u64 {
 
-    corner_1: math::Complex,
-    corner_2: math::Complex,
+    let RenderSettings { width, height, threads: thread_count, iterations, warmup, phase_len, timeout, deposit, stats, projection, .. } = *settings;
 
-    filename: &str
+    let (corner_1, corner_2): (math::Complex<T>, math::Complex<T>) = (settings.complex1.cast(), settings.complex2.cast());
 
-    ) {
 
     /* Setup multi-threading and write_back */
 
-    let step_size: [f64;2] = [
-        (corner_1.r - corner_2.r).abs() / (width  as f64),  // stepsize in x direction
-        (corner_1.i - corner_2.i).abs() / (height as f64)   // stepsize in y direction
-        ];
-
-    // The Frame, we explore, has four corners.
-    // lower_bound is the corner with the SMALLEST coordinates
-    // upper_bound is the corner with the LARGEST coordinates
-    let (lower_bound, upper_bound) = {
-
-        let (a, b, c, d)  = {
-            match (corner_1.r < corner_2.r, corner_1.i < corner_2.i) {
-                ( true,  true) => (corner_1.r, corner_1.i, corner_2.r, corner_2.i),
-                ( true, false) => (corner_1.r, corner_2.i, corner_2.r, corner_1.i),
-                (false,  true) => (corner_2.r, corner_1.i, corner_1.r, corner_2.i),
-                (false, false) => (corner_2.r, corner_2.i, corner_1.r, corner_1.i),
-            }
-        };
-
-        (math::Complex::new(a, b), math::Complex::new(c, d))
-
-    };
+    let frame = math::Frame::new(corner_1, corner_2, width, height);
 
     let (log_snd, log_rcv) = channel();
+    let (done_snd, done_rcv) = channel();
+
+    let budget = Arc::new(SampleBudget::new(sample_count));
 
-    let thread_samples = sample_count / thread_count;
+    let pool = error!(rayon::ThreadPoolBuilder::new().num_threads(thread_count as usize).build(), "Couldn't create the threads for the computation!", full);
 
     let timestamp = Instant::now();
 
 
     /* Make the threads */
 
-    let mut handles: Vec<thread::JoinHandle<()>> = Vec::with_capacity(thread_count as usize);
-
     for thread_index in 0..thread_count {
 
         /* Setup clones of variables (cause move-closure) */
 
         let supreme = Arc::clone(&supreme_birb);
-
-        let timestamp = timestamp.clone();
-        let timeout   = timeout.clone();
+        let budget  = Arc::clone(&budget);
 
         let log_snd  = log_snd.clone();
+        let done_snd = done_snd.clone();
+
+        let generator = generator.clone();
 
 
         /* Make the thread */
 
-        let t = thread::spawn(move || {
+        pool.spawn(move || {
 
             // Itsy-bitsy bit of logging directly from here!
-            println!("Thread {r}{}{w} in WarmUp", thread_index, r=RED, w=WHITE);
+            note!("Thread {r}{}{w} in WarmUp", thread_index, r=RED, w=WHITE);
+
+            // Every thread draws from a stream of its own, see math::seed_thread()
+            math::seed_thread(seed.map(|s| math::stream(s, thread_index as u64)));
 
-            // Create necessary data structures
-            let mut orbits: Vec<Vec<math::Complex>> = Vec::with_capacity(phase_len as usize);
-            let mut mh_orbits = math::MHOrbits::new(thread_samples, warmup, iterations, step_size, lower_bound, upper_bound);
+            // Create necessary data structures, the samples come from the budget later on
+            let mut orbits: Vec<Vec<math::Complex<T>>> = Vec::with_capacity(phase_len as usize);
+            let whole = deposit != Deposit::Points || stats;
+            let mut mh_orbits = math::MHOrbits::new(0, warmup, iterations, frame, whole, generator, projection);
+            let mut done = 0u64;
+            let mut deposits = 0u64;
 
-            println!("{y}Thread {r}{}{y} now computing payload{w}", thread_index, y=YELLOW, r=RED, w=WHITE);
+            note!("{y}Thread {r}{}{y} now computing payload{w}", thread_index, y=YELLOW, r=RED, w=WHITE);
 
             let mut delta_t = timestamp.elapsed();
 
             // Compute!
-            while !mh_orbits.finished() && delta_t <= timeout {
+            while delta_t <= timeout {
 
-                /* Produce new orbits */
+                /* Produce new orbits, as many as are left in the budget, but a phase at most */
 
-                for _ in 0..phase_len {
+                let chunk = budget.take(phase_len);
+                if chunk == 0 { break }
+
+                mh_orbits.add_samples(chunk);
+
+                for _ in 0..chunk {
 
                     if let Some(o) = mh_orbits.next() { orbits.push(o) }
-                    // not breaking on None here, cause we need logging info send to the logger fn
 
                 }
 
+                done += chunk;
+
 
                 /* Write back to supreme birb */
 
                 let mut birb = error!(supreme.lock(), "Couldn't acquire Mutex lock");
 
-                orbits.iter().for_each(|o| write_back(o, &mut *birb, step_size, lower_bound, width));
+                deposits += match (deposit, stats) {
+                    (Deposit::Points, false)         => orbits.iter().map(|o| write_back(o, &mut *birb, &frame)).sum::<u64>(),
+                    (Deposit::Points, true)          => orbits.iter().map(|o| write_stats(o, &mut *birb, &frame)).sum::<u64>(),
+                    (Deposit::Trails{ weighted }, _) => orbits.iter().map(|o| write_trails(o, &mut *birb, &frame, weighted, stats)).sum::<u64>(),
+                };
 
                 orbits.clear(); // so I can reuse this on the next cycle
 
+                drop(birb);
+
 
                 /* Send logging info */
 
-                error!(log_snd.send((thread_index, mh_orbits.remaining())), "\x1B[31;1mError:\x1B[0m Sending logging data from thread {} failed. This indicates something was wrong with the main thread!");
+                error!(log_snd.send((thread_index, done, deposits, false)), "Sending logging data from a thread failed. This indicates something was wrong with the main thread!");
 
 
                 /* Check the timeout */
@@ -237,22 +366,120 @@ pub fn butterbrot_run(
             }
 
             // Itsy-bitsy output on success
-            println!("{g}Thread {} computed its payload{w}", thread_index, w=WHITE, g=GREEN);
+            note!("{g}Thread {} computed its payload{w}", thread_index, w=WHITE, g=GREEN);
 
-        });
+            error!(log_snd.send((thread_index, done, deposits, true)), "Sending logging data from a thread failed. This indicates something was wrong with the main thread!");
+            error!(done_snd.send(thread_index), "A thread couldn't report back. This indicates something was wrong with the main thread!");
 
-        handles.push(t);
+        });
 
     }
 
 
     /* Logging output */
-    let _rx = logging(logging_interval, log_rcv, width, height, thread_count, sample_count, iterations, corner_1, corner_2, filename, timestamp, timeout);
+    let _rx = logging(settings, log_rcv, sample_count, timestamp);
 
     /* Join */
-    handles.into_iter().for_each(move |h| h.join().expect("Thread didn't return properly!"));
+    // A thread, that panics, takes the whole process down with it, so every thread reports back
+    (0..thread_count).for_each(|_| { error!(done_rcv.recv(), "Thread didn't return properly!"); });
+
+    note!("{g}All threads finished.{w}", w=WHITE, g=GREEN);
+
+    // The threads finish every chunk they take, so whatever is left wasn't computed
+    sample_count - budget.left()
+
+}
+
+/// the samples, that are left to compute, shared by all the threads of `butterbrot_run()`
+///
+/// Instead of dividing the samples among the threads up front, the threads take a chunk of them at
+/// a time, whenever they're done with the previous one. Fast threads simply take more chunks,
+/// while the slow ones finish theirs, so no thread sits around idle, while there's still work
+/// left. And no sample gets lost to rounding, the chunks add up to exactly the number of samples,
+/// the budget started with.
+#[derive(Debug)]
+pub struct SampleBudget {
+    left: AtomicU64,
+}
+
+impl SampleBudget {
+
+    pub fn new(samples:u64) -> SampleBudget {
+        SampleBudget { left: AtomicU64::new(samples) }
+    }
+
+    /// takes up to `chunk` samples from the budget, and returns how many it got
+    ///
+    /// That's fewer than `chunk`, when the budget is almost used up, and 0, once it's empty.
+    pub fn take(&self, chunk:u64) -> u64 {
+
+        let taken = self.left.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| Some(left - left.min(chunk)));
+
+        // The closure always returns Some, so this is always Ok
+        let left = taken.unwrap_or_else(|left| left);
+
+        left.min(chunk)
+
+    }
+
+    /// how many samples haven't been taken yet
+    pub fn left(&self) -> u64 {
+        self.left.load(Ordering::SeqCst)
+    }
+
+}
+
+/// How many rows every thread of `escape_time_run()` gets to compute at a time
+const BAND_ROWS: u64 = 4;
+
+/// Computes a classic escape-time image of the `frame` into `birb`, in parallel
+///
+/// `birb` is any `counters::Accumulator`, with all its counters still 0. Every counter of the
+/// `count` channel of `birb` is set to the iteration index, at which the `Orbit` of the number in
+/// the middle of the counter escaped, or 0, if it didn't escape within `iterations`. For
+/// `Fractal::Mandelbrot` that number is `c`, for `Fractal::Julia` it's `z0`, so the image shows
+/// exactly, what a Buddhabrot with the same settings samples from. The `generator` decides, whether
+/// the `Orbit`s are computed directly or by perturbation.
+///
+/// The rows of the birb are independent of each other, so rayon gets to compute them in parallel,
+/// a band of `BAND_ROWS` rows per thread at a time, which are then added to `birb`. Within a row,
+/// `math::LANES` counters at a time are computed in lockstep, see `math::escape_batch()`.
+pub fn escape_time_run<T: math::Scalar, A: Accumulator>(birb:&mut A, iterations:u64, frame:&math::Frame<T>, generator:&math::OrbitGenerator<T>) {
+
+    let width  = birb.width();
+    let height = birb.height();
+
+    // The counters are all in birb, as wide as it has them, only a few rows per thread are in u64s
+    let band_rows = (rayon::current_num_threads() as u64 * BAND_ROWS).min(height).max(1);
+
+    let mut band = vec![0u64; (width * band_rows) as usize];
+
+    for first in (0..height).step_by(band_rows as usize) {
+
+        let rows = band_rows.min(height - first);
+
+        band[..(width * rows) as usize]
+            .par_chunks_mut(width as usize)
+            .enumerate()
+            .for_each(|(row, counters)| {
 
-    println!("{g}All threads finished.{w}", w=WHITE, g=GREEN);
+                let samples: Vec<math::Complex<T>> = (0..counters.len())
+                    .map(|column| frame.center_of(column as u64, first + row as u64))
+                    .collect();
+
+                let escapes = generator.escape_batch(&samples, iterations);
+
+                counters.iter_mut().zip(escapes.iter()).for_each(|(counter, escape)| {
+                    *counter = escape.map(|k| k as u64 + 1).unwrap_or(0);
+                });
+
+            });
+
+        for (i, &n) in band[..(width * rows) as usize].iter().enumerate() {
+            if n > 0 { birb.add((first * width) as usize + i + 2, n) }
+        }
+
+    }
 
 }
 
@@ -269,21 +496,23 @@ pub fn butterbrot_run(
 /// index.
 ///
 /// Computing the index works a little something like this:
-/// 1. offset the `Complex` number with the absolute value of the `lower_bound` of the frame, the
-///    birb represents. That makes all the `Complex` numbers have positive signs, but keeps their
-///    relational relationships the same.
+/// 1. offset the `Complex` number by the `lower_bound` of the `frame`, the birb represents. That
+///    makes all the `Complex` numbers have positive signs, but keeps their relational
+///    relationships the same. (This happens in the full precision of `T`, see `Frame::pixel()`,
+///    cause at deep zooms the offset is all that's left of the number after rounding.)
 /// 2. the `r` and `i` fields can be used to represent columns and rows from the birb,
-///    respectively. By dividing them by the `step_size`s for each direction, flooring that, and
+///    respectively. By dividing them by the frame's `step_size`s for each direction, flooring that, and
 ///    converting it to an integer, we get the number of steps, one has to take in either
 ///    direction, to get to the counter.
 /// 3. to compute the index into the `Vector` to get the correct counter, we have to multiply the
 ///    number of rows, we have to step over, with the number of columns there is, and add to that
 ///    the number of columns, we have to step over. To that we add 2, cause the first to numbers in
 ///    a birb are reserved for width and height. (This is easier to understand on a piece of paper)
-fn write_back(orbit:&Vec<math::Complex>, supreme_birb:&mut Vec<u64>, step_size: [f64; 2], lower_bound:math::Complex, width:u64) {
+///
+/// Returns the number of counters increased, the deposits, see `Rate`.
+fn write_back<T: math::Scalar, A: Accumulator>(orbit:&[math::Complex<T>], supreme_birb:&mut A, frame:&math::Frame<T>) -> u64 {
 
-    let x_step = step_size[0];
-    let y_step = step_size[1];
+    let width  = supreme_birb.width();
 
     orbit.iter().for_each(|c| {
 
@@ -291,17 +520,191 @@ fn write_back(orbit:&Vec<math::Complex>, supreme_birb:&mut Vec<u64>, step_size:
         // Floor that and turn it to u64, that yields the row and column of the complex number in
         // the image buffer.
 
-        let column = ((c.r + lower_bound.r.abs()) / x_step).floor() as u64;
-        let row    = ((c.i + lower_bound.i.abs()) / y_step).floor() as u64;
+        let (x, y) = frame.pixel(c);
+
+        let column = x.floor() as u64;
+        let row    = y.floor() as u64;
+
+        supreme_birb.add(((column + row * width) + 2) as usize, 1);
+
+    });
+
+    orbit.len() as u64
+
+}
+
+/// increases the counters of the birb `supreme_birb`, and its statistics channels
+///
+/// This is `write_back()` for birbs with the `STATS_CHANNELS`. The `orbit` must be *whole*, see
+/// `MHOrbits::new()`, cause the position of a number in the `Orbit` is its iteration index and the
+/// length of the `Orbit` is its total length. Hence also, this function has to skip the numbers
+/// outside of the frame on its own.
+///
+/// Returns the number of counters increased, like `write_back()`.
+fn write_stats<T: math::Scalar, A: Accumulator>(orbit:&[math::Complex<T>], supreme_birb:&mut A, frame:&math::Frame<T>) -> u64 {
+
+    let width  = supreme_birb.width();
+    let length = orbit.len() as u64;
+
+    let mut deposits = 0u64;
+
+    orbit.iter().enumerate()
+        .filter(|(_,c)| frame.contains(c))
+        .for_each(|(k,c)| {
+
+            let (x, y) = frame.pixel(c);
+
+            let column = x.floor() as u64;
+            let row    = y.floor() as u64;
+
+            let index = ((column + row * width) + 2) as usize;
 
-        supreme_birb[ ((column + row * width) + 2) as usize ] += 1;
+            supreme_birb.add(index, 1);
+            deposit_stats(supreme_birb, index, 1, k as u64 + 1, &c.cast(), length);
+
+            deposits += 1;
+
+        });
+
+    deposits
+
+}
+
+/// adds `hits` hits of `z`, the `k`-th number of an `Orbit` of total `length`, to the
+/// `STATS_CHANNELS` of the counter at `index`
+///
+/// `index` is the index of the counter in the `count` channel, the other channels follow it at
+/// distances of `width * height`. The `count` itself is left alone.
+#[inline]
+fn deposit_stats<A: Accumulator>(supreme_birb:&mut A, index:usize, hits:u64, k:u64, z:&math::Complex, length:u64) {
+
+    let n = (supreme_birb.width() * supreme_birb.height()) as usize;
+
+    // arg(z) as a unit vector, arg(0) is 0
+    let abs = z.abs();
+    let (cos, sin) = if abs > 0f64 { (z.r / abs, z.i / abs) } else { (1f64, 0f64) };
+
+    let arg_r = ((cos * ARG_SCALE).round() as i64 * hits as i64) as u64;
+    let arg_i = ((sin * ARG_SCALE).round() as i64 * hits as i64) as u64;
+
+    supreme_birb.add(index +     n, hits * k);
+    supreme_birb.wrapping_add(index + 2 * n, arg_r);
+    supreme_birb.wrapping_add(index + 3 * n, arg_i);
+    supreme_birb.add(index + 4 * n, hits * length);
+
+}
+
+/// increases the counters of the birb `supreme_birb` along the trail of an `Orbit`
+///
+/// This is the `Deposit::Trails` counterpart to `write_back()`. The `orbit` must be *whole*, that
+/// is consecutive numbers in it must be consecutive numbers of the actual `Orbit`, see
+/// `MHOrbits::new()`.
+///
+/// Every segment `z_k -> z_k+1` is first clipped to the frame, so segments, that cross the frame's
+/// border, still leave their trail. Then the clipped segment is mapped to pixel coordinates and
+/// rasterized by walking the grid of counters along the segment (a DDA that visits every counter
+/// the segment passes through exactly once). The counter the segment ends in is left out, cause
+/// that's where the next segment starts -- unless the segment ends in the same counter it started
+/// in, or was clipped at its end, then there's nobody else to count it.
+///
+/// If `weighted` is set, a segment over `n` counters deposits `TRAIL_WEIGHT / n` hits per counter
+/// instead of 1 (the remainder is spread over the counters, so every segment deposits exactly
+/// `TRAIL_WEIGHT`).
+///
+/// If `stats` is set, the `STATS_CHANNELS` are accumulated as well, every counter along a segment
+/// `z_k -> z_k+1` gets the statistics of `z_k`.
+///
+/// Returns the number of counters increased, like `write_back()`, a counter, that two segments
+/// pass through, counts twice. The hits of `weighted` don't matter for that.
+fn write_trails<T: math::Scalar, A: Accumulator>(orbit:&[math::Complex<T>], supreme_birb:&mut A, frame:&math::Frame<T>, weighted:bool, stats:bool) -> u64 {
+
+    let width  = supreme_birb.width();
+    let height = supreme_birb.height();
+
+    // The counter pixel coordinates land in. Clamped, so numbers right on the upper edges don't
+    // fall out of the birb.
+    let cell  = |x:f64, y:f64| ((x.floor() as i64).min(width as i64 - 1).max(0), (y.floor() as i64).min(height as i64 - 1).max(0));
+
+    let length = orbit.len() as u64;
+    let mut deposits = 0u64;
+
+    orbit.windows(2).enumerate().for_each(|(n, segment)| {
+
+        let (a, b) = match math::clip_segment(&segment[0], &segment[1], frame) {
+            Some(s) => s,
+            None    => return,
+        };
+
+        let (x0, y0) = frame.pixel(&a);
+        let (x1, y1) = frame.pixel(&b);
+
+        let (mut column, mut row) = cell(x0, y0);
+        let (end_column, end_row) = cell(x1, y1);
+
+
+        /* Figure out, how many counters to visit */
+
+        let steps = (end_column - column).abs() + (end_row - row).abs();
+
+        let clipped_end = b.r != segment[1].r || b.i != segment[1].i;
+        let counters    = (if steps == 0 || clipped_end { steps + 1 } else { steps }) as u64;
+
+        deposits += counters;
+
+
+        /* Set up the walk along the grid */
+
+        let dx = x1 - x0;
+        let dy = y1 - y0;
+
+        let step_column = if dx > 0f64 { 1 } else { -1 };
+        let step_row    = if dy > 0f64 { 1 } else { -1 };
+
+        // How far along the segment (as a fraction of it) the next vertical and horizontal grid
+        // lines are, and how far apart they are
+        let delta_x = if dx != 0f64 { 1f64 / dx.abs() } else { f64::INFINITY };
+        let delta_y = if dy != 0f64 { 1f64 / dy.abs() } else { f64::INFINITY };
+
+        let mut next_x = if dx > 0f64 { (x0.floor() + 1f64 - x0) * delta_x } else { (x0 - x0.floor()) * delta_x };
+        let mut next_y = if dy > 0f64 { (y0.floor() + 1f64 - y0) * delta_y } else { (y0 - y0.floor()) * delta_y };
+
+
+        /* Walk */
+
+        for k in 0..counters {
+
+            let hits = if weighted {
+                // Spread TRAIL_WEIGHT evenly over the counters, without losing the remainder
+                (k + 1) * TRAIL_WEIGHT / counters - k * TRAIL_WEIGHT / counters
+            } else {
+                1
+            };
+
+            let index = ((column as u64 + row as u64 * width) + 2) as usize;
+
+            supreme_birb.add(index, hits);
+            if stats { deposit_stats(supreme_birb, index, hits, n as u64 + 1, &segment[0].cast(), length) }
+
+            // Step into the neighbouring counter, whose grid line comes first. The step count is
+            // what counts, so rounding trouble can't make us miss the end.
+            if column == end_column || (row != end_row && next_y < next_x) {
+                row    += step_row;
+                next_y += delta_y;
+            } else {
+                column += step_column;
+                next_x += delta_x;
+            }
+
+        }
 
     });
 
+    deposits
+
 }
 
 /// generates a String with the *unchanging* part of the logging output
-fn static_msg(width:u64, height:u64, iterations:i32, sample_count:i32, c1:math::Complex, c2:math::Complex, filename:&str) -> String {
+fn static_msg(width:u64, height:u64, iterations:u64, sample_count:u64, c1:math::Complex, c2:math::Complex, filename:&str) -> String {
 
 
     let w = format!("width: {y}{0}{w}", width,           w=WHITE, y=YELLOW);
@@ -332,44 +735,226 @@ fn static_msg(width:u64, height:u64, iterations:i32, sample_count:i32, c1:math::
 
 /// generates a String with the dynamic thread logging output
 ///
-/// `total` should be the total number of samples *per thread*, not for the entire program run.  
-/// `data` is the tuple, that comes back from a thread via the mpsc-channel.
-fn thread_msg(data:(i32, i32), total:i32) -> String {
+/// `total` should be the total number of samples for the entire program run, as the threads share
+/// them, see `SampleBudget`.  
+/// `data` is the tuple, that comes back from a thread via the mpsc-channel, `rate` is how fast
+/// that thread has been lately.
+fn thread_msg(data:(i32, u64, u64, bool), total:u64, rate:&Rate) -> String {
 
-    format!("thread {r}{} {w}{{ done: {y}{1:>8}{w}, left: {y}{2:>8}{w}, percent: {y}{3:>6}% {w}done }}", data.0, total - data.1, data.1, ((total - data.1) as f32 / total as f32) * 100f32, w=WHITE, y=YELLOW, r=RED)
+    format!("thread {r}{} {w}{{ done: {y}{1:>8}{w}, share: {y}{2:>6.2}% {w}of all samples, {y}{3:>9.0}{w} samples/s, {y}{4:>10.0}{w} deposits/s{5} }}", data.0, data.1, percent(data.1, total), rate.samples, rate.deposits, if data.3 { ", finished" } else { "" }, w=WHITE, y=YELLOW, r=RED)
 
 }
 
 /// generates a String with the dynamic computation wide logging output
 ///
 /// `done` should be the sum number of how many samples all thread have computed so far.  
-/// `total` should be the total number of samples all threads should compute together
-fn status_msg(done:i32, total:i32, timestamp:Instant, timeout:Duration) -> String {
+/// `total` should be the total number of samples all threads should compute together, `rate` is
+/// how fast all of them together have been lately.
+///
+/// There's a line about the timeout, only if there is one.
+fn status_msg(done:u64, total:u64, timestamp:Instant, timeout:Duration, rate:&Rate) -> String {
 
     let a  = "samples done / total:   ";
     let b  = "percentage done:        ";
     let c  = "time elapsed:           ";
-    let d  = "left / maximum runtime: ";
+    let d  = "samples / deposits:     ";
+    let e  = "estimated time left:    ";
+    let f  = "timeout:                ";
+
+    let elapsed  = timestamp.elapsed().as_secs_f64();
+    let estimate = Progress::new(done, total, elapsed, timeout, rate);
 
-    let ts = timestamp.elapsed().as_secs();
-    let to = timeout.as_secs();
+    let eta = match estimate.eta {
+        Some(eta) if estimate.timeout_left.map(|t| eta > t).unwrap_or(false) => format!("{y}{}{w}, but the timeout comes first", estimate::human_duration(eta), w=WHITE, y=YELLOW),
+        Some(eta) => format!("{y}{}{w}", estimate::human_duration(eta), w=WHITE, y=YELLOW),
+        None      => "unknown, yet".to_string(),
+    };
+
+    let timeout_line = match (estimate.timeout_left, estimate.projected) {
+        (Some(left), Some(projected)) => format!("{}{y}{}{w} left, about {y}{:.0}{w} samples ({y}{:.2}%{w}) done by then\n",
+                                                 f, estimate::human_duration(left), projected, percent(projected as u64, total), w=WHITE, y=YELLOW),
+        _ => String::new(),
+    };
 
-    format!("{}{y}{} {w}/{y} {}{w}\n{}{y}{}%{w}\n{}{y}{}s{w}\n{}{y}{}s {w}/{y} {}s{w}\n",
+    format!("{}{y}{} {w}/{y} {}{w}\n{}{y}{:.2}%{w}\n{}{y}{}{w}\n{}{y}{:.0}/s {w}/{y} {:.0}/s{w}\n{}{}\n{}",
             a, done, total,
-            b, (done as f32 / total as f32) * 100f32,
-            c, ts,
-            d, to - ts, to,
+            b, percent(done, total),
+            c, estimate::human_duration(elapsed),
+            d, rate.samples, rate.deposits,
+            e, eta,
+            timeout_line,
             w=WHITE, y=YELLOW)
 
 }
 
+/// the fields of a `progress` event of the JSON log, see `log::event()`, with the same as the
+/// `thread_msg()`s and the `status_msg()` of the text log
+///
+/// `msg` are the tuples, that came back from the threads, see `logging()`, `done` is their sum.
+/// The rates are the smoothed samples and deposits per second of `throughput`, the ETA is how many
+/// seconds are left at that rate, `null` until there's a rate. The timeout, the time left until it
+/// and the samples projected to be done by then are `null`, if there's no timeout.
+fn status_json(msg:&[Option<(i32, u64, u64, bool)>], done:u64, total:u64, timestamp:Instant, timeout:Duration, throughput:&Throughput) -> Vec<(&'static str, String)> {
+
+    let elapsed  = timestamp.elapsed().as_secs_f64();
+    let left     = total.saturating_sub(done);
+    let estimate = Progress::new(done, total, elapsed, timeout, &throughput.all);
+
+    let or_null = |x:Option<f64>| x.map(log::json_number).unwrap_or_else(|| "null".to_string());
+
+    let threads: Vec<String> = msg.iter().filter_map(|v| *v).map(|(i, d, _, f)| {
+        let rate = throughput.threads[i as usize];
+        format!("{{\"thread\":{},\"done\":{},\"share\":{},\"rate\":{},\"deposit_rate\":{},\"finished\":{}}}",
+                i, d, log::json_number(percent(d, total)), log::json_number(rate.samples), log::json_number(rate.deposits), f)
+    }).collect();
+
+    vec![
+        ("elapsed", log::json_number(elapsed)),
+        ("done", done.to_string()),
+        ("left", left.to_string()),
+        ("total", total.to_string()),
+        ("percent", log::json_number(percent(done, total))),
+        ("rate", log::json_number(throughput.all.samples)),
+        ("deposit_rate", log::json_number(throughput.all.deposits)),
+        ("eta", or_null(estimate.eta)),
+        ("timeout", if timeout.as_secs() == u64::MAX { "null".to_string() } else { timeout.as_secs().to_string() }),
+        ("timeout_left", or_null(estimate.timeout_left)),
+        ("projected", or_null(estimate.projected.map(|p| p.round()))),
+        ("threads", format!("[{}]", threads.join(","))),
+    ]
+
+}
+
+/// About how many seconds the `Rate`s are averaged over
+///
+/// The rates of the threads change, as the Metropolis-Hastings walk finds its way around, and a
+/// thread, that waits for the lock on the birb, doesn't compute. Averaged over this long, the ETA
+/// doesn't jump around every interval, but still follows, when the render gets slower or faster.
+const RATE_WINDOW: f64 = 30f64;
+
+/// how many samples and deposits per second a thread, or all of them together, computed lately
+///
+/// A deposit is an increase of a counter of the birb, see `write_back()`. The rates are an
+/// exponential moving average, each new measurement weighs in by how long it took, compared to the
+/// `RATE_WINDOW`, so it doesn't matter how often they're `update()`d. The first measurement counts
+/// fully, it's the average since the start.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Rate {
+    samples: f64,
+    deposits: f64,
+    last: (u64, u64, f64),  // samples, deposits and seconds since the start at the last update
+    known: bool,            // whether there's been a measurement yet
+}
+
+impl Rate {
+
+    fn new() -> Rate {
+        Rate { samples: 0f64, deposits: 0f64, last: (0, 0, 0f64), known: false }
+    }
+
+    /// takes in, that `done` samples and `deposits` have been computed `at` seconds after the start
+    fn update(&mut self, done:u64, deposits:u64, at:f64) {
+
+        let dt = at - self.last.2;
+        if dt <= 0f64 { return }
+
+        let measured = (done.saturating_sub(self.last.0) as f64 / dt, deposits.saturating_sub(self.last.1) as f64 / dt);
+
+        let weight = if self.known { 1f64 - (-dt / RATE_WINDOW).exp() } else { 1f64 };
+
+        self.samples  += weight * (measured.0 - self.samples);
+        self.deposits += weight * (measured.1 - self.deposits);
+
+        self.last  = (done, deposits, at);
+        self.known = true;
+
+    }
+
+}
+
+/// the `Rate`s of every thread, and of all of them together, see `logging()`
+#[derive(Clone, Debug, PartialEq)]
+struct Throughput {
+    threads: Vec<Rate>,
+    all: Rate,
+}
+
+impl Throughput {
+
+    fn new(threads:i32) -> Throughput {
+        Throughput { threads: vec![Rate::new(); threads as usize], all: Rate::new() }
+    }
+
+    /// takes in the tuples, that came back from the threads, `at` seconds after the start
+    fn update(&mut self, msg:&[Option<(i32, u64, u64, bool)>], at:f64) {
+
+        let (mut done, mut deposits) = (0u64, 0u64);
+
+        for (i, d, p, _) in msg.iter().filter_map(|v| *v) {
+            self.threads[i as usize].update(d, p, at);
+            done     += d;
+            deposits += p;
+        }
+
+        self.all.update(done, deposits, at);
+
+    }
+
+}
+
+/// when a render will be done, at the `Rate` it's going at
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Progress {
+    eta: Option<f64>,           // seconds until all the samples are done, if there's a rate
+    timeout_left: Option<f64>,  // seconds until the timeout, if there is one
+    projected: Option<f64>,     // the samples done by the timeout, if there is one
+}
+
+impl Progress {
+
+    /// `done` of `total` samples after `elapsed` seconds, at `rate`. A `timeout` of `u64::MAX`
+    /// seconds is no timeout, see `butterbrot::parse_args()`.
+    fn new(done:u64, total:u64, elapsed:f64, timeout:Duration, rate:&Rate) -> Progress {
+
+        let left = total.saturating_sub(done) as f64;
+
+        let eta = if left == 0f64 {
+            Some(0f64)
+        } else if rate.samples > 0f64 {
+            Some(left / rate.samples)
+        } else {
+            None
+        };
+
+        let timeout_left = if timeout.as_secs() == u64::MAX { None } else { Some((timeout.as_secs_f64() - elapsed).max(0f64)) };
+
+        // The budget stops the render, if it's used up first
+        let projected = timeout_left.map(|t| (done as f64 + rate.samples * t).min(total as f64));
+
+        Progress { eta, timeout_left, projected }
+
+    }
+
+}
+
+/// how many percent `part` is of `total`, with the division done in `f64`, so it stays precise
+/// even for counts way beyond what an `i32` or `f32` can hold
+fn percent(part:u64, total:u64) -> f64 {
+
+    if total == 0 { return 100f64 }
+
+    part as f64 / total as f64 * 100f64
+
+}
+
 /// Outputs logging information about the state of the threads handled by `butterbrot_run`
 ///
 /// ### What this does
 /// This function sporadically composes and prints a log with the computation's state based on
 /// status info it gets from the computation threads via an `mpsc` channel. The computation threads
-/// send a pair with their unique index and the number of samples, their iterator has left to do.
-/// Whenever `logging()` has gotten such a pair for each of the threads, it will output a log
+/// send a tuple with their unique index, the number of samples they have computed so far, the
+/// number of deposits they've made so far, and whether they're finished.
+/// Whenever `logging()` has gotten such a tuple for each of the threads, it will output a log
 /// message. It always waits, til it has received at least one message from all threads, so the log
 /// will be complete.  
 /// If the threads are enough out of sync, that a thread sends multiple messages, while another
@@ -377,11 +962,12 @@ fn status_msg(done:i32, total:i32, timestamp:Instant, timeout:Duration) -> Strin
 ///
 /// Furthermore, `logging()` has an in-built timer, which can be set from the command line. It will
 /// only output a message, when it received data from all threads, *and* enough time since the last
-/// print has elapsed.
+/// print has elapsed. Every log message updates the `Throughput`, so the rates and the ETA of the
+/// log are those of the last `RATE_WINDOW` seconds or so.
 ///
 /// There are some special behaviours to keep in mind. `logging()` returns when all threads have
-/// finished, that is, have 0 samples left to compute.  
-/// `logging()` returns an `mpsc::Receiver<(i32,i32)>`. There's a good reason for that, if a
+/// finished, that is, found the `SampleBudget` empty.  
+/// `logging()` returns an `mpsc::Receiver<(i32,u64,u64,bool)>`. There's a good reason for that, if a
 /// custom timeout has been specified and the timeout is reached, `logging()` will almost certainly
 /// return quite a while *before* the computation threads notice, that the timeout has been
 /// reached. After all the computation threads will finish their current cycle, before re-checking,
@@ -392,64 +978,84 @@ fn status_msg(done:i32, total:i32, timestamp:Instant, timeout:Duration) -> Strin
 /// `Receiver` alive long enough, for the computation threads to `join()`.
 ///
 /// ### Parameters
-/// There's really nothing much to explain there. `timestamp` should be the `Instant` when the
-/// computation was started, so the `timestamp` variable made by `butterbrot_run` right before it
-/// starts creating threads. All the others are pretty obviously named...
-///
-fn logging(
-    interval:Duration,
-    rx:Receiver<(i32, i32)>,
-    width:u64,height:u64,
-    threads:i32,
-    sample_count:i32,
-    iterations:i32,
-    c1:math::Complex,
-    c2:math::Complex,
-    filename:&str,
-    timestamp:Instant,
-    timeout:Duration) -> Receiver<(i32,i32)> {
-
-    let mut msg:Vec<Option<(i32,i32)>> = Vec::with_capacity(threads as usize);
+/// There's really nothing much to explain there. `settings` are the ones of the render, see
+/// `RenderSettings`, `sample_count` is how many samples the threads compute, see
+/// `butterbrot_run()`. `timestamp` should be the `Instant` when the computation was started, so the
+/// `timestamp` variable made by `butterbrot_run` right before it starts creating threads.
+fn logging(settings:&RenderSettings, rx:Receiver<(i32, u64, u64, bool)>, sample_count:u64, timestamp:Instant) -> Receiver<(i32,u64,u64,bool)> {
+
+    let RenderSettings { width, height, threads, iterations, timeout, interval, .. } = *settings;
+    let (c1, c2): (math::Complex, math::Complex) = (settings.complex1.cast(), settings.complex2.cast());
+    let filename = &settings.filename;
+
+    let mut msg:Vec<Option<(i32,u64,u64,bool)>> = Vec::with_capacity(threads as usize);
     for _ in 0..threads { msg.push(None) }
 
+    let mut throughput = Throughput::new(threads);
+
     let static_message = static_msg(width, height, iterations, sample_count, c1, c2, filename);
-    let thread_samples = sample_count / threads;
+
+    // A JSON log says, what's rendered, only once
+    if log::format() == log::Format::Json {
+        let complex = |c:math::Complex| format!("[{},{}]", log::json_number(c.r), log::json_number(c.i));
+        log::event("start", &[
+            ("width", width.to_string()),
+            ("height", height.to_string()),
+            ("iterations", iterations.to_string()),
+            ("samples", sample_count.to_string()),
+            ("threads", threads.to_string()),
+            ("complex1", complex(c1)),
+            ("complex2", complex(c2)),
+            ("filename", log::json_string(filename)),
+        ]);
+    }
 
     let mut delta_t = timestamp.elapsed();
 
     while delta_t <= timeout {
 
         // Get all messages in the queue
-        while let Ok((a,b)) = rx.try_recv() {
-            msg[a as usize] = Some((a,b))
+        while let Ok((a,b,c,d)) = rx.try_recv() {
+            msg[a as usize] = Some((a,b,c,d))
         }
 
         // If there are NO Nones among the messages, we can output a new log
-        if !msg.iter().any(|&v| v == None) {
+        if !msg.contains(&None) {
+
+            let done = msg.iter().filter_map(|v| *v).fold(0, |acc, (_,v,_,_)| acc + v);
+
+            throughput.update(&msg, timestamp.elapsed().as_secs_f64());
+
+            match log::format() {
+
+                _ if log::quiet() => (),
+
+                log::Format::Text => {
 
-            println!("\n{}\n", static_message);
+                    out!("\n{}\n", static_message);
 
-            // Print thread messages and compute total number of samples remaining
-            // NOTE: these are two distinct tasks, but using just one iterator is more resource efficient
-            let left = msg.iter()
-                .filter_map(|v| *v)                                             // turn the options into just their values
-                .inspect(|p| println!("{}", thread_msg(*p, thread_samples)))    // print thread message
-                .fold(0, |acc, (_,v)| acc + v);                                 // sum up second component of the pairs
+                    // Print thread messages
+                    msg.iter().filter_map(|v| *v).for_each(|p| out!("{}", thread_msg(p, sample_count, &throughput.threads[p.0 as usize])));
 
+                    // Print status message
+                    out!("\n{}", status_msg(done, sample_count, timestamp, timeout, &throughput.all));
 
-            // Print status message
-            println!("\n{}", status_msg(sample_count - left, sample_count, timestamp, timeout));
+                },
 
-            // Reset msg to None values, if the thread hasn't reached 0 samples left
+                log::Format::Json => log::event("progress", &status_json(&msg, done, sample_count, timestamp, timeout, &throughput)),
+
+            }
+
+            // Reset msg to None values, if the thread hasn't finished yet
             msg = msg.iter()
                 .filter_map(|&v| v)
-                .map(|(i,s)| if s == 0 { Some((i,s)) } else { None })
+                .map(|(i,s,p,f)| if f { Some((i,s,p,f)) } else { None })
                 .collect();
 
 
             // Break, if there's nothing left to do
-            // After the previous statement all messages will be a Some variant, if all threads are at 0 samples remaining
-            if !msg.iter().any(|&v| v == None) {
+            // After the previous statement all messages will be a Some variant, if all threads are finished
+            if !msg.contains(&None) {
                 break
             }
 
@@ -473,24 +1079,58 @@ mod tests {
     use super::io::*;
     use super::math::*;
 
-    #[test]
-    fn read_write() {
+    /// A directory of a test's own in the temporary one, which is removed again, however the test
+    /// ends
+    struct TempDir(std::path::PathBuf);
 
-        /* Try and read the birb file */
-        let buffer: Vec<u64> = read_birb("/home/work/Documents/github/HegemonsHerald/butterbrot_rs/src/foo.birb");
+    impl TempDir {
 
-        buffer.iter().enumerate().for_each(|(i,v)| {
+        fn new() -> TempDir {
+
+            static COUNT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+            let n = COUNT.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let path = std::env::temp_dir().join(format!("butterbrot_test_{}_{}", std::process::id(), n));
+            std::fs::create_dir_all(&path).unwrap();
+
+            TempDir(path)
+
+        }
+
+        /// the path of the file `name` in it
+        fn file(&self, name:&str) -> String {
+            self.0.join(name).to_str().unwrap().to_string()
+        }
+
+    }
+
+    impl Drop for TempDir {
+
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+
+    }
+
+    #[test]
+    fn read_write() {
+
+        /* Try and read the birb file */
+        let buffer: Vec<u64> = read_birb("/home/work/Documents/github/HegemonsHerald/butterbrot_rs/src/foo.birb");
+
+        buffer.iter().enumerate().for_each(|(i,v)| {
 
             println!("{} {}", i, v);
 
         });
 
         /* Try and write the birb file */
-        write_birb("birb.birb", &buffer);
+        write_birb("birb.birb", &buffer, &Header::new(&["count"]));
 
     }
 
     #[test]
+    #[allow(clippy::vec_init_then_push)]
     fn make_example_data() {
 
         let mut buffer:Vec<u64> = Vec::new();
@@ -522,7 +1162,7 @@ mod tests {
     #[test]
     fn mh_orbits_struct() {
 
-        let mh_orbit = MHOrbits::new(5, 30, 4, [0.2f64,0.002f64], Complex::new(-1f64, -7.0), Complex::new(10.0, 10.0));
+        let mh_orbit = MHOrbits::new(5, 30, 4, Frame::new(Complex::new(-1f64, -7.0), Complex::new(10.0, 10.0), 55, 8500), false, OrbitGenerator::Direct(Fractal::mandelbrot()), Projection::buddhabrot());
 
         for i in mh_orbit {
             println!(":=========================================:");
@@ -546,4 +1186,1112 @@ mod tests {
 
     }
 
+    #[test]
+    fn clipping() {
+
+        let frame = Frame::new(Complex::new(-1.0, -1.0), Complex::new(1.0, 1.0), 2, 2);
+
+        // Entirely inside stays untouched
+        let (a, b) = clip_segment(&Complex::new(-0.5, 0.0), &Complex::new(0.5, 0.5), &frame).unwrap();
+        assert!(a.r == -0.5 && a.i == 0.0 && b.r == 0.5 && b.i == 0.5);
+
+        // Crossing the whole frame gets cut at both edges
+        let (a, b) = clip_segment(&Complex::new(-3.0, 0.0), &Complex::new(3.0, 0.0), &frame).unwrap();
+        assert!(a.r == -1.0 && b.r == 1.0);
+
+        // Entirely outside, or not finite, leaves nothing
+        assert!(clip_segment(&Complex::new(2.0, 2.0), &Complex::new(3.0, -3.0), &frame).is_none());
+        assert!(clip_segment(&Complex::new(0.0, 0.0), &Complex::new(f64::INFINITY, 0.0), &frame).is_none());
+
+    }
+
+    #[test]
+    fn trails() {
+
+        use super::{write_trails, TRAIL_WEIGHT};
+
+        let frame = Frame::new(Complex::new(0.0, 0.0), Complex::new(4.0, 4.0), 4, 4);
+
+        // A horizontal line through the second row, starting outside the frame
+        let orbit = vec![Complex::new(-2.0, 1.5), Complex::new(3.5, 1.5)];
+
+        let mut birb = vec![0u64; 4 * 4 + 2];
+        birb[0] = 4;
+        birb[1] = 4;
+        write_trails(&orbit, &mut birb, &frame, false, false);
+        assert_eq!(&birb[2 + 4..2 + 8], &[1, 1, 1, 0]);     // the end of the segment is left out
+        assert_eq!(birb[2..].iter().sum::<u64>(), 3);
+
+        let mut birb = vec![0u64; 4 * 4 + 2];
+        birb[0] = 4;
+        birb[1] = 4;
+        write_trails(&orbit, &mut birb, &frame, true, false);
+        assert_eq!(birb[2..].iter().sum::<u64>(), TRAIL_WEIGHT);
+
+    }
+
+    #[test]
+    fn birb_header() {
+
+        let directory = TempDir::new();
+        let filename = &directory.file("header.birb");
+
+        let mut header = Header::new(&super::STATS_CHANNELS);
+        header.set("iterations", "200".to_string());
+
+        // width 2, height 1 and five channels
+        let birb: Vec<u64> = vec![2, 1, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+
+        write_birb(filename, &birb, &header);
+
+        assert_eq!(read_header(filename), header);
+        assert_eq!(read_birb(filename), birb);
+        assert_eq!(read_header(filename).get("iterations"), Some("200"));
+
+    }
+
+    #[test]
+    fn stats() {
+
+        use super::{write_stats, ARG_SCALE};
+
+        let frame = Frame::new(Complex::new(0.0, 0.0), Complex::new(2.0, 1.0), 2, 1);
+
+        // The first number is outside, the second lands in counter 1, the third in counter 0
+        let orbit = vec![Complex::new(-1.0, 0.5), Complex::new(1.5, 0.5), Complex::new(0.5, 0.0)];
+
+        let mut birb = vec![0u64; 2 * 5 + 2];
+        birb[0] = 2;
+        birb[1] = 1;
+
+        write_stats(&orbit, &mut birb, &frame);
+
+        // count, iteration, arg_r, arg_i, length
+        assert_eq!(&birb[2..4],  &[1, 1]);
+        assert_eq!(&birb[4..6],  &[3, 2]);
+        assert_eq!(birb[6], ARG_SCALE as u64);
+        assert_eq!(&birb[10..12], &[3, 3]);
+
+    }
+
+    #[test]
+    fn projection() {
+
+        let z = Complex::new(1f64, 2.0);
+        let c = Complex::new(3.0, 4.0);
+
+        let p = Projection::buddhabrot().project(&z, &c);
+        assert!(p.r == 1.0 && p.i == 2.0);
+
+        let p = Projection::plane("zi-ci").unwrap().project(&z, &c);
+        assert!(p.r == 2.0 && p.i == 4.0);
+
+        assert!(Projection::plane("zr-zr").is_none());
+        assert!(Projection::plane("zr-foo").is_none());
+
+        // A quarter turn through the zr-cr plane turns zr into -cr
+        let p = Projection::buddhabrot().rotated(0, 2, std::f64::consts::FRAC_PI_2).project(&z, &c);
+        assert!((p.r + 3.0).abs() < 1e-12 && p.i == 2.0);
+
+    }
+
+    #[test]
+    fn fractal() {
+
+        let sample = Complex::new(0.5, 1.0);
+
+        // The Buddhabrot starts at 0, so its first number is the sample itself
+        assert_eq!(Fractal::mandelbrot().orbit(sample, 1).next(), Some(sample));
+
+        // Starting from z0 = 1 instead: 1 * 1 + sample
+        let z0 = Fractal::Mandelbrot { z0: Complex::new(1.0, 0.0) };
+        assert_eq!(z0.orbit(sample, 1).next(), Some(Complex::new(1.5, 1.0)));
+        assert_eq!(z0.c(&sample), sample);
+
+        // Julia samples z0: sample * sample + c
+        let julia = Fractal::Julia { c: Complex::new(-1.0, 0.0) };
+        assert_eq!(julia.orbit(sample, 1).next(), Some(Complex::new(-1.75, 1.0)));
+        assert_eq!(julia.c(&sample), Complex::new(-1.0, 0.0));
+
+    }
+
+    #[test]
+    fn escape_time() {
+
+        use super::escape_time_run;
+
+        // Three counters along the real axis around -2, -0.5 and 1
+        let mut birb = vec![3, 1, 0, 0, 0];
+        let frame = Frame::new(Complex::new(-2.75, -0.25), Complex::new(1.75, 0.25), 3, 1);
+
+        escape_time_run(&mut birb, 50, &frame, &OrbitGenerator::Direct(Fractal::mandelbrot()));
+
+        // -2 sits right on the edge, -0.5 is inside the Mandelbrot set, 1 escapes at its second number: 1, 2
+        assert_eq!(&birb[2..], &[1, 0, 2]);
+
+    }
+
+    #[test]
+    fn double_double() {
+
+        let one = DoubleDouble::new(1.0);
+        let tiny = DoubleDouble::new(1e-20);
+
+        // An f64 would forget about the tiny bit
+        assert_eq!(((one + tiny) - one).to_f64(), 1e-20);
+
+        // Parsing keeps the digits beyond an f64, 0.1 is a third of 0.3 down to the last bit
+        let third = "0.1".parse::<DoubleDouble>().unwrap() * DoubleDouble::new(3.0) - "0.3".parse::<DoubleDouble>().unwrap();
+        assert!(third.to_f64().abs() < 1e-31);
+        assert_eq!("-1.5e-3".parse::<DoubleDouble>().unwrap(), DoubleDouble::new(-1.5) / DoubleDouble::new(1000.0));
+        assert!("1.2.3".parse::<DoubleDouble>().is_err());
+
+        // Huge exponents don't take forever, and the special values of f64 still work
+        assert_eq!("1e999999999".parse::<DoubleDouble>().unwrap().to_f64(), f64::INFINITY);
+        assert_eq!("-2.5e-999999999".parse::<DoubleDouble>().unwrap().to_f64(), 0.0);
+        assert_eq!("-inf".parse::<DoubleDouble>().unwrap().to_f64(), f64::NEG_INFINITY);
+        assert!("NaN".parse::<DoubleDouble>().unwrap().to_f64().is_nan());
+
+        let two = DoubleDouble::new(2.0);
+        assert!((two.sqrt() * two.sqrt() - two).to_f64().abs() < 1e-30);
+
+        // Narrowing and widening
+        let c = Complex::new(0.5f64, -0.25).cast::<DoubleDouble>();
+        assert_eq!(c.cast::<f32>(), Complex::new(0.5f32, -0.25));
+
+    }
+
+    #[test]
+    fn precision() {
+
+        use super::write_back;
+
+        let center = Complex::new("-0.743643887037158704752191506114774".parse::<DoubleDouble>().unwrap(), "0.131825904205311970493132056385139".parse::<DoubleDouble>().unwrap());
+        let frame_at = |step:f64| {
+            let delta = Complex::new(DoubleDouble::new(2.0 * step), DoubleDouble::new(step));
+            Frame::new(center.sub(&delta), center.add(&delta), 4, 2)
+        };
+
+        assert_eq!(Precision::for_frame(&frame_at(1e-2)),  Some(Precision::F64));
+        assert_eq!(Precision::for_frame(&frame_at(1e-20)), Some(Precision::DoubleDouble));
+        assert_eq!(Precision::for_frame(&frame_at(1e-40)), None);
+
+        // At a zoom of 1e20, double-double still tells the counters apart
+        let frame = frame_at(1e-20);
+        let orbit: Vec<Complex<DoubleDouble>> = (0..4).map(|column| frame.center_of(column, 1)).collect();
+
+        let mut birb = vec![0u64; 4 * 2 + 2];
+        birb[0] = 4;
+        birb[1] = 2;
+
+        write_back(&orbit, &mut birb, &frame);
+        assert_eq!(&birb[2..], &[0, 0, 0, 0, 1, 1, 1, 1]);
+
+        // f32 would do for the shallow frame, but it's only for previews and has to be asked for
+        assert!(Precision::F32.resolves(&frame_at(1e-2)));
+        assert!(!Precision::F32.resolves(&frame_at(1e-6)));
+        assert!(Precision::F32.is_preview() && !Precision::F64.is_preview());
+        assert_eq!(Precision::from_name("f32"), Some(Precision::F32));
+
+        // and it computes all the way through in single precision
+        let frame: Frame<f32> = Frame::new(Complex::new(-2.0, -1.0), Complex::new(2.0, 1.0), 4, 2);
+        let orbit: Vec<Complex<f32>> = (0..4).map(|column| frame.center_of(column, 1)).collect();
+
+        let mut birb = vec![0u64; 4 * 2 + 2];
+        birb[0] = 4;
+        birb[1] = 2;
+
+        write_back(&orbit, &mut birb, &frame);
+        assert_eq!(&birb[2..], &[0, 0, 0, 0, 1, 1, 1, 1]);
+
+    }
+
+    #[test]
+    fn perturbation() {
+
+        let dd = |x:f64| DoubleDouble::new(x);
+
+        // A reference inside the main cardioid, so it never escapes
+        let fractal = Fractal::<DoubleDouble>::mandelbrot();
+        let reference = Reference::new(fractal, Complex::new(dd(-0.1), dd(0.1)), 100);
+
+        // Close to the reference, the differences alone get it right to the last digits
+        let sample = Complex::new(dd(-0.1) + dd(1e-25), dd(0.1) - dd(3e-26));
+        let direct: Vec<Complex<DoubleDouble>> = fractal.orbit(sample, 100).collect();
+        let perturbed: Vec<Complex<DoubleDouble>> = reference.orbit(sample, 100).collect();
+
+        assert_eq!(perturbed.len(), 100);
+        direct.iter().zip(perturbed.iter()).for_each(|(a, b)| assert!(a.sub(b).abs().to_f64() < 1e-30));
+
+        // Far away from it, the Orbit gets rebased and still escapes at the same number
+        let generator = OrbitGenerator::Perturbation(reference);
+        for &(r, i) in [(0.3, 0.5), (-1.8, 0.01), (0.26, 0.0)].iter() {
+            let sample = Complex::new(dd(r), dd(i));
+            assert_eq!(generator.escape(sample, 100), OrbitGenerator::Direct(fractal).escape(sample, 100));
+        }
+
+    }
+
+    #[test]
+    fn batch() {
+
+        let fractal = Fractal::mandelbrot();
+
+        // Some inside, some outside, some right on the edge, and more of them than there are lanes
+        let mut samples: Vec<Complex> = [(-1.0, 0.0), (0.5, 0.5), (-2.0, 0.0), (0.26, 0.0), (-0.75, 0.1), (1.0, 1.0), (0.0, 0.0)]
+            .iter().map(|&(r, i)| Complex::new(r, i)).collect();
+        samples.extend((0..4 * LANES).map(|k| Complex::new(-2.0 + 0.11 * k as f64, 0.7 - 0.05 * k as f64)));
+
+        let escapes = escape_batch(&fractal, &samples, 300);
+
+        samples.iter().enumerate().for_each(|(l, &sample)| {
+            assert_eq!(escapes[l], fractal.orbit(sample, 300).position(|z| z.abs() >= 2.0), "sample {:?}", sample);
+        });
+
+        // n, that isn't a multiple of the block
+        assert_eq!(escape_batch(&fractal, &samples, 5), samples.iter().map(|&c| fractal.orbit(c, 5).position(|z| z.abs() >= 2.0)).collect::<Vec<_>>());
+
+    }
+
+    #[test]
+    fn sample_budget() {
+
+        use super::SampleBudget;
+        use std::sync::Arc;
+
+        // Chunks, that don't divide the budget, still add up to exactly the budget
+        let budget = Arc::new(SampleBudget::new(10_007));
+
+        let handles: Vec<_> = (0..7).map(|_| {
+            let budget = Arc::clone(&budget);
+            std::thread::spawn(move || {
+                let mut taken = 0;
+                loop {
+                    let chunk = budget.take(100);
+                    if chunk == 0 { return taken }
+                    taken += chunk;
+                }
+            })
+        }).collect();
+
+        let total: u64 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+        assert_eq!(total, 10_007);
+        assert_eq!(budget.left(), 0);
+        assert_eq!(budget.take(100), 0);
+
+    }
+
+    #[test]
+    fn big_counts() {
+
+        use super::{SampleBudget, thread_msg, status_msg};
+        use std::time::{Duration, Instant};
+
+        let big = i32::MAX as u64 * 3;
+
+        // The budget hands out chunks way beyond i32
+        let budget = SampleBudget::new(big);
+        let chunks: Vec<u64> = (0..).map(|_| budget.take(1 << 30)).take_while(|&c| c > 0).collect();
+        assert_eq!(chunks.len(), 6);
+        assert_eq!(chunks.iter().sum::<u64>(), big);
+
+        // A stub orbit source: with such a large c every Orbit escapes right away, so it's always
+        // exactly one number long and costs next to nothing
+        let stub  = OrbitGenerator::Direct(Fractal::Julia { c: Complex::new(1000f64, 0.0) });
+        let frame = Frame::new(Complex::new(-2000f64, -2000.0), Complex::new(2000.0, 2000.0), 4, 4);
+
+        let mut mh = MHOrbits::new(big, 0, 1, frame, false, stub.clone(), Projection::buddhabrot());
+        assert_eq!(mh.remaining(), big);
+        assert!(mh.by_ref().take(1000).all(|o| o.len() == 1));
+        assert_eq!(mh.remaining(), big - 1000);
+
+        mh.add_samples(u64::MAX);
+        assert_eq!(mh.remaining(), u64::MAX);
+
+        // The warmup doesn't eat into the samples
+        assert_eq!(MHOrbits::new(3, 10, 1, frame, false, stub, Projection::buddhabrot()).count(), 3);
+
+        // And the logging gets the percentages right, even after the timeout
+        assert!(thread_msg((0, big, 0, false), 2 * big, &super::Rate::new()).contains("50.00%"));
+        assert!(status_msg(big, 2 * big, Instant::now(), Duration::from_secs(0), &super::Rate::new()).contains("50.00%"));
+
+    }
+
+    #[test]
+    fn counters() {
+
+        use super::counters::*;
+        use super::{write_back, write_stats};
+
+        let frame = Frame::new(Complex::new(0.0, 0.0), Complex::new(2.0, 1.0), 2, 1);
+
+        // u16 counters take hits just like a birb of u64s
+        let mut counters = Counters::new(2, 1, 1, CounterWidth::U16);
+        write_back(&[Complex::new(1.5, 0.5), Complex::new(1.5, 0.5)], &mut counters, &frame);
+        assert_eq!(counters.to_birb(), vec![2, 1, 0, 2]);
+
+        // until one fills up, then all of them widen
+        counters.add(2, 65535);
+        assert_eq!(counters.counter_width(), CounterWidth::U16);
+
+        counters.add(3, 65534);
+        assert_eq!(counters.counter_width(), CounterWidth::U32);
+        assert_eq!(counters.to_birb(), vec![2, 1, 65535, 65536]);
+
+        counters.add(3, 1 << 40);
+        assert_eq!(counters.counter_width(), CounterWidth::U64);
+        assert_eq!(counters.get(3), (1 << 40) + 65536);
+
+        // u64s saturate
+        counters.add(3, u64::MAX);
+        assert_eq!(counters.get(3), u64::MAX);
+
+        // The signed stats channels need u64s right away
+        let mut counters = Counters::new(2, 1, 5, CounterWidth::U16);
+        write_stats(&[Complex::new(0.5, 0.0)], &mut counters, &frame);
+        assert_eq!(counters.counter_width(), CounterWidth::U64);
+        assert_eq!(counters.get(2), 1);
+
+        // Every width goes through a birb file and comes back as u64s, and the width is recorded
+        let directory = TempDir::new();
+        let filename = &directory.file("counters.birb");
+
+        let birb: Vec<u64> = vec![3, 1, 1, 70_000, 5_000_000_000];
+
+        for &width in CounterWidth::ALL.iter() {
+
+            let small = vec![2, 1, 1, 70_000];
+            write_counters(filename, &Counters::from_birb(&small, width), &Header::new(&["count"]));
+
+            assert_eq!(read_header(filename).counter_width(), width.wider(CounterWidth::U32));
+            assert_eq!(read_birb(filename), small);
+
+            let mut header = Header::new(&["count"]);
+            header.set("counter", width.name().to_string());
+            write_birb(filename, &[3, 1, 7, 8, 9], &header);
+
+            assert_eq!(read_header(filename).counter_width(), width);
+            assert_eq!(read_birb(filename), vec![3, 1, 7, 8, 9]);
+
+        }
+
+        // Too large for the header's width gets widened on writing
+        let mut header = Header::new(&["count"]);
+        header.set("counter", "u16".to_string());
+        write_birb(filename, &birb, &header);
+        assert_eq!(read_header(filename).counter_width(), CounterWidth::U64);
+        assert_eq!(read_birb(filename), birb);
+
+    }
+
+    #[test]
+    fn tiles() {
+
+        use super::counters::*;
+        use super::tiles::*;
+
+        let directory = TempDir::new();
+        let filename = &directory.file("tiles.birb");
+        let scratch  = format!("{}.scratch", filename);
+
+        // Three by two tiles, the right and bottom ones sticking out, two channels, the second one
+        // signed, and memory for just one tile, so they get spilled all the time
+        let (width, height) = (600u64, 300u64);
+        let n = (width * height) as usize;
+
+        let mut tiled = TiledCounters::new(width, height, 2, 1, &scratch);
+        let mut birb  = vec![0u64; 2 * n + 2];
+        birb[0] = width;
+        birb[1] = height;
+
+        let mut x = 42u64;
+        for k in 0..100_000u64 {
+
+            x = x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let index = (x >> 33) as usize % n + 2;
+
+            tiled.add(index, k % 7);
+            birb.add(index, k % 7);
+            tiled.wrapping_add(index + n, 0u64.wrapping_sub(k % 3));
+            birb.wrapping_add(index + n, 0u64.wrapping_sub(k % 3));
+
+            if k % 30_000 == 0 { tiled.flush() }
+
+        }
+
+        write_tiled(filename, &mut tiled, &Header::new(&["count", "signed"]));
+        drop(tiled);
+
+        assert!(!std::path::Path::new(&scratch).exists());
+        assert_eq!(read_header(filename).tile(), Some(TILE));
+        assert_eq!(read_header(filename).counter_width(), CounterWidth::U64);
+        assert_eq!(read_dimensions(filename), (width, height));
+
+        // Read as a whole, it's just the birb
+        assert_eq!(read_birb(filename), birb);
+
+        // And band by band, across the edges of the tiles and off the bottom
+        let band = |rows:std::ops::Range<u64>| {
+            let rows = rows.start..rows.end.min(height);
+            let len  = rows.end - rows.start;
+            let mut band = vec![width, len];
+            for c in 0..2 {
+                band.extend_from_slice(&birb[(2 + c * width * height + rows.start * width) as usize..(2 + c * width * height + rows.end * width) as usize]);
+            }
+            band
+        };
+
+        for rows in [0..256, 250..260, 256..300, 299..1000, 5..5].iter() {
+            assert_eq!(read_rows(filename, rows.clone()), band(rows.clone()));
+        }
+
+        // Plain birbs read band by band just the same, and don't keep the tile size
+        write_birb(filename, &birb, &read_header(filename));
+        assert_eq!(read_header(filename).tile(), None);
+        assert_eq!(read_rows(filename, 250..260), band(250..260));
+
+    }
+
+    #[test]
+    fn birb_reader() {
+
+        use super::counters::*;
+
+        let directory = TempDir::new();
+        let filename = &directory.file("reader.birb");
+        let copy     = directory.file("copy.birb");
+
+        // Two channels of 5x7 u16s
+        let mut birb = vec![5, 7];
+        birb.extend(0..70u64);
+
+        let mut header = Header::new(&["count", "iteration"]);
+        header.set("counter", "u16".to_string());
+        write_birb(filename, &birb, &header);
+
+        // The bands are birbs of their own, the last one cut off at the bottom
+        let mut reader = BirbReader::open(filename);
+        assert_eq!((reader.width(), reader.height(), reader.channels()), (5, 7, 2));
+        assert_eq!(reader.rows(5..9), vec![5, 2, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 60, 61, 62, 63, 64, 65, 66, 67, 68, 69]);
+
+        let bands: Vec<(u64, Vec<u64>)> = reader.bands().collect();
+        assert_eq!(bands.len(), 1);
+        assert_eq!(bands[0], (0, birb.clone()));
+
+        // Written back band by band, in any order and width, it's the same birb
+        let mut writer = BirbWriter::create(&copy, &header, 5, 7, CounterWidth::U32);
+        for &(first, last) in [(4, 7), (0, 1), (1, 4)].iter() {
+            writer.write_rows(first, &reader.rows(first..last));
+        }
+        writer.finish();
+
+        assert_eq!(read_header(&copy).counter_width(), CounterWidth::U32);
+        assert_eq!(read_birb(&copy), birb);
+
+    }
+
+    #[test]
+    fn malformed_birbs() {
+
+        let directory = TempDir::new();
+        let file = |name:&str, bytes:&[u8]| { let f = directory.file(name); std::fs::write(&f, bytes).unwrap(); f };
+
+        let whole = directory.file("whole.birb");
+        let mut birb = vec![5, 7];
+        birb.extend(0..35u64);
+        write_birb(&whole, &birb, &Header::new(&["count"]));
+        let bytes = std::fs::read(&whole).unwrap();
+
+        // A header longer than the file it's in
+        let mut long = MAGIC.to_le_bytes().to_vec();
+        long.extend_from_slice(&u64::MAX.to_le_bytes());
+
+        // Each stops with the error, that it's malformed, not with a panic somewhere further in
+        let fails = |f:String| std::thread::spawn(move || { BirbReader::open(&f); }).join().map_err(|e| e.is::<super::log::Failed>()) == Err(true);
+
+        assert!(!fails(whole));
+        assert!(fails(file("cut.birb", &bytes[..bytes.len() - 8])));
+        assert!(fails(file("random.birb", &[0xa7; 24])));
+        assert!(fails(file("text.birb", b"This is a text file, and not a birb.\n\n\n\n")));
+        assert!(fails(file("long.birb", &long)));
+
+    }
+
+    #[test]
+    fn estimate() {
+
+        use super::counters::CounterWidth;
+        use super::estimate::*;
+
+        // The example from the docs: 40000x40000 u64s are 12.8 GB
+        let e = Estimate { birb: birb(40_000, 40_000, 1, CounterWidth::U64, false, None), orbits: orbits(10_000, 99, Precision::F64), reference: 0, threads: 4 };
+        assert_eq!(e.birb, 12_800_000_000);
+        assert_eq!(e.orbits, 10_000 * 100 * 16);
+        assert_eq!(e.total(), 12_800_000_000 + 4 * 16_000_000);
+        assert_eq!(human_bytes(e.birb), "12.80 GB");
+
+        // Statistics need u64s, whatever the counters, and every thread has its own reference
+        let e = Estimate { birb: birb(10, 10, 5, CounterWidth::U16, true, None), orbits: orbits(1, 9, Precision::DoubleDouble), reference: reference(9, Precision::DoubleDouble), threads: 2 };
+        assert_eq!(e.birb, 10 * 10 * 5 * 8);
+        assert_eq!(e.reference, 10 * (32 + 32));
+        assert_eq!(e.total(), e.birb + 2 * (e.orbits + e.reference) + e.reference);
+
+        // Tiled counters take up as much as they're allowed to, and the deposits on top
+        assert_eq!(birb(40_000, 40_000, 1, CounterWidth::U64, false, Some(1_000_000_000)), 1_000_000_000 + super::tiles::batch_bytes());
+
+        // Absurd sizes don't overflow, they're just too much
+        assert_eq!(birb(u64::MAX, 2, 5, CounterWidth::U64, false, None), u64::MAX);
+        assert_eq!(birb(u64::MAX, 2, 5, CounterWidth::U64, false, Some(u64::MAX)), u64::MAX);
+
+        assert_eq!(human_bytes(999), "999 B");
+        assert_eq!(human_bytes(u64::MAX), "18.45 EB");
+
+    }
+
+    #[test]
+    fn distributed() {
+
+        use super::distributed::*;
+        use std::net::TcpListener;
+        use std::sync::{Arc, Mutex};
+
+        assert_eq!(units(1000, 300).into_iter().map(|u| (u.id, u.samples)).collect::<Vec<_>>(), vec![(0, 300), (1, 300), (2, 300), (3, 100)]);
+
+        let args = |a:&[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+        assert_eq!(role("render", args(&["render", "-s", "5"])), (Role::Alone, args(&["render", "-s", "5"])));
+        assert_eq!(role("render", args(&["render", "serve", "--unit", "2"])), (Role::Alone, args(&["render", "serve", "--unit", "2"])));
+        assert_eq!(role("serve", args(&["serve", "-s", "5", "--unit", "2"])), (Role::Serve { listen: LISTEN.to_string(), unit: Some(2) }, args(&["serve", "-s", "5"])));
+        assert_eq!(role("worker", args(&["worker", "--connect", "a:1", "-t", "2"])), (Role::Worker { connect: "a:1".to_string() }, args(&["worker", "-t", "2"])));
+
+        // A 2x1 birb, with a count and a signed channel
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address  = listener.local_addr().unwrap().to_string();
+
+        let birb = Arc::new(Mutex::new(vec![2u64, 1, 0, 0, 0, 0]));
+        let summed = Arc::clone(&birb);
+
+        let coordinator = std::thread::spawn(move || serve(listener, args(&["-s", "1000"]), summed, vec![false, true], 1000, 300, std::time::Duration::from_millis(500)));
+
+        // A worker, that disappears with its unit, which goes to someone else
+        {
+            let (mut connection, config) = Connection::open(&address);
+            assert_eq!(config, args(&["-s", "1000"]));
+            assert!(connection.next_unit().is_some());
+        }
+
+        // A worker, that announces a birb of the wrong size, is hung up on, before it's read
+        {
+            use std::io::{BufRead, BufReader, Write};
+
+            let stream = std::net::TcpStream::connect(&address).unwrap();
+            let mut lines = BufReader::new(stream.try_clone().unwrap()).lines().map(|l| l.unwrap());
+
+            let unit = lines.by_ref().find(|l| l.starts_with("unit ")).unwrap();
+            writeln!(&stream, "done {} {}", unit.split(' ').nth(1).unwrap(), u64::MAX).unwrap();
+
+            assert!(lines.next().is_none());
+        }
+
+        // A worker, that stays connected, but goes silent, is given up on, and hung up on
+        let silent = {
+            let (mut connection, _) = Connection::open(&address);
+            assert!(connection.next_unit().is_some());
+            std::thread::spawn(move || connection.next_unit())
+        };
+
+        // Workers, that count the samples, and -1 in the signed channel
+        let workers: Vec<_> = (0..3).map(|_| {
+            let address = address.clone();
+            std::thread::spawn(move || {
+                let (mut connection, _) = Connection::open(&address);
+                let mut ids = Vec::new();
+                while let Some(unit) = connection.next_unit() {
+                    connection.send(unit, &[2, 1, unit.samples, 0, u64::MAX, 0]);
+                    ids.push(unit.id);
+                }
+                ids
+            })
+        }).collect();
+
+        coordinator.join().unwrap();
+        assert_eq!(silent.join().unwrap(), None);
+
+        let mut ids: Vec<u64> = workers.into_iter().flat_map(|w| w.join().unwrap()).collect();
+        ids.sort();
+
+        assert_eq!(ids, vec![0, 1, 2, 3]);
+        assert_eq!(*birb.lock().unwrap(), vec![2, 1, 1000, 0, u64::MAX - 3, 0]);
+
+        // Seeded threads draw the same samples
+        let frame = Frame::new(Complex::new(-2.0, -2.0), Complex::new(2.0, 2.0), 10, 10);
+        let draw = |seed| {
+            super::math::seed_thread(seed);
+            let orbits: Vec<_> = MHOrbits::new(5, 10, 50, frame, true, OrbitGenerator::Direct(Fractal::mandelbrot()), Projection::buddhabrot()).collect();
+            super::math::seed_thread(None);
+            orbits
+        };
+        assert_eq!(draw(Some(7)), draw(Some(7)));
+        assert!(draw(Some(7)) != draw(Some(8)));
+
+        // Units and threads draw from streams of their own, whatever their seeds, even next to each
+        // other, or 2^48 apart
+        use super::math::stream;
+        let pairs = [(5, 0), (5, 1), (6, 0), (6, 1), (0, 6), (1, 5), (5 + (1 << 48), 0), (5 + (1 << 48), 1)];
+        let firsts: Vec<_> = pairs.iter().map(|&(seed, id)| draw(Some(stream(seed, id))).into_iter().next().unwrap()).collect();
+        for i in 0..pairs.len() {
+            for j in 0..i { assert!(firsts[i] != firsts[j], "{:?} and {:?}", pairs[i], pairs[j]) }
+        }
+
+    }
+
+    #[test]
+    fn append() {
+
+        use super::counters::*;
+        use super::tiles::*;
+
+        let directory = TempDir::new();
+        let filename = &directory.file("append.birb");
+        let scratch  = directory.file("append.scratch");
+
+        // A count and a signed channel, across the edges of the tiles
+        let (width, height) = (300u64, 260u64);
+        let n = (width * height) as usize;
+
+        let mut birb = vec![width, height];
+        birb.extend((0..n as u64).map(|k| k % 5));
+        birb.extend((0..n as u64).map(|k| 0u64.wrapping_sub(k % 3)));
+
+        write_birb(filename, &birb, &Header::new(&["count", "arg_r"]));
+
+        let signed = [false, true];
+
+        // Twice the birb, once as a whole and once band by band, into narrow counters
+        let mut counters = Counters::new(width, height, 2, CounterWidth::U16);
+        read_into(filename, &mut counters, &signed);
+        for &(first, last) in [(0, 7), (7, 256), (256, 260)].iter() {
+            add_band(&mut counters, first, &read_rows(filename, first..last), &signed);
+        }
+
+        let doubled: Vec<u64> = birb.iter().enumerate().map(|(i, &h)| if i < 2 { h } else { h.wrapping_add(h) }).collect();
+        assert_eq!(counters.to_birb(), doubled);
+
+        // And into tiles, from a tiled birb
+        let mut tiled = TiledCounters::new(width, height, 2, 1, &scratch);
+        read_into(filename, &mut tiled, &signed);
+        write_tiled(filename, &mut tiled, &Header::new(&["count", "arg_r"]));
+        drop(tiled);
+
+        let mut tiled = TiledCounters::new(width, height, 2, 1, &scratch);
+        read_into(filename, &mut tiled, &signed);
+        read_into(filename, &mut tiled, &signed);
+        write_tiled(filename, &mut tiled, &Header::new(&["count", "arg_r"]));
+        drop(tiled);
+
+        assert_eq!(read_birb(filename), doubled);
+
+        // Only the same render is appended to, and only, if the birb says how many samples it holds
+        use super::butterbrot::appendable;
+
+        let mut header = Header::new(&["count"]);
+        super::RENDER_KEYS.iter().for_each(|key| header.set(key, "1".to_string()));
+
+        let mut found = header.clone();
+        found.set("samples", "500".to_string());
+
+        assert_eq!(appendable(&found, (4, 3), &header, (4, 3)), Ok(500));
+        assert!(appendable(&found, (4, 3), &header, (3, 4)).unwrap_err().contains("4x3, not 3x4"));
+        assert!(appendable(&found, (4, 3), &Header::new(&["count", "arg_r"]), (4, 3)).unwrap_err().contains("channels"));
+
+        let mut other = found.clone();
+        other.set("iterations", "2".to_string());
+        assert_eq!(appendable(&other, (4, 3), &header, (4, 3)), Err("it was rendered with iterations '2', not '1'.".to_string()));
+
+        other.set("preview", "true".to_string());
+        assert!(appendable(&other, (4, 3), &header, (4, 3)).unwrap_err().contains("previews"));
+
+        // A birb from before the metadata, or from another tool, says neither what it is, nor how
+        // many samples it holds
+        let bare = Header::new(&["count"]);
+        assert!(appendable(&bare, (4, 3), &header, (4, 3)).unwrap_err().contains("doesn't record its mode"));
+
+        let mut unknown = header.clone();
+        unknown.remove("samples");
+        assert_eq!(appendable(&unknown, (4, 3), &header, (4, 3)), Err("it doesn't record, how many samples it holds.".to_string()));
+
+    }
+
+    #[test]
+    fn atomic_file() {
+
+        use std::io::Write;
+
+        let directory = TempDir::new();
+        let filename = &directory.file("out.birb");
+
+        std::fs::write(filename, b"old").unwrap();
+
+        // Dropped before it's committed, the old file stays, and nothing is left behind
+        {
+            let mut f = AtomicFile::create(filename).unwrap();
+            f.write_all(b"half").unwrap();
+        }
+        assert_eq!(std::fs::read(filename).unwrap(), b"old");
+        assert_eq!(std::fs::read_dir(&directory.0).unwrap().count(), 1);
+
+        // Committed, it takes the old one's place
+        let mut f = AtomicFile::create(filename).unwrap();
+        f.write_all(b"new").unwrap();
+        f.commit().unwrap();
+        assert_eq!(std::fs::read(filename).unwrap(), b"new");
+        assert_eq!(std::fs::read_dir(&directory.0).unwrap().count(), 1);
+
+        // Two at once don't touch each other's temporary file, the last one committed wins
+        let mut a = AtomicFile::create(filename).unwrap();
+        let mut b = AtomicFile::create(filename).unwrap();
+        a.write_all(b"a").unwrap();
+        drop(AtomicFile::create(filename).unwrap());
+        b.write_all(b"b").unwrap();
+        a.commit().unwrap();
+        assert_eq!(std::fs::read(filename).unwrap(), b"a");
+        b.commit().unwrap();
+        assert_eq!(std::fs::read(filename).unwrap(), b"b");
+        assert_eq!(std::fs::read_dir(&directory.0).unwrap().count(), 1);
+
+        // Only one render at a time may write a file, under whichever of its names
+        let reservation = Reservation::new(filename);
+        let again = format!("{}/./out.birb", directory.0.display());
+        assert!(std::thread::spawn(move || { Reservation::new(&again); }).join().is_err());
+        drop(reservation);
+        Reservation::new(filename);
+
+        let args = |a:&[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+        assert_eq!(clobber(args(&["birb2bmp", "a.birb", "b.bmp"])), (Clobber::Refuse, args(&["birb2bmp", "a.birb", "b.bmp"])));
+        assert_eq!(clobber(args(&["birb2bmp", "--overwrite", "a.birb", "b.bmp"])), (Clobber::Overwrite, args(&["birb2bmp", "a.birb", "b.bmp"])));
+        assert_eq!(clobber(args(&["birb2bmp", "a.birb", "--no-clobber"])), (Clobber::Keep, args(&["birb2bmp", "a.birb"])));
+
+        let name = gen_filename("birb");
+        assert_eq!(name.len(), "birb_XXXXXXXX.birb".len());
+        assert!(name[5..13].chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()));
+
+    }
+
+    #[test]
+    fn filename_template() {
+
+        let temp = TempDir::new();
+        let directory = temp.0.to_str().unwrap();
+
+        let mut header = Header::new(&["count"]);
+        header.set("fractal", "julia -0.8 0.156".to_string());
+        header.set("iterations", "500".to_string());
+        header.set("complex1", "-1.5 -1.0".to_string());
+
+        let values = template_values(&header, 300, 200);
+        let template = format!("{}/{{formula}}_{{width}}x{{height}}_i{{iterations}}_{{complex1}}.birb", directory);
+        let filename = format!("{}/julia_300x200_i500_-1.5_-1.0.birb", directory);
+
+        assert_eq!(fill_template(&template, &values, Clobber::Refuse), filename);
+
+        // Taken names get a number, unless they may be overwritten or left be
+        std::fs::write(&filename, b"birb").unwrap();
+        assert_eq!(fill_template(&template, &values, Clobber::Refuse), format!("{}/julia_300x200_i500_-1.5_-1.0_2.birb", directory));
+        assert_eq!(fill_template(&template, &values, Clobber::Overwrite), filename);
+        assert_eq!(fill_template(&template, &values, Clobber::Keep), filename);
+
+        // Plain names are left to check_output()
+        assert_eq!(fill_template(&filename, &values, Clobber::Refuse), filename);
+
+        let date = fill_template("{date}", &values, Clobber::Refuse);
+        assert_eq!(date.len(), "20261018-153012".len());
+        assert!(date.starts_with("20") && date.chars().nth(8) == Some('-'));
+
+    }
+
+    #[test]
+    fn command_line() {
+
+        use super::cli::*;
+
+        let args = |a:&[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+
+        let command = Command {
+            name: "test",
+            about: "",
+            help: "",
+            options: vec![
+                Opt::value("width", &["<number>"], "").short('w').integer(1, 100).default("40"),
+                Opt::value("center", &["<real>", "<imaginairy>"], "").short('c').number(f64::NEG_INFINITY, f64::INFINITY).default("0 0"),
+                Opt::value("rotate", &["<plane>", "<angle>"], "").repeats(),
+                Opt::value("deposit", &["<mode>"], "").choices(&[("points", ""), ("trails", "")]),
+                Opt::value("threads", &["<number>"], "").aliases(&["-th"]).env("BIRB_TEST_THREADS"),
+                Opt::flag("stats", ""),
+            ],
+            positionals: vec![Positional { name: "<birb>", required: false, many: true, help: "" }],
+        };
+
+        // Spelled any which way, negative numbers are values, the last one counts, unless it repeats
+        let m = parse(&command, &args(&["--width=7", "a.birb", "-c", "-0.5", "1e-3", "--rotate", "zr-cr", "1", "-w", "9", "--rotate=zi-ci", "2", "-th", "3", "--", "--stats"]));
+        assert_eq!(m.get::<u64>("width"), Some(9));
+        assert_eq!(m.get_all::<f64>("center"), Some(vec![-0.5, 0.001]));
+        assert_eq!(m.all("rotate"), vec![&args(&["zr-cr", "1"])[..], &args(&["zi-ci", "2"])[..]]);
+        assert_eq!(m.value("threads"), Some("3"));
+        assert_eq!(m.positionals, args(&["a.birb", "--stats"]));
+        assert!(!m.flag("stats") && m.given("width") && m.value("deposit").is_none());
+
+        // The defaults, and the environment before them
+        std::env::set_var("BIRB_TEST_THREADS", "12");
+        let m = parse(&command, &[]);
+        assert_eq!((m.value("width"), m.source("width")), (Some("40"), Some(Source::Default)));
+        assert_eq!(m.get_all::<f64>("center"), Some(vec![0f64, 0f64]));
+        assert_eq!((m.value("threads"), m.source("threads")), (Some("12"), Some(Source::Env)));
+        std::env::remove_var("BIRB_TEST_THREADS");
+
+        // Only the options asked for are taken out, with their values
+        let (m, rest) = extract(&command.options[..2], args(&["prog", "-c", "1", "2", "--stats", "--width=3", "x"]));
+        assert_eq!((m.get::<u64>("width"), m.get_all::<f64>("center")), (Some(3), Some(vec![1f64, 2f64])));
+        assert_eq!(rest, args(&["prog", "--stats", "x"]));
+
+        // The help and the completions know all the commands and their flags
+        let commands = commands();
+        for c in commands.iter() {
+            let help = help(c);
+            assert!(c.options.iter().all(|o| help.contains(&o.long())), "{}", c.name);
+            assert!(help.lines().all(|l| l.chars().count() <= 80 || !l.contains(' ')), "{}", c.name);
+        }
+        for shell in SHELLS.iter() {
+            let script = completions(shell, &commands).unwrap();
+            assert!(commands.iter().all(|c| script.contains(c.name)));
+            assert!(script.contains("weighted-trails") && script.contains("no-clobber"));
+        }
+
+        // The old binaries still take what they used to
+        let m = parse(&super::butterbrot::command(), &super::butterbrot::old_style(args(&["butterbrot", "-h", "30", "-wu", "5", "-int", "2", "-o", "h", "--phase_len", "8"]))[1..]);
+        assert_eq!((m.value("height"), m.value("warmup"), m.value("interval"), m.value("filename"), m.value("phase-len")), (Some("30"), Some("5"), Some("2"), Some("h"), Some("8")));
+        assert_eq!(super::butterbrot::old_style(args(&["butterbrot", "serve", "--unit", "4", "h"])), args(&["butterbrot", "serve", "--unit", "4", "--help"]));
+
+        assert_eq!(super::birb2bmp::old_style(args(&["birb2bmp", "a.birb", "b.bmp", "x", "--overwrite"])), args(&["birb2bmp", "--overwrite", "-o", "b.bmp", "--invert", "--", "a.birb"]));
+        assert_eq!(super::birb2bmp::old_style(args(&["birb2bmp", "a.birb", "b.bmp", "direction"])), args(&["birb2bmp", "-o", "b.bmp", "--colour", "direction", "--", "a.birb"]));
+        assert_eq!(super::birb_combinator::old_style(args(&["birb_combinator", "a.birb", "b.birb"])), args(&["birb_combinator", "--", "a.birb", "b.birb"]));
+        assert_eq!(super::birb_combinator::old_style(args(&["birb_combinator", "c.birb", "a.birb", "b.birb"])), args(&["birb_combinator", "-o", "c.birb", "--", "a.birb", "b.birb"]));
+
+    }
+
+    #[test]
+    fn documented_defaults() {
+
+        use super::cli::*;
+        use super::butterbrot::{command, options};
+
+        let command = command();
+
+        // Every option with a default says so, in all the places it's documented
+        let help = help(&command);
+        let man  = man(&command);
+        for opt in options().iter().filter(|o| o.default.is_some()) {
+            assert!(option_help(opt).contains(&format!("Default: {}", opt.default.unwrap())), "{}", opt.name);
+            assert!(help.contains(&option_help(opt)), "{}", opt.name);
+            assert!(man.contains(&format!("Default: {}", opt.default.unwrap().replace('-', "\\-"))), "{}", opt.name);
+        }
+
+        // The README and the man pages are the ones generated from the options, not older ones
+        let readme = include_str!("../../README.md");
+        assert!(readme.contains(&markdown(&command)), "the table of the README is out of date, see 'birb help --markdown render'");
+
+        let page = |name:&str| std::fs::read_to_string(format!("{}/doc/man/{}.1", env!("CARGO_MANIFEST_DIR"), name)).unwrap();
+        assert_eq!(page("birb"), man_overview(&commands()), "doc/man/birb.1 is out of date, see 'birb help --man'");
+        for c in commands().iter() {
+            assert_eq!(page(&format!("birb-{}", c.name)), super::cli::man(c), "doc/man/birb-{}.1 is out of date, see 'birb help --man {}'", c.name, c.name);
+        }
+
+    }
+
+    #[test]
+    fn configuration() {
+
+        use super::config::*;
+        use super::cli::{parse_with, Source};
+        use super::butterbrot::command;
+
+        let args = |a:&[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+
+        // TOML and JSON read to the same values, numbers with all their digits
+        let toml = toml("# a render\nwidth = 4_000\ncenter = [-0.743643887037151123456,\n  0.1] # the seahorses\ndeposit = 'trails'\nstats = true\nrotate = [[\"zr-cr\", 0.3]]\n\n[[job]]\nname = \"a\"\n[[job]]\nname = \"b\"\n").unwrap();
+        let json = json("{\"width\": 4000, \"center\": [-0.743643887037151123456, 0.1], \"deposit\": \"trails\", \"stats\": true, \"rotate\": [[\"zr-cr\", 0.3]], \"seed\": null}").unwrap();
+
+        assert_eq!(toml.get("center"), Some(&Value::Array(vec![Value::Number("-0.743643887037151123456".to_string()), Value::Number("0.1".to_string())])));
+        assert_eq!(json.get("center"), toml.get("center"));
+        assert_eq!(toml.get("job"), Some(&Value::Array(vec![
+            Value::Table(vec![("name".to_string(), Value::Text("a".to_string()))]),
+            Value::Table(vec![("name".to_string(), Value::Text("b".to_string()))]),
+        ])));
+        assert!(json.get("seed").is_none());
+        assert!(super::config::toml("width = 1\nwidth = 2").is_err() && super::config::toml("width = [1").is_err() && super::config::json("[1]").is_err());
+
+        // As the options of a render
+        let command = command();
+        let render = Value::Table(match toml { Value::Table(t) => t.into_iter().filter(|(k, _)| k != "job").collect(), _ => unreachable!() });
+        let options = super::config::options(&render, &command, "test");
+        assert_eq!(options, super::config::options(&json, &command, "test"));
+        assert_eq!(options[0], ("width".to_string(), args(&["4000"])));
+        assert_eq!(options[4], ("rotate".to_string(), args(&["zr-cr", "0.3"])));
+
+        // The later configurations take the place of the earlier ones, and false unsets a flag
+        let preset = super::config::options(&super::config::toml("width = 10\nheight = 20\nstats = true").unwrap(), &command, "test");
+        let config = super::config::options(&super::config::toml("width = 30\nstats = false").unwrap(), &command, "test");
+        let merged = merge(vec![preset, config]);
+        assert_eq!(merged, vec![("height".to_string(), args(&["20"])), ("width".to_string(), args(&["30"]))]);
+
+        // The command line goes before the configuration, which goes before the defaults
+        let m = parse_with(&command, &args(&["-w", "50"]), &merged);
+        assert_eq!((m.value("width"), m.source("width")), (Some("50"), Some(Source::Given)));
+        assert_eq!((m.value("height"), m.source("height")), (Some("20"), Some(Source::Config)));
+        assert_eq!((m.value("zoom"), m.source("zoom")), (Some("100"), Some(Source::Default)));
+        assert_eq!(m.arguments(&[]), args(&["--width", "50", "--height", "20"]));
+
+        // What's printed, reads back the same
+        let m = parse_with(&command, &args(&["--stats", "-r", "zr-cr", "0.5", "-c", "-1", "0.25", "-o", "a \"b\".birb"]), &[]);
+        let printed = to_toml(&m, &command, &[]);
+        let again = parse_with(&command, &[], &merge(vec![super::config::options(&super::config::toml(&printed).unwrap(), &command, "test")]));
+        assert_eq!(to_toml(&again, &command, &[]), printed);
+        assert_eq!((again.value("filename"), again.all("rotate"), again.flag("stats")), (Some("a \"b\".birb"), vec![&args(&["zr-cr", "0.5"])[..]], true));
+
+        // Even the one of a Julia-style render, which leaves out the default z0, that would clash
+        let dir = TempDir::new();
+        let file = dir.file("julia.toml");
+        let printed = super::butterbrot::printed_config(&super::butterbrot::resolve(&args(&["butterbrot", "--julia", "-0.4", "0.6"])));
+        assert!(printed.contains("\n# z0 =\n"));
+        std::fs::write(&file, &printed).unwrap();
+        let settings = super::butterbrot::parse_args(args(&["butterbrot", "--config", &file]));
+        assert!(matches!(settings.fractal, Fractal::Julia { .. }));
+        assert_eq!(super::butterbrot::printed_config(&super::butterbrot::resolve(&args(&["butterbrot", "--config", &file]))), printed);
+
+    }
+
+    #[test]
+    fn queue() {
+
+        use super::queue::*;
+
+        let args = |a:&[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+
+        // The defaults go beneath every job, which are named by their number, unless they have a name
+        let file = super::config::toml("concurrency = 2\n[defaults]\nwidth = 40\nstats = true\n[[job]]\nname = \"a\"\nwidth = 50\npreset = \"deep\"\n[[job]]\nstats = false\nrotate = [[\"zr-cr\", 1]]\n").unwrap();
+        let (jobs, concurrency) = jobs(&file, "test");
+
+        assert_eq!(concurrency, Some(2));
+        assert_eq!(jobs.iter().map(|j| j.name.as_ref()).collect::<Vec<&str>>(), vec!["a", "job 2"]);
+        assert_eq!(arguments(&jobs[0], false), args(&["birb", "--preset", "deep", "--stats", "--width", "50"]));
+        assert_eq!(arguments(&jobs[1], true), args(&["birb", "--width", "40", "--rotate", "zr-cr", "1", "--overwrite"]));
+
+        let (again, _) = super::queue::jobs(&file, "test");
+        assert_eq!(again, jobs);
+
+        // How to log is the queue's, the jobs can't have one of their own
+        let (quiet, _) = super::queue::jobs(&super::config::toml("[[job]]\nquiet = true\n").unwrap(), "test");
+        assert!(std::thread::spawn(move || arguments(&quiet[0], false)).join().is_err());
+
+        // The settings only change, if the job does, or the config it reads
+        let dir = TempDir::new();
+        let config = dir.file("config.toml");
+        std::fs::write(&config, "iterations = 20").unwrap();
+
+        let file = super::config::toml(&format!("[[job]]\nname = \"a\"\nconfig = \"{}\"\n[[job]]\nwidth = 40\n", config)).unwrap();
+        let (jobs, _) = super::queue::jobs(&file, "test");
+        let before = settings(&jobs[0]);
+
+        assert_eq!(settings(&jobs[0]), before);
+        assert_ne!(settings(&jobs[1]), before);
+        std::fs::write(&config, "iterations = 30").unwrap();
+        assert_ne!(settings(&jobs[0]), before);
+
+        // The state reads back, what was written, and a job is only done, if it didn't change and
+        // its birb is still there
+        let states = vec![
+            State { name: "a".to_string(), settings: settings(&jobs[0]), status: Status::Done, seconds: Some(12), output: Some("Cargo.toml".to_string()) },
+            State { name: "job \"2\"".to_string(), settings: settings(&jobs[1]), status: Status::Failed, seconds: Some(1), output: None },
+        ];
+        assert_eq!(read_state(&write_state(&states, "jobs.toml")), states);
+
+        assert!(is_done(&settings(&jobs[0]), Some(&states[0])));
+        assert!(!is_done(&settings(&jobs[1]), Some(&states[0])) && !is_done(&settings(&jobs[1]), Some(&states[1])) && !is_done(&settings(&jobs[0]), None));
+        assert!(!is_done(&settings(&jobs[0]), Some(&State { output: Some("missing.birb".to_string()), ..states[0].clone() })));
+
+        // A whole queue of tiny renders, one of which fails, without stopping the others
+        let jobs = dir.file("jobs.toml");
+        std::fs::write(&jobs, format!("concurrency = 2\n[defaults]\nwidth = 8\nheight = 8\nsamples = 100\niterations = 10\nthreads = 1\ninterval = 0\n\n[[job]]\nname = \"a\"\nfilename = \"{}\"\n[[job]]\nname = \"broken\"\nappend = \"{}\"\n[[job]]\nname = \"b\"\nfilename = \"{}\"\n", dir.file("a.birb"), dir.file("missing.birb"), dir.file("b.birb"))).unwrap();
+
+        let queue = |jobs:&str| { let args = args(&["queue", "--quiet", jobs]); std::thread::spawn(move || run(args)).join().is_ok() };
+        let status = || read_state(&std::fs::read_to_string(dir.file("jobs.state.toml")).unwrap()).into_iter().map(|s| (s.name, s.status)).collect::<Vec<(String, Status)>>();
+        let done = vec![("a".to_string(), Status::Done), ("broken".to_string(), Status::Failed), ("b".to_string(), Status::Done)];
+
+        assert!(!queue(&jobs));
+        assert_eq!(status(), done);
+        assert!(std::path::Path::new(&dir.file("a.birb")).exists() && std::path::Path::new(&dir.file("b.birb")).exists());
+
+        // Started again, it skips the birbs, that are done. Rendering them again would fail, as
+        // they exist already.
+        assert!(!queue(&jobs));
+        assert_eq!(status(), done);
+
+    }
+
+    #[test]
+    fn logging_output() {
+
+        use super::log::*;
+        use std::time::{Duration, Instant};
+
+        // The colours come out for pipes and log files
+        assert_eq!(plain("\x1B[31;1mError:\x1B[0m The birb \x1B[34ma.birb\x1B[0m is gone"), "Error: The birb a.birb is gone");
+        assert_eq!(plain("no colours"), "no colours");
+
+        assert_eq!(json_string("a \"b\"\\\n\x01"), "\"a \\\"b\\\"\\\\\\n\\u0001\"");
+        assert_eq!((json_number(1.5), json_number(f64::INFINITY)), ("1.5".to_string(), "null".to_string()));
+
+        // A tick of the JSON log has the threads and the totals
+        let msg = vec![Some((0, 300, 3000, false)), Some((1, 100, 1000, true))];
+        let mut throughput = super::Throughput::new(2);
+        throughput.update(&msg, 2f64);
+
+        let fields = super::status_json(&msg, 400, 1000, Instant::now() - Duration::from_secs(2), Duration::from_secs(u64::MAX), &throughput);
+        let field = |key:&str| fields.iter().find(|(k, _)| *k == key).map(|(_, v)| v.clone()).unwrap();
+
+        assert_eq!((field("done"), field("left"), field("total"), field("timeout")), ("400".to_string(), "600".to_string(), "1000".to_string(), "null".to_string()));
+        assert_eq!((field("rate"), field("deposit_rate"), field("timeout_left"), field("projected")), ("200".to_string(), "2000".to_string(), "null".to_string(), "null".to_string()));
+        assert_eq!(field("eta"), "3");
+        assert_eq!(field("threads"), "[{\"thread\":0,\"done\":300,\"share\":30,\"rate\":150,\"deposit_rate\":1500,\"finished\":false},{\"thread\":1,\"done\":100,\"share\":10,\"rate\":50,\"deposit_rate\":500,\"finished\":true}]");
+
+    }
+
+    #[test]
+    fn progress() {
+
+        use std::time::Duration;
+
+        // Two threads, two seconds into the render
+        let msg = vec![Some((0, 300, 3000, false)), Some((1, 100, 1000, true))];
+        let mut throughput = super::Throughput::new(2);
+        throughput.update(&msg, 2f64);
+
+        // The rates follow slowly, a stall of a second doesn't make the ETA jump to forever
+        let mut rate = super::Rate::new();
+        rate.update(1000, 0, 10f64);
+        rate.update(1000, 0, 11f64);
+        assert!(rate.samples > 90f64 && rate.samples < 100f64);
+
+        // A timeout, that comes before the samples are done, projects what's done by then
+        let progress = super::Progress::new(400, 1000, 2f64, Duration::from_secs(4), &throughput.all);
+        assert_eq!((progress.eta, progress.timeout_left, progress.projected), (Some(3f64), Some(2f64), Some(800f64)));
+        assert_eq!(super::Progress::new(400, 1000, 2f64, Duration::from_secs(100), &throughput.all).projected, Some(1000f64));
+        assert_eq!(super::Progress::new(0, 1000, 0f64, Duration::from_secs(u64::MAX), &super::Rate::new()).eta, None);
+
+        // Durations read like durations, not like 1.8e19 seconds
+        use super::estimate::human_duration;
+        assert_eq!((human_duration(4.25), human_duration(42.0), human_duration(185.0)), ("4.2s".to_string(), "42s".to_string(), "3m 05s".to_string()));
+        assert_eq!((human_duration(7509.0), human_duration(200000.0), human_duration(f64::INFINITY)), ("2h 05m 09s".to_string(), "2d 07h 33m".to_string(), "forever".to_string()));
+
+    }
+
 }
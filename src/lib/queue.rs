@@ -0,0 +1,413 @@
+//! `birb queue`: renders one after the other, or a few at a time, from a file of jobs
+//!
+//! The jobs file is a configuration file, see the `config` module, with a table for every job:
+//!
+//! ```toml
+//! concurrency = 2             # how many jobs to run at a time, 1 unless given
+//!
+//! [defaults]                  # beneath the options of every job
+//! iterations = 1000
+//! threads    = 8
+//!
+//! [[job]]
+//! name     = "seahorse"       # 'job 1', 'job 2' and so on, unless given
+//! preset   = "deep"           # a --preset and a --config, both optional
+//! config   = "seahorse.toml"
+//! filename = "seahorse_{seed}.birb"
+//! samples  = 100_000_000
+//!
+//! [[job]]
+//! center = [-1.25, 0]
+//! ```
+//!
+//! Every job is a `butterbrot::render()` of its own. What became of it, is written to a state file
+//! next to the jobs file after every change, `jobs.state.toml` for `jobs.toml`: whether it's
+//! running, done or failed, how long it took, and the birb it wrote. A queue, that's started again,
+//! skips the jobs, that are done, as long as their options, with the ones of their preset and
+//! config, are still the same and their birb is still there. A job, that fails, is recorded as
+//! failed, and the others go on. Jobs, that run at the same time, can't write the same birb, see
+//! `io::Reservation`. The state file is TOML, too, so it can be read by anything.
+
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use super::cli::{self, Command, Opt, Positional};
+use super::config::{self, Value};
+use super::butterbrot;
+use super::io::AtomicFile;
+use super::log::{self, fail};
+
+/// `birb queue`
+pub fn command() -> Command {
+
+    let mut options = vec![
+            Opt::value("concurrency", &["<number>"], "How many jobs to run at a time. Every job still uses its own --threads, so with more than one, fewer threads each are a good idea. Their logs are interleaved.\n\nWithout it, it's the concurrency of the jobs file, or 1.")
+                .short('j').integer(1, 1024),
+            Opt::value("state", &["<file>"], "Where to keep the state of the queue.\n\nWithout it, it's next to the jobs file, like jobs.state.toml for jobs.toml."),
+            Opt::flag("overwrite", "Replace the birbs of the jobs, if they exist already. Without it, a job, whose birb exists already, fails."),
+    ];
+    options.extend(butterbrot::options().into_iter().filter(|o| LOG_OPTIONS.contains(&o.name)));
+
+    Command {
+        name: "queue",
+        about: "Render a file of jobs, one after the other, or a few at a time",
+        help: "Runs the renders of a jobs file, a TOML or JSON configuration file with a [[job]] table for every render, whose keys are the options of 'birb render', like in a --config. A job may also have a name, a preset and a config, and a [defaults] table sets options for all of them. Only --log-format and --quiet are the same for all the jobs, they're flags of the queue. What became of every job is written to a state file after every change: whether it's running, done or failed, how long it took, and the birb it wrote. A queue, that's started again, skips the jobs, that are done, as long as their options are the same and their birb is still there, and a job, that fails, doesn't stop the others.",
+        options,
+        positionals: vec![
+            Positional { name: "<jobs>", required: true, many: false, help: "The jobs file." },
+        ],
+    }
+
+}
+
+/// The keys of a job, that aren't options of the render
+const JOB_KEYS: [&str; 3] = ["name", "preset", "config"];
+
+/// The options of a render, that are the queue's, as how to log is the same for the whole process,
+/// see the `log` module
+const LOG_OPTIONS: [&str; 2] = ["log-format", "quiet"];
+
+
+/* Jobs */
+
+/// A job of the queue
+#[derive(Clone, Debug, PartialEq)]
+pub struct Job {
+    pub name: String,
+    pub table: Value,          // the table of the job, with the defaults beneath it
+}
+
+/// the jobs of the jobs file `jobs`, with the `[defaults]` beneath each of them, and its concurrency
+pub fn jobs(jobs:&Value, what:&str) -> (Vec<Job>, Option<u64>) {
+
+    let table = |value:Option<&Value>, name:&str| -> Vec<(String, Value)> {
+        match value {
+            Some(Value::Table(t)) => t.clone(),
+            None                  => Vec::new(),
+            Some(_)               => fail(format!("'{}' in {} should be a table.", name, what)),
+        }
+    };
+
+    let defaults = table(jobs.get("defaults"), "defaults");
+
+    let list = match jobs.get("job") {
+        Some(Value::Array(list)) => list.clone(),
+        _                        => fail(format!("There are no jobs in {}, add them as [[job]] tables.", what)),
+    };
+
+    if let Value::Table(entries) = jobs {
+        if let Some((key, _)) = entries.iter().find(|(k, _)| !["concurrency", "defaults", "job"].contains(&k.as_ref())) {
+            fail(format!("There's no '{}' in a jobs file, only 'concurrency', '[defaults]' and '[[job]]'. The options of the renders go into the jobs, or the defaults.", key))
+        }
+    }
+
+    let concurrency = match jobs.get("concurrency") {
+        Some(Value::Number(n)) => Some(n.parse::<u64>().ok().filter(|&n| n >= 1).unwrap_or_else(|| fail(format!("The concurrency in {} should be a whole number, at least 1.", what)))),
+        None                   => None,
+        Some(_)                => fail(format!("The concurrency in {} should be a whole number, at least 1.", what)),
+    };
+
+    let jobs = list.iter().enumerate().map(|(k, job)| {
+
+        let mut entries = defaults.clone();
+        for (key, value) in table(Some(job), "job") {
+            entries.retain(|(k, _)| *k != key);
+            entries.push((key, value));
+        }
+
+        let name = match entries.iter().find(|(k, _)| k == "name") {
+            Some((_, Value::Text(name))) => name.clone(),
+            Some(_)                      => fail(format!("The name of job {} in {} should be a string.", k + 1, what)),
+            None                         => format!("job {}", k + 1),
+        };
+
+        Job { name, table: Value::Table(entries) }
+
+    }).collect::<Vec<Job>>();
+
+    for (k, job) in jobs.iter().enumerate() {
+        if jobs[..k].iter().any(|j| j.name == job.name) {
+            fail(format!("There are two jobs called '{}' in {}, their names have to differ.", job.name, what))
+        }
+    }
+
+    (jobs, concurrency)
+
+}
+
+/// a hash of the options of the render `job` stands for, to see, whether it changed since it was
+/// done
+///
+/// It's a hash of the command line of the render, as the preset, the config and the job make it up,
+/// so it changes with the files of the preset and the config, too, but not with the environment.
+pub fn settings(job:&Job) -> String {
+    hash(&butterbrot::resolve(&arguments(job, false)).arguments(&["preset", "config"]).join("\n"))
+}
+
+/// `text` hashed with 64 bit FNV-1a, as hex digits, which are the same on every machine and in
+/// every version, unlike the hashers of the standard library
+fn hash(text:&str) -> String {
+    let h = text.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3));
+    format!("{:016x}", h)
+}
+
+/// the command line of `birb render`, that `job` stands for, the name of the program first
+///
+/// Options, that aren't ones of a render, and values, that aren't right, stop with an error, like
+/// they do in a --config.
+pub fn arguments(job:&Job, overwrite:bool) -> Vec<String> {
+
+    let entries = match &job.table { Value::Table(t) => t.clone(), _ => Vec::new() };
+    let what = format!("the job '{}'", job.name);
+
+    let mut args = vec![cli::PROGRAM.to_string()];
+
+    for key in ["preset", "config"].iter() {
+        match entries.iter().find(|(k, _)| k == key) {
+            Some((_, Value::Text(v))) => args.extend(vec![format!("--{}", key), v.clone()]),
+            Some(_)                   => fail(format!("The {} of {} should be a string.", key, what)),
+            None                      => (),
+        }
+    }
+
+    let options = Value::Table(entries.into_iter().filter(|(k, _)| !JOB_KEYS.contains(&k.as_ref())).collect());
+
+    for (name, values) in config::merge(vec![config::options(&options, &butterbrot::command(), &what)]) {
+        if name == "dry-run" || name == "print-config" {
+            fail(format!("'{}' doesn't render anything, it can't be used in {}.", name, what))
+        }
+        if LOG_OPTIONS.contains(&name.as_ref()) {
+            fail(format!("'{}' is the same for all the jobs, it can't be set in {}. Give it to the queue, like 'birb queue --{0}'.", name, what))
+        }
+        args.push(format!("--{}", name));
+        args.extend(values);
+    }
+
+    if overwrite { args.push("--overwrite".to_string()) }
+
+    args
+
+}
+
+
+/* The State */
+
+/// What became of a job
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Waiting,
+    Running,
+    Done,
+    Failed,
+}
+
+impl Status {
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Waiting => "waiting",
+            Status::Running => "running",
+            Status::Done    => "done",
+            Status::Failed  => "failed",
+        }
+    }
+
+    fn from_name(name:&str) -> Option<Status> {
+        [Status::Waiting, Status::Running, Status::Done, Status::Failed].iter().find(|s| s.name() == name).cloned()
+    }
+
+}
+
+/// The state of a job, as it's in the state file
+#[derive(Clone, Debug, PartialEq)]
+pub struct State {
+    pub name: String,
+    pub settings: String,
+    pub status: Status,
+    pub seconds: Option<u64>,       // how long it took, once it's done or failed
+    pub output: Option<String>,     // the birb it wrote, once it's done
+}
+
+/// the states in the state file `text`, the ones, that can't be read, left out
+pub fn read_state(text:&str) -> Vec<State> {
+
+    let jobs = match config::toml(text).ok().and_then(|v| v.get("job").cloned()) {
+        Some(Value::Array(jobs)) => jobs,
+        _                        => return Vec::new(),
+    };
+
+    let text = |job:&Value, key:&str| match job.get(key) { Some(Value::Text(s)) => Some(s.clone()), _ => None };
+
+    jobs.iter().filter_map(|job| {
+        Some(State {
+            name: text(job, "name")?,
+            settings: text(job, "settings")?,
+            status: Status::from_name(&text(job, "status")?)?,
+            seconds: match job.get("seconds") { Some(Value::Number(n)) => n.parse().ok(), _ => None },
+            output: text(job, "output"),
+        })
+    }).collect()
+
+}
+
+/// the `states` of the queue of the jobs file `jobs`, as the text of its state file
+pub fn write_state(states:&[State], jobs:&str) -> String {
+
+    let mut out = format!("# The state of the queue '{}', written by 'birb queue'\n", jobs);
+    let quote = |s:&str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+
+    for s in states.iter() {
+        write!(out, "\n[[job]]\nname     = {}\nsettings = {}\nstatus   = {}\n", quote(&s.name), quote(&s.settings), quote(s.status.name())).unwrap();
+        if let Some(seconds) = s.seconds { writeln!(out, "seconds  = {}", seconds).unwrap() }
+        if let Some(output) = &s.output { writeln!(out, "output   = {}", quote(output)).unwrap() }
+    }
+
+    out
+
+}
+
+/// writes the `states` to the state file `path`, replacing it in one go
+fn save(states:&[State], path:&str, jobs:&str) {
+
+    let saved = AtomicFile::create(path).and_then(|mut file| {
+        std::io::Write::write_all(&mut file, write_state(states, jobs).as_bytes())?;
+        file.commit()
+    });
+
+    // The jobs go on, even if their state can't be kept
+    if let Err(e) = saved {
+        warning!("Couldn't write the state of the queue to '{}': {}", path, e);
+    }
+
+}
+
+/// whether the job, that was `before`, is done, with the same `settings`, and its birb still there
+pub fn is_done(settings:&str, before:Option<&State>) -> bool {
+    match before {
+        Some(State { status: Status::Done, settings: done, output: Some(output), .. }) => done == settings && std::path::Path::new(output).exists(),
+        _                                                                      => false,
+    }
+}
+
+
+/* Running */
+
+/// runs the queue of the jobs file, that the command line `args` names, the name of the program
+/// first
+pub fn run(args:Vec<String>) {
+
+    let m = cli::parse(&command(), &args[1..]);
+
+    let file = m.positionals[0].clone();
+    let (jobs, concurrency) = jobs(&config::read(&file), &format!("'{}'", file));
+
+    let concurrency = m.get::<u64>("concurrency").or(concurrency).unwrap_or(1) as usize;
+    let overwrite = m.flag("overwrite");
+
+    // How to log is set for the whole process, by every render. They all set the queue's, so jobs,
+    // that run at the same time, don't change it for each other.
+    let format = m.value("log-format").unwrap_or("text").to_string();
+    let mut logging = vec!["--log-format".to_string(), format.clone()];
+    if m.flag("quiet") { logging.push("--quiet".to_string()) }
+    log::set(if format == "json" { log::Format::Json } else { log::Format::Text }, m.flag("quiet"));
+
+    let state_file = m.value("state").map(|s| s.to_string()).unwrap_or_else(|| {
+        let stem = file.rsplit_once('.').filter(|(stem, _)| !stem.is_empty() && !stem.ends_with('/')).map(|(stem, _)| stem).unwrap_or(&file);
+        format!("{}.state.toml", stem)
+    });
+
+    // What was done before, the jobs, that aren't in the jobs file anymore, left out. Jobs, whose
+    // options aren't right, stop the queue here, before anything is rendered.
+    let before = std::fs::read_to_string(&state_file).map(|t| read_state(&t)).unwrap_or_default();
+
+    let states: Vec<State> = jobs.iter().map(|job| {
+        let settings = settings(job);
+        let was = before.iter().find(|s| s.name == job.name);
+        match was {
+            Some(s) if is_done(&settings, was) => s.clone(),
+            _ => State { name: job.name.clone(), settings, status: Status::Waiting, seconds: None, output: None },
+        }
+    }).collect();
+
+    let todo: Vec<usize> = (0..jobs.len()).filter(|&k| states[k].status != Status::Done).collect();
+
+    errln!("Running {y}{}{w} of {y}{}{w} jobs, {y}{}{w} at a time, keeping their state in \x1B[34m{}\x1B[0m", todo.len(), jobs.len(), concurrency, state_file, y = "\x1B[33m", w = "\x1B[0m");
+    for s in states.iter().filter(|s| s.status == Status::Done) {
+        errln!("Skipping \x1B[34m{}\x1B[0m, it's done already", s.name);
+    }
+
+    save(&states, &state_file, &file);
+
+    let states = Arc::new(Mutex::new(states));
+    let todo   = Arc::new(Mutex::new(todo.into_iter()));
+    let jobs   = Arc::new(jobs);
+
+    // Every runner takes the next job, until there are none left
+    let runners: Vec<_> = (0..concurrency).map(|_| {
+
+        let (states, todo, jobs, state_file, file, logging) = (Arc::clone(&states), Arc::clone(&todo), Arc::clone(&jobs), state_file.clone(), file.clone(), logging.clone());
+
+        std::thread::spawn(move || {
+
+            let update = |k:usize, status:Status, seconds:Option<u64>, output:Option<String>| {
+                let mut states = states.lock().unwrap_or_else(|e| e.into_inner());
+                states[k].status  = status;
+                states[k].seconds = seconds;
+                states[k].output  = output;
+                save(&states, &state_file, &file);
+            };
+
+            loop {
+
+                let next = todo.lock().unwrap_or_else(|e| e.into_inner()).next();
+                let k = match next { Some(k) => k, None => break };
+
+                let job = &jobs[k];
+                let mut args = arguments(job, overwrite);
+                args.extend(logging.clone());
+
+                errln!("\nStarting \x1B[34m{}\x1B[0m", job.name);
+                update(k, Status::Running, None, None);
+
+                // A job, that fails, stops with `log::fail()`, like a render does, which ends its thread,
+                // not the queue. Bugs, that panic otherwise, still get reported as usual.
+                let started = Instant::now();
+                let rendered = std::thread::spawn(move || butterbrot::render(&butterbrot::command(), args)).join();
+                let seconds = Some(started.elapsed().as_secs());
+
+                match rendered {
+                    Ok(output) => {
+                        errln!("\x1B[32mDone with\x1B[0m \x1B[34m{}\x1B[0m, it's in \x1B[34m{}\x1B[0m", jobs[k].name, output);
+                        update(k, Status::Done, seconds, Some(output));
+                    },
+                    Err(_) => {
+                        errln!("\x1B[31;1mFailed:\x1B[0m \x1B[34m{}\x1B[0m, going on with the others", jobs[k].name);
+                        update(k, Status::Failed, seconds, None);
+                    },
+                }
+
+            }
+
+        })
+
+    }).collect();
+
+    for runner in runners { let _ = runner.join(); }
+
+    // Sum it up
+    let states = states.lock().unwrap_or_else(|e| e.into_inner());
+    let column = states.iter().map(|s| s.name.len()).max().unwrap_or(0);
+
+    out!();
+    for s in states.iter() {
+        let colour = if s.status == Status::Done { "\x1B[32m" } else { "\x1B[31m" };
+        out!("  {:w$}  {}{:7}\x1B[0m {:>8}  {}", s.name, colour, s.status.name(), s.seconds.map(|t| format!("{}s", t)).unwrap_or_default(), s.output.clone().unwrap_or_default(), w = column);
+    }
+
+    let failed = states.iter().filter(|s| s.status != Status::Done).count();
+    if failed > 0 {
+        fail(format!("{} of {} jobs failed, see '{}'. Start the queue again, to run only those.", failed, states.len(), state_file))
+    }
+
+}
@@ -0,0 +1,277 @@
+//! Out-of-core counters, for birbs larger than the memory of the machine
+//!
+//! A 60000x40000 poster birb of `u64`s takes 19.2 GB, which doesn't fit into the 16 GB of a
+//! workstation, not even as `u32`s together with everything else. `TiledCounters` cut the birb into
+//! square tiles of `TILE` by `TILE` counters, keep only as many of them in memory as they're
+//! allowed to, and spill the rest to a scratch file.
+//!
+//! Hits land all over the birb, so depositing them one by one would mean loading a tile for almost
+//! every single one. Instead the deposits are collected, and applied in one go, once there are
+//! `BATCH` of them: first to the tiles, that are in memory anyway, then tile by tile to the others.
+//!
+//! The result is written as a tiled birb, see `io::write_tiled()`, straight from the tiles, so the
+//! whole birb never needs to be in memory at once.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use super::counters::Accumulator;
+
+/// The width and height of a tile, in counters
+pub const TILE: u64 = 256;
+
+/// How many deposits are collected, before they're applied to the tiles (that's 64 MB of them)
+const BATCH: usize = 1 << 22;
+
+/// how much memory the collected deposits take up, at the most
+pub fn batch_bytes() -> u64 {
+    (BATCH * std::mem::size_of::<Pending>()) as u64
+}
+
+/// a deposit, that hasn't been applied to its tile yet
+#[derive(Clone, Copy, Debug)]
+struct Pending {
+    offset: u32,        // index of the counter in its tile
+    wrapping: bool,     // whether it's a two's complement, see `Accumulator::wrapping_add()`
+    n: u64,
+}
+
+/// a tile of `TiledCounters`, `TILE * TILE` counters per channel
+///
+/// The tiles at the right and bottom edges of the birb stick out over its edges. Those counters
+/// are simply never used.
+#[derive(Debug)]
+struct Tile {
+    cells: Option<Vec<u64>>,    // the counters, if the tile is in memory
+    spilled: bool,              // whether the tile has been written to the scratch file
+    pending: Vec<Pending>,
+    used: u64,                  // when the tile was last used, to find the one to spill
+}
+
+/// the counters of a birb, in tiles, only a bounded number of which are kept in memory
+///
+/// The tiles, that don't fit into memory, go to a scratch file, which is removed again, once the
+/// `TiledCounters` are dropped. The counters are `u64`s. When the birb is written, the narrowest
+/// `CounterWidth`, that holds all of them, is used.
+#[derive(Debug)]
+pub struct TiledCounters {
+    width: u64,
+    height: u64,
+    channels: u64,
+
+    columns: u64,               // how many tiles there are in a row
+    tiles: Vec<Tile>,
+
+    resident: Vec<usize>,       // the tiles in memory
+    max_resident: usize,
+    clock: u64,
+    pending: usize,
+
+    scratch: File,
+    scratch_path: PathBuf,
+
+    max: u64,                   // the largest counter so far
+    signed: bool,               // whether there have been any two's complements
+}
+
+impl TiledCounters {
+
+    /// creates `channels` channels of `width * height` counters, all 0, which keep no more than
+    /// about `memory` bytes in memory, and spill the rest to the file `scratch_path`
+    ///
+    /// At least one tile is always kept in memory, however small `memory` is. The collected
+    /// deposits take up to 64 MB on top.
+    pub fn new(width:u64, height:u64, channels:u64, memory:u64, scratch_path:&str) -> TiledCounters {
+
+        let columns = width.div_ceil(TILE);
+        let rows    = height.div_ceil(TILE);
+
+        let tile_bytes = TILE * TILE * channels * 8;
+
+        let scratch = error!(OpenOptions::new().read(true).write(true).create(true).truncate(true).open(scratch_path), "Couldn't create the scratch file for the tiles.", full);
+
+        TiledCounters {
+            width,
+            height,
+            channels,
+            columns,
+            tiles: (0..columns * rows).map(|_| Tile { cells: None, spilled: false, pending: Vec::new(), used: 0 }).collect(),
+            resident: Vec::new(),
+            max_resident: (memory / tile_bytes).max(1) as usize,
+            clock: 0,
+            pending: 0,
+            scratch,
+            scratch_path: PathBuf::from(scratch_path),
+            max: 0,
+            signed: false,
+        }
+
+    }
+
+    pub fn channels(&self) -> u64 {
+        self.channels
+    }
+
+    /// how many tiles there are in total
+    pub fn tile_count(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// how many counters a tile has, across all channels
+    pub fn tile_len(&self) -> usize {
+        (TILE * TILE * self.channels) as usize
+    }
+
+    /// the largest counter so far, and whether any of them hold two's complements
+    pub fn max(&self) -> (u64, bool) {
+        (self.max, self.signed)
+    }
+
+    /// the tile and the index in it, that the birb `index` is at, see `Accumulator`
+    #[inline]
+    fn locate(&self, index:usize) -> (usize, u32) {
+
+        let n = self.width * self.height;
+        let i = index as u64 - 2;
+
+        let (channel, p) = (i / n, i % n);
+        let (y, x)       = (p / self.width, p % self.width);
+
+        let tile   = (y / TILE) * self.columns + x / TILE;
+        let offset = channel * TILE * TILE + (y % TILE) * TILE + x % TILE;
+
+        (tile as usize, offset as u32)
+
+    }
+
+    #[inline]
+    fn deposit(&mut self, index:usize, n:u64, wrapping:bool) {
+
+        let (tile, offset) = self.locate(index);
+
+        self.tiles[tile].pending.push(Pending { offset, wrapping, n });
+        self.pending += 1;
+
+        if self.pending >= BATCH { self.flush() }
+
+    }
+
+    /// applies all the collected deposits, starting with the tiles, that are in memory
+    pub fn flush(&mut self) {
+
+        let mut order: Vec<usize> = (0..self.tiles.len()).filter(|&t| !self.tiles[t].pending.is_empty()).collect();
+
+        // Stable, so the others stay in the order they're in the scratch file
+        order.sort_by_key(|&t| self.tiles[t].cells.is_none());
+
+        for t in order {
+
+            self.load(t);
+
+            let pending = std::mem::take(&mut self.tiles[t].pending);
+            let cells   = self.tiles[t].cells.as_mut().unwrap();
+
+            for p in pending.iter() {
+                let cell = &mut cells[p.offset as usize];
+                if p.wrapping {
+                    *cell = cell.wrapping_add(p.n);
+                    self.signed = true;
+                } else {
+                    *cell = cell.saturating_add(p.n);
+                    self.max = self.max.max(*cell);
+                }
+            }
+
+        }
+
+        self.pending = 0;
+
+    }
+
+    /// makes sure the tile `t` is in memory, spilling the least recently used one, if need be
+    fn load(&mut self, t:usize) {
+
+        self.clock += 1;
+        self.tiles[t].used = self.clock;
+
+        if self.tiles[t].cells.is_some() { return }
+
+        if self.resident.len() >= self.max_resident {
+
+            let (k, &lru) = self.resident.iter().enumerate().min_by_key(|(_, &r)| self.tiles[r].used).unwrap();
+            self.resident.swap_remove(k);
+
+            let cells = self.tiles[lru].cells.take().unwrap();
+            self.write_slot(lru, &cells);
+            self.tiles[lru].spilled = true;
+
+        }
+
+        let cells = if self.tiles[t].spilled { self.read_slot(t) } else { vec![0; self.tile_len()] };
+
+        self.tiles[t].cells = Some(cells);
+        self.resident.push(t);
+
+    }
+
+    /// calls `f` with the counters of the tile `t`, wherever they are, without loading it
+    ///
+    /// Call `flush()` first, or the collected deposits are missing.
+    pub fn with_tile<F: FnMut(&[u64])>(&mut self, t:usize, mut f:F) {
+
+        if let Some(cells) = &self.tiles[t].cells { return f(cells) }
+
+        let cells = if self.tiles[t].spilled { self.read_slot(t) } else { vec![0; self.tile_len()] };
+
+        f(&cells)
+
+    }
+
+    fn write_slot(&mut self, t:usize, cells:&[u64]) {
+
+        let bytes: Vec<u8> = cells.iter().flat_map(|n| n.to_le_bytes()).collect();
+
+        error!(self.scratch.seek(SeekFrom::Start((t * bytes.len()) as u64)), "Couldn't write a tile to the scratch file.", full);
+        error!(self.scratch.write_all(&bytes), "Couldn't write a tile to the scratch file.", full);
+
+    }
+
+    fn read_slot(&mut self, t:usize) -> Vec<u64> {
+
+        let mut bytes = vec![0u8; self.tile_len() * 8];
+
+        error!(self.scratch.seek(SeekFrom::Start((t * bytes.len()) as u64)), "Couldn't read a tile from the scratch file.", full);
+        error!(self.scratch.read_exact(&mut bytes), "Couldn't read a tile from the scratch file.", full);
+
+        bytes.chunks(8).map(super::io::word).collect()
+
+    }
+
+}
+
+impl Accumulator for TiledCounters {
+
+    fn width(&self) -> u64 { self.width }
+
+    fn height(&self) -> u64 { self.height }
+
+    #[inline]
+    fn add(&mut self, index:usize, n:u64) {
+        self.deposit(index, n, false)
+    }
+
+    #[inline]
+    fn wrapping_add(&mut self, index:usize, n:u64) {
+        self.deposit(index, n, true)
+    }
+
+}
+
+impl Drop for TiledCounters {
+
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.scratch_path);
+    }
+
+}